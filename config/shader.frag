@@ -2,10 +2,23 @@
 
 layout(location = 0) in vec4 inColor;
 layout(location = 1) in float d;
+layout(location = 2) in float line_distance;
+layout(location = 3) flat in vec4 dasharray;
 
 layout(location = 0) out vec4 outColor;
 
 void main() {
+    // Leave out the gaps after the two dashes.
+    float period = dasharray.x + dasharray.y + dasharray.z + dasharray.w;
+    if(period > 0.0) {
+        float position = mod(line_distance, period);
+        bool in_first_gap = position >= dasharray.x && position < dasharray.x + dasharray.y;
+        bool in_second_gap = position >= dasharray.x + dasharray.y + dasharray.z;
+        if(in_first_gap || in_second_gap) {
+            discard;
+        }
+    }
+
     outColor = inColor;
     outColor.a = outColor.a * 1-abs(d);
 }
\ No newline at end of file
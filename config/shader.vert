@@ -4,16 +4,22 @@
 layout(location = 0) in ivec2 position;
 layout(location = 1) in ivec2 normal;
 layout(location = 2) in uint feature;
+layout(location = 3) in float vertex_distance;
 
 layout(location = 0) out vec4 outColor;
 layout(location = 1) out float d;
+layout(location = 2) out float line_distance;
+layout(location = 3) flat out vec4 dasharray;
 
 layout(std140) struct LayerData {
     vec4 background_color;
     vec4 outline_color;
     float border_width;
-    uint line_width;
+    float line_width;
+    // The display flag, the line cap, the line join and the world scale flag, one byte each.
+    uint line_style;
     float z_index;
+    vec4 line_dasharray;
 };
 
 layout(std140, set = 0, binding = 0) uniform Locals {
@@ -42,19 +48,28 @@ void main() {
 
     uint feature_id = feature & 0xFFFF;
     uint feature_type = (feature >> 16) & 0x3;
+    uint line_part = (feature >> 18) & 0x7;
 
     // Shortcut the array indexing.
     LayerData layer_data = layer_datas[feature_id];
     TileData tile_data = tile_datas[tile_id];
 
     // Is the line we are currently handling sized in world coordinates or pixels?
-    bool is_world_scale_line = (layer_data.line_width & 0x02) == 1;
+    bool is_world_scale_line = ((layer_data.line_style >> 24) & 0xFF) == 1;
     bool is_line = feature_type == 1;
 
-    float line_width = layer_data.line_width >> 2;
+    float line_width = layer_data.line_width;
+
+    // Collapse the caps and joins the line does not use, see `LinePart`, `LineCap` and `LineJoin`.
+    uint line_cap = (layer_data.line_style >> 8) & 0xFF;
+    uint line_join = (layer_data.line_style >> 16) & 0xFF;
+    bool is_unused = (line_part == 1 && line_cap != 2)
+        || (line_part == 2 && line_cap != 1)
+        || (line_part == 3 && line_join != 0)
+        || (line_part == 4 && line_join != 2);
 
     // Calculate the tile normal normal in [0.0, 1.0] coordinates.
-    vec2 local_normal = normal / tile_data.extent;
+    vec2 local_normal = is_unused ? vec2(0.0) : normal / tile_data.extent;
     if(is_line) {
         d = sign(local_normal.y);
     } else {
@@ -72,6 +87,14 @@ void main() {
     // Transform the vertex.
     gl_Position = tile_data.transform * tile_local_position;
 
+    // Measure the distance along the line in line widths for the dashes.
+    vec4 tile_unit = tile_data.transform * vec4(1.0 / tile_data.extent, 0.0, 0.0, 0.0);
+    float pixels_per_unit = length(tile_unit.xy * canvas_size / 2.0);
+    // World scale widths are given in tile units, so they scale with the zoom like the distance does.
+    float line_width_pixels = is_world_scale_line ? line_width * pixels_per_unit : line_width;
+    line_distance = vertex_distance * pixels_per_unit / max(line_width_pixels, 1.0);
+    dasharray = is_line ? layer_data.line_dasharray : vec4(0.0);
+
     // If we have a pixel scale line, add the normal to the vertex after the world transform.
     if(is_line && !is_world_scale_line) {
         gl_Position.xy += local_normal / canvas_size * line_width;
@@ -1,9 +1,10 @@
 use crate::drawing::ui::*;
 use crate::*;
-use lyon::math::Point;
+use lyon::math::{Point, Vector};
 use stats::Stats;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
 
 pub struct AppState {
     pub tile_cache: TileCache,
@@ -15,8 +16,30 @@ pub struct AppState {
     pub selected_objects: Vec<EditableObject>,
     pub stats: Stats,
     pub ui: UIState,
+    /// Selects the theme from the position of the sun instead of the configuration.
+    pub automatic_theme: bool,
+    pub own_ship: OwnShip,
+    pub follow: FollowMode,
+    pub ais_targets: AisTargetTable,
+    /// The MMSI of the AIS target under the cursor.
+    pub hovered_target: Option<u32>,
+    /// The MMSI of the AIS target which was clicked last.
+    pub selected_target: Option<u32>,
+    /// The targets which come dangerously close, sorted by the time until their closest point of approach.
+    pub alarms: Vec<CollisionAlarm>,
+    pub routes: RouteBook,
+    /// The index of the route which is shown in the HUD and edited.
+    pub active_route: Option<usize>,
+    /// Places waypoints on the active route with clicks on the map.
+    pub editing_route: bool,
+    /// The index of the waypoint of the active route which is being dragged.
+    pub dragged_waypoint: Option<usize>,
+    nmea: Option<NmeaReader>,
+    ais_decoder: AisDecoder,
+    hidpi_factor: f64,
     visible_tiles: BTreeMap<TileId, VisibleTile>,
     feature_collection: Arc<RwLock<FeatureCollection>>,
+    selection_tags: Vec<String>,
 }
 
 impl AppState {
@@ -28,10 +51,14 @@ impl AppState {
         zoom: f32,
         hidpi_factor: f64,
     ) -> Self {
+        let mut css_cache = RulesCache::load_from_file(style.into());
+        css_cache.set_theme(configured_theme());
         Self {
-            tile_cache: TileCache::new(CONFIG.general.data_root.clone()),
-            css_cache: RulesCache::try_load_from_file(style)
-                .expect("Unable to load the style file. Please consult the log."),
+            tile_cache: TileCache::new(create_tile_source())
+                .with_memory_budget(CONFIG.renderer.tile_memory_budget * 1024 * 1024)
+                .with_loader_threads(CONFIG.renderer.loader_threads),
+            selection_tags: selection_tags(&css_cache),
+            css_cache,
             screen: Screen::new(
                 center,
                 width,
@@ -45,6 +72,20 @@ impl AppState {
             selected_objects: vec![],
             stats: Stats::new(),
             ui: UIState::new(),
+            automatic_theme: CONFIG.renderer.automatic_theme,
+            own_ship: OwnShip::default(),
+            follow: FollowMode::Off,
+            ais_targets: AisTargetTable::new(Duration::from_secs(CONFIG.ais.max_age)),
+            hovered_target: None,
+            selected_target: None,
+            alarms: vec![],
+            routes: load_routes(),
+            active_route: None,
+            editing_route: false,
+            dragged_waypoint: None,
+            nmea: start_nmea_reader(),
+            ais_decoder: AisDecoder::new(),
+            hidpi_factor,
             visible_tiles: BTreeMap::new(),
             feature_collection: Arc::new(RwLock::new(FeatureCollection::new(
                 CONFIG.renderer.max_features as u32,
@@ -61,6 +102,14 @@ impl AppState {
     }
 
     pub fn load_tiles(&mut self) {
+        // Tiles loaded with other selection tags would not match the rules anymore.
+        let selection_tags = selection_tags(&self.css_cache);
+        if selection_tags != self.selection_tags {
+            self.tile_cache.clear();
+            self.visible_tiles.clear();
+            self.selection_tags = selection_tags;
+        }
+
         let tile_field = self.screen.get_tile_boundaries_for_zoom_level(self.zoom, 1);
 
         // Remove old bigger tiles which are not in the FOV anymore.
@@ -78,13 +127,16 @@ impl AppState {
             }
         }
 
+        self.tile_cache
+            .set_tiles_in_use(self.visible_tiles.keys().copied());
         self.tile_cache.finalize_loaded_tiles();
+        self.tile_cache.set_focus(self.screen.center, tile_field);
         for tile_id in tile_field.iter() {
             if !self.visible_tiles.contains_key(&tile_id) {
                 self.tile_cache.request_tile(
                     &tile_id,
                     self.feature_collection.clone(),
-                    &CONFIG.renderer.selection_tags.clone(),
+                    &self.selection_tags,
                 );
 
                 let tile_cache = &mut self.tile_cache;
@@ -144,9 +196,18 @@ impl AppState {
     pub fn update_hovered_objects(&mut self, point: (f32, f32)) {
         self.hovered_objects =
             Collider::get_hovered_objects(&self.visible_tiles, &self.screen, self.zoom, point);
+        // The screen is measured in physical pixels.
+        let hidpi_factor = self.hidpi_factor as f32;
+        self.hovered_target = self.ais_targets.target_at(
+            &self.screen,
+            self.zoom,
+            lyon::math::point(point.0, point.1) * hidpi_factor,
+            12.0 * hidpi_factor,
+        );
     }
 
     pub fn update_selected_hover_objects(&mut self) {
+        self.selected_target = self.hovered_target;
         self.selected_objects = self
             .hovered_objects
             .iter()
@@ -164,12 +225,413 @@ impl AppState {
         }
     }
 
+    /// Writes the edited style rules back to the stylesheets they were loaded from.
+    pub fn save_style(&mut self) {
+        if let Err(err) = self.css_cache.save() {
+            log::error!("Failed to save the style: {}", err);
+        }
+    }
+
+    /// Selects the theme for the position of the sun at the center of the map.
+    ///
+    /// Does nothing unless the theme is automatic.
+    /// The styles are computed again with the next tiles, which are not reloaded.
+    pub fn update_theme(&mut self) {
+        if !self.automatic_theme {
+            return;
+        }
+        let (lat, lon) = global_space_to_deg(&self.screen.center);
+        let elevation = sun_elevation(lat, lon, SystemTime::now());
+        self.css_cache
+            .set_theme(Theme::for_sun_elevation(elevation));
+    }
+
+    /// Takes the sentences received from the NMEA source over into the own ship and the AIS targets
+    /// and moves the map along with the own ship while following it.
+    pub fn update_nmea(&mut self) {
+        if let Some(nmea) = &self.nmea {
+            for sentence in nmea.try_iter() {
+                match &sentence {
+                    Sentence::Vdm(vdm) => match self.ais_decoder.decode(vdm) {
+                        // The own vessel is not a target.
+                        Some(Ok(report)) if !vdm.own => self.ais_targets.update(&report),
+                        Some(Err(NmeaError::UnsupportedMessage(_))) => {}
+                        Some(Err(err)) => log::info!("Ignoring an AIS message: {}", err),
+                        _ => {}
+                    },
+                    sentence => self.own_ship.update(sentence),
+                }
+            }
+        }
+        self.ais_targets.expire();
+        self.update_alarms();
+
+        if self.follow == FollowMode::Off {
+            return;
+        }
+        if let Some(position) = self.own_ship.global_position() {
+            self.screen.center = position;
+        }
+        self.screen.rotation = match self.follow {
+            FollowMode::CourseUp => self
+                .own_ship
+                .course
+                .or(self.own_ship.heading)
+                .unwrap_or(self.screen.rotation),
+            _ => 0.0,
+        };
+    }
+
+    /// Finds the targets which pass the own ship closer than the configured guard thresholds.
+    ///
+    /// Alarms stay acknowledged as long as their target stays dangerous.
+    fn update_alarms(&mut self) {
+        let own_position = match (self.own_ship.position, self.own_ship.has_fix()) {
+            (Some(position), true) => position,
+            _ => {
+                self.alarms.clear();
+                return;
+            }
+        };
+        let own = motion(own_position, self.own_ship.course, self.own_ship.speed);
+        let acknowledged = self
+            .alarms
+            .iter()
+            .filter(|alarm| alarm.acknowledged)
+            .map(|alarm| alarm.mmsi)
+            .collect::<BTreeSet<_>>();
+
+        self.alarms = self
+            .ais_targets
+            .iter()
+            .filter(|target| target.class != AisClass::AidToNavigation)
+            .filter_map(|target| {
+                let position = target.position?;
+                let distance = distance(own_position, position);
+                let approach = match (own, motion(position, target.course, target.speed)) {
+                    (Some(own), Some(target)) => Some(closest_approach(&own, &target)),
+                    _ => None,
+                };
+                let dangerous = match approach {
+                    Some(approach) => {
+                        approach.cpa <= CONFIG.ais.cpa_alarm
+                            && approach.tcpa >= 0.0
+                            && approach.tcpa <= CONFIG.ais.tcpa_alarm
+                    }
+                    // Without a prediction only a target which is close already is known to be dangerous.
+                    None => distance <= CONFIG.ais.cpa_alarm,
+                };
+                Some(CollisionAlarm {
+                    mmsi: target.mmsi,
+                    distance,
+                    approach,
+                    acknowledged: acknowledged.contains(&target.mmsi),
+                })
+                .filter(|_| dangerous)
+            })
+            .collect();
+        // Targets with an unknown approach are close already and come first.
+        self.alarms.sort_by(|a, b| {
+            let tcpa = |alarm: &CollisionAlarm| alarm.approach.map_or(f32::MIN, |a| a.tcpa);
+            tcpa(a).total_cmp(&tcpa(b))
+        });
+    }
+
+    /// Returns the alarm of a target if it comes dangerously close.
+    pub fn alarm(&self, mmsi: u32) -> Option<&CollisionAlarm> {
+        self.alarms.iter().find(|alarm| alarm.mmsi == mmsi)
+    }
+
+    /// Returns the route which is edited.
+    pub fn active_route(&self) -> Option<&Route> {
+        self.routes.routes.get(self.active_route?)
+    }
+
+    fn active_route_mut(&mut self) -> Option<&mut Route> {
+        self.routes.routes.get_mut(self.active_route?)
+    }
+
+    /// Converts a logical position of the cursor into physical pixels.
+    fn cursor_to_pixel(&self, point: (f32, f32)) -> Point {
+        lyon::math::point(point.0, point.1) * self.hidpi_factor as f32
+    }
+
+    /// Converts a logical position of the cursor into degrees.
+    fn cursor_to_deg(&self, point: (f32, f32)) -> LatLon {
+        let pixel = self.cursor_to_pixel(point);
+        global_space_to_deg(&self.screen.pixel_to_global(self.zoom, &pixel))
+    }
+
+    /// Adds a route with a new name and makes it the active one.
+    pub fn new_route(&mut self) {
+        let name = format!("Route {}", self.routes.routes.len() + 1);
+        self.routes.routes.push(Route::new(name));
+        self.active_route = Some(self.routes.routes.len() - 1);
+        self.editing_route = true;
+        self.save_routes();
+    }
+
+    /// Deletes the active route.
+    pub fn delete_route(&mut self) {
+        if let Some(index) = self.active_route.take() {
+            self.routes.routes.remove(index);
+            self.editing_route = false;
+            self.save_routes();
+        }
+    }
+
+    /// Starts dragging the waypoint of the active route under the cursor while editing.
+    ///
+    /// Returns whether a waypoint was grabbed.
+    pub fn grab_waypoint(&mut self, point: (f32, f32)) -> bool {
+        if !self.editing_route {
+            return false;
+        }
+        let pixel = self.cursor_to_pixel(point);
+        let radius = 8.0 * self.hidpi_factor as f32;
+        self.dragged_waypoint = self
+            .active_route()
+            .and_then(|route| route.waypoint_at(&self.screen, self.zoom, pixel, radius));
+        self.dragged_waypoint.is_some()
+    }
+
+    /// Moves the dragged waypoint to the cursor.
+    ///
+    /// Returns whether a waypoint is dragged.
+    pub fn move_dragged_waypoint(&mut self, point: (f32, f32)) -> bool {
+        let index = match self.dragged_waypoint {
+            Some(index) => index,
+            None => return false,
+        };
+        let position = self.cursor_to_deg(point);
+        if let Some(waypoint) = self
+            .active_route_mut()
+            .and_then(|route| route.waypoints.get_mut(index))
+        {
+            *waypoint = position;
+        }
+        true
+    }
+
+    /// Drops the dragged waypoint.
+    ///
+    /// Returns whether a waypoint was dragged.
+    pub fn release_waypoint(&mut self) -> bool {
+        if self.dragged_waypoint.take().is_none() {
+            return false;
+        }
+        self.save_routes();
+        true
+    }
+
+    /// Places a waypoint at the cursor, on the leg under the cursor or after the last waypoint.
+    pub fn place_waypoint(&mut self, point: (f32, f32)) {
+        let pixel = self.cursor_to_pixel(point);
+        let radius = 6.0 * self.hidpi_factor as f32;
+        let position = self.cursor_to_deg(point);
+        let leg = match self.active_route() {
+            Some(route) => route.leg_at(&self.screen, self.zoom, pixel, radius),
+            None => return,
+        };
+        if let Some(route) = self.active_route_mut() {
+            match leg {
+                Some(start) => route.waypoints.insert(start + 1, position),
+                None => route.waypoints.push(position),
+            }
+        }
+        self.save_routes();
+    }
+
+    /// Deletes the waypoint of the active route under the cursor while editing,
+    /// which joins the legs before and after it.
+    pub fn delete_waypoint_at(&mut self, point: (f32, f32)) {
+        if !self.editing_route {
+            return;
+        }
+        let pixel = self.cursor_to_pixel(point);
+        let radius = 8.0 * self.hidpi_factor as f32;
+        if let Some(index) = self
+            .active_route()
+            .and_then(|route| route.waypoint_at(&self.screen, self.zoom, pixel, radius))
+        {
+            self.delete_waypoint(index);
+        }
+    }
+
+    /// Deletes a waypoint of the active route.
+    pub fn delete_waypoint(&mut self, index: usize) {
+        if let Some(route) = self.active_route_mut() {
+            if index < route.waypoints.len() {
+                route.waypoints.remove(index);
+            }
+        }
+        self.save_routes();
+    }
+
+    /// Inserts a waypoint in the middle of a leg of the active route.
+    pub fn split_leg(&mut self, start: usize) {
+        if let Some(route) = self.active_route_mut() {
+            route.split_leg(start);
+        }
+        self.save_routes();
+    }
+
+    /// Writes the routes to the configured file.
+    pub fn save_routes(&self) {
+        if let Err(err) = self.routes.save(&CONFIG.routes.file) {
+            log::error!(
+                "Failed to save the routes to {}: {}",
+                CONFIG.routes.file,
+                err
+            );
+        }
+    }
+
+    /// Follows the own ship in `mode`, turning the map north up again if it does not follow anymore.
+    pub fn set_follow(&mut self, mode: FollowMode) {
+        self.follow = mode;
+        if mode == FollowMode::Off {
+            self.screen.rotation = 0.0;
+        }
+    }
+
+    /// Moves the map by a distance dragged on the screen in pixels, which stops following the own ship.
+    ///
+    /// The map turns north up again.
+    pub fn drag(&mut self, delta: Vector) {
+        self.screen.center -= self.screen.pixels_to_global(self.zoom, delta);
+        self.set_follow(FollowMode::Off);
+    }
+
     pub fn set_center(&mut self, center: (f32, f32)) {
         let tile_coordinate = deg2num(center.0, center.1, self.zoom as u32);
         self.screen.center = num_to_global_space(&tile_coordinate);
     }
 }
 
+/// How the map follows the own ship.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FollowMode {
+    Off,
+    /// Keeps the own ship in the center with north up.
+    NorthUp,
+    /// Keeps the own ship in the center with its course up.
+    CourseUp,
+}
+
+/// Returns the theme configured in the `[renderer]` section of the config.
+fn configured_theme() -> Theme {
+    Theme::from_name(&CONFIG.renderer.theme).unwrap_or_else(|| {
+        log::warn!(
+            "Unknown theme '{}'. Falling back to the day theme.",
+            CONFIG.renderer.theme
+        );
+        Theme::Day
+    })
+}
+
+/// Starts reading the NMEA source configured in the `[nmea]` section of the config.
+fn start_nmea_reader() -> Option<NmeaReader> {
+    if CONFIG.nmea.source.is_empty() {
+        return None;
+    }
+    let replay_interval = Duration::from_secs_f32(CONFIG.nmea.replay_interval.max(0.0));
+    let source = match NmeaSource::from_url(&CONFIG.nmea.source, replay_interval) {
+        Some(source) => source,
+        None => {
+            log::error!(
+                "Unknown NMEA source '{}'. Use serial://, tcp://, udp:// or file://.",
+                CONFIG.nmea.source
+            );
+            return None;
+        }
+    };
+    match NmeaReader::start(&source) {
+        Ok(reader) => Some(reader),
+        Err(err) => {
+            log::error!("Failed to open the NMEA source {}: {}", source, err);
+            None
+        }
+    }
+}
+
+/// Loads the routes from the file configured in the `[routes]` section of the config.
+fn load_routes() -> RouteBook {
+    RouteBook::load(&CONFIG.routes.file).unwrap_or_else(|err| {
+        log::error!(
+            "Failed to load the routes from {}: {}",
+            CONFIG.routes.file,
+            err
+        );
+        RouteBook::default()
+    })
+}
+
+/// Creates the `TileSource` configured in the `[source]` section of the config.
+///
+/// Prefers a MBTiles or PMTiles archive if one is configured and falls back to the tile server.
+fn create_tile_source() -> Box<dyn TileSource> {
+    if !CONFIG.source.mbtiles.is_empty() {
+        if let Some(source) = MbTilesSource::try_open(&CONFIG.source.mbtiles) {
+            return Box::new(source);
+        }
+        log::warn!(
+            "Could not open {}. Falling back to the tile server.",
+            CONFIG.source.mbtiles
+        );
+    }
+
+    if !CONFIG.source.pmtiles.is_empty() {
+        if let Some(source) = PmTilesSource::try_open(&CONFIG.source.pmtiles) {
+            return Box::new(source);
+        }
+        log::warn!(
+            "Could not open {}. Falling back to the tile server.",
+            CONFIG.source.pmtiles
+        );
+    }
+
+    Box::new(
+        HttpTileSource::new(
+            CONFIG.source.url.clone(),
+            CONFIG.source.key.clone(),
+            CONFIG.general.data_root.clone(),
+        )
+        .with_zoom_range(CONFIG.source.min_zoom, CONFIG.source.max_zoom)
+        .with_cache_policy(CONFIG.cache.policy())
+        .with_attribution(CONFIG.source.attribution.clone()),
+    )
+}
+
+/// A target which comes closer than the guard thresholds.
+pub struct CollisionAlarm {
+    pub mmsi: u32,
+    /// The current distance in nautical miles.
+    pub distance: f32,
+    /// The closest point of approach, `None` if the course or speed of a vessel is unknown.
+    pub approach: Option<Approach>,
+    pub acknowledged: bool,
+}
+
+/// Returns the motion of a vessel if its course and speed are known.
+///
+/// The course of a vessel which does not move is not needed.
+fn motion(position: (f32, f32), course: Option<f32>, speed: Option<f32>) -> Option<Motion> {
+    match (course, speed) {
+        (Some(course), Some(speed)) => Some(Motion {
+            position,
+            course,
+            speed,
+        }),
+        (None, Some(speed)) if speed == 0.0 => Some(Motion {
+            position,
+            course: 0.0,
+            speed,
+        }),
+        _ => None,
+    }
+}
+
 pub struct EditableObject {
     pub object: Object,
     pub selected: bool,
@@ -183,3 +645,14 @@ impl EditableObject {
         }
     }
 }
+
+/// Returns the tags objects need in their selector for the configuration and the rules of `css_cache`.
+fn selection_tags(css_cache: &RulesCache) -> Vec<String> {
+    let mut selection_tags = css_cache.selection_tags();
+    for tag in &CONFIG.renderer.selection_tags {
+        if !selection_tags.contains(tag) {
+            selection_tags.push(tag.clone());
+        }
+    }
+    selection_tags
+}
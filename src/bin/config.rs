@@ -9,10 +9,14 @@ pub struct Renderer {
     pub fragment_shader: String,
     pub css: String,
     pub max_tiles: usize,
+    pub tile_memory_budget: usize,
+    pub loader_threads: usize,
     pub max_features: u64,
     pub tile_size: u32,
     pub msaa_samples: u32,
     pub selection_tags: Vec<String>,
+    pub theme: String,
+    pub automatic_theme: bool,
     pub ui_font: String,
     pub temperature: Temperature,
 }
@@ -30,10 +34,46 @@ pub struct General {
     pub data_root: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct Source {
+    pub mbtiles: String,
+    pub pmtiles: String,
+    pub url: String,
+    pub key: String,
+    pub min_zoom: u32,
+    pub max_zoom: u32,
+    pub attribution: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Nmea {
+    pub source: String,
+    pub replay_interval: f32,
+    pub vector_minutes: f32,
+    pub hdop_error: f32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Ais {
+    pub max_age: u64,
+    pub cpa_alarm: f32,
+    pub tcpa_alarm: f32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Routes {
+    pub file: String,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Config {
     pub general: General,
     pub renderer: Renderer,
+    pub source: Source,
+    pub cache: osm::CacheConfig,
+    pub nmea: Nmea,
+    pub ais: Ais,
+    pub routes: Routes,
 }
 
 impl Config {
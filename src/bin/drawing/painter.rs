@@ -323,6 +323,11 @@ impl Painter {
                             offset: 8,
                             shader_location: 2,
                         },
+                        VertexAttributeDescriptor {
+                            format: VertexFormat::Float,
+                            offset: 12,
+                            shader_location: 3,
+                        },
                     ],
                 }],
             },
@@ -347,11 +352,11 @@ impl Painter {
 
         let buffer = feature_collection.assemble_style_buffer();
         let len = buffer.len();
-        let layer_data_len = len.max(1) * 12 * 4;
+        let layer_data_len = len.max(1) * std::mem::size_of::<FeatureStyle>();
         let layer_data_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: None,
             contents: if len == 0 {
-                &[0; 48]
+                &[0; std::mem::size_of::<FeatureStyle>()]
             } else {
                 as_byte_slice(&buffer.as_slice())
             },
@@ -426,7 +431,7 @@ impl Painter {
     }
 
     fn uniform_buffer_size() -> u64 {
-        4 * 4 + 12 * 4 * CONFIG.renderer.max_features
+        4 * 4 + std::mem::size_of::<FeatureStyle>() as u64 * CONFIG.renderer.max_features
     }
 
     pub fn create_blend_bind_group(
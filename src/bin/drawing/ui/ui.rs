@@ -1,5 +1,6 @@
 use crate::app_state::AppState;
 use crate::app_state::EditableObject;
+use crate::app_state::FollowMode;
 use crate::*;
 use imgui::*;
 use wgpu::TextureFormat;
@@ -91,13 +92,47 @@ impl HUD {
         {
             let mouse_pos = ui.io().mouse_pos;
 
+            paint_routes(&ui, app_state);
+            paint_ais_targets(&ui, app_state);
+            paint_own_ship(&ui, app_state);
+
             // Draw menubar.
             ui.main_menu_bar(|| {
                 ui.menu(im_str!("File"), true, || {
+                    let save = imgui::MenuItem::new(im_str!("Save style"))
+                        .shortcut(im_str!("Ctrl + S"))
+                        .enabled(app_state.css_cache.has_unsaved_edits())
+                        .build(&ui);
+                    if save {
+                        app_state.save_style();
+                    }
                     imgui::MenuItem::new(im_str!("Quit"))
                         .shortcut(im_str!("Ctrl + Q"))
                         .build(&ui);
                 });
+                ui.menu(im_str!("View"), true, || {
+                    for &theme in &Theme::ALL {
+                        let clicked = imgui::MenuItem::new(&im_str!("{} theme", theme))
+                            .selected(app_state.css_cache.theme() == theme)
+                            .build(&ui);
+                        if clicked {
+                            app_state.automatic_theme = false;
+                            app_state.css_cache.set_theme(theme);
+                        }
+                    }
+                    ui.separator();
+                    imgui::MenuItem::new(im_str!("Automatic theme"))
+                        .build_with_ref(&ui, &mut app_state.automatic_theme);
+                });
+                ui.menu(im_str!("Edit"), true, || {
+                    let undo = imgui::MenuItem::new(im_str!("Undo style edit"))
+                        .shortcut(im_str!("Ctrl + Z"))
+                        .enabled(app_state.css_cache.can_undo())
+                        .build(&ui);
+                    if undo {
+                        app_state.css_cache.undo();
+                    }
+                });
 
                 ui.text(&im_str!(
                     "Mouse Position: ({:.1},{:.1})",
@@ -110,6 +145,10 @@ impl HUD {
                     app_state.stats.get_average(),
                     app_state.zoom
                 ));
+
+                if let Some(attribution) = app_state.tile_cache.source().attribution() {
+                    ui.text(&im_str!("{}", attribution));
+                }
             });
 
             // Draw main window.
@@ -121,6 +160,12 @@ impl HUD {
                     size[1] = 100.0;
                     let window = imgui::ChildWindow::new("Hovered objects").size(size);
                     window.build(&ui, || {
+                        if let Some(target) = app_state
+                            .hovered_target
+                            .and_then(|mmsi| app_state.ais_targets.get(mmsi))
+                        {
+                            ui.text(im_str!("AIS target {}", target.display_name()));
+                        }
                         let objects = app_state
                             .hovered_objects
                             .iter()
@@ -168,22 +213,53 @@ impl HUD {
 
                         ui.text(im_str!("{:#?}", object.tags()));
 
+                        ui.separator();
+                        ui.text(im_str!("Computed style"));
+                        ui.separator();
+
+                        let style = app_state.css_cache.computed_style(
+                            &object
+                                .selector()
+                                .clone()
+                                .with_any("zoom", app_state.zoom.to_string()),
+                        );
+                        for (key, computed) in style {
+                            ui.text(im_str!(
+                                "{}: {:?}{} from {}",
+                                key,
+                                computed.value.at_zoom(app_state.zoom),
+                                if computed.important {
+                                    " !important"
+                                } else {
+                                    ""
+                                },
+                                computed.rule.selector
+                            ));
+                        }
+
                         ui.separator();
                         ui.text(im_str!("Applying rules"));
                         ui.separator();
 
-                        let mut rules = app_state
-                            .css_cache
-                            .get_matching_rules_mut(&object.selector());
-                        for rule in rules.iter_mut() {
-                            let show_block =
-                                add_header_separator(&ui, im_str!("{}", rule.selector));
+                        let css_cache = &mut app_state.css_cache;
+                        // Rules of the other themes do not apply and are not shown.
+                        let rules = css_cache.get_matching_rule_indices(
+                            &object
+                                .selector()
+                                .clone()
+                                .with_any("theme", css_cache.theme().name()),
+                        );
+                        for rule in rules {
+                            let show_block = add_header_separator(
+                                &ui,
+                                im_str!("{}", css_cache.rules[rule].selector),
+                            );
                             if show_block {
-                                add_color_picker(&ui, rule, "background-color");
-                                add_color_picker(&ui, rule, "border-color");
-                                add_slider_float(&ui, rule, "border-width");
-                                add_slider_float(&ui, rule, "line-width");
-                                add_display_none(&ui, rule);
+                                add_color_picker(&ui, css_cache, rule, "background-color");
+                                add_color_picker(&ui, css_cache, rule, "border-color");
+                                add_slider_float(&ui, css_cache, rule, "border-width");
+                                add_slider_float(&ui, css_cache, rule, "line-width");
+                                add_display_none(&ui, css_cache, rule);
                             }
                         }
                     } else {
@@ -205,6 +281,153 @@ impl HUD {
                     if head {
                         ui.text(im_str!("{:#?}", app_state.tile_cache.get_stats()));
                     }
+
+                    // Show the tiles which could not be loaded.
+                    let failed_tiles = app_state.tile_cache.failed_tiles();
+                    let head =
+                        CollapsingHeader::new(&im_str!("Failed Tiles ({})", failed_tiles.len()))
+                            .default_open(true)
+                            .build(&ui);
+                    if head {
+                        let mut retry = vec![];
+                        for (tile_id, error) in failed_tiles {
+                            if ui.small_button(&im_str!("Retry##{}", tile_id)) {
+                                retry.push(*tile_id);
+                            }
+                            ui.same_line(0.0);
+                            ui.text(im_str!("{}: {}", tile_id, error));
+                        }
+                        if !failed_tiles.is_empty()
+                            && ui.button(im_str!("Retry all"), [100.0, 25.0])
+                        {
+                            app_state.tile_cache.retry_failed_tiles();
+                        }
+                        for tile_id in retry {
+                            app_state.tile_cache.retry_tile(&tile_id);
+                        }
+                    }
+                });
+
+            let window = imgui::Window::new(im_str!("Routes"));
+            window
+                .position([520.0, 520.0], imgui::Condition::FirstUseEver)
+                .size([400.0, 350.0], imgui::Condition::FirstUseEver)
+                .build(&ui, || {
+                    let mut size = ui.window_size();
+                    size[1] = 100.0;
+                    let mut active_route = app_state.active_route;
+                    imgui::ChildWindow::new("Route list")
+                        .size(size)
+                        .build(&ui, || {
+                            for (index, route) in app_state.routes.routes.iter().enumerate() {
+                                let clicked = Selectable::new(&im_str!(
+                                    "{} ({:.1} nm)##{}",
+                                    route.name,
+                                    route.total_distance(),
+                                    index
+                                ))
+                                .selected(active_route == Some(index))
+                                .build(&ui);
+                                if clicked {
+                                    active_route = Some(index);
+                                }
+                            }
+                        });
+                    if active_route != app_state.active_route {
+                        app_state.active_route = active_route;
+                        app_state.editing_route = false;
+                    }
+
+                    if ui.small_button(im_str!("New route")) {
+                        app_state.new_route();
+                    }
+                    if app_state.active_route.is_some() {
+                        ui.same_line(0.0);
+                        if ui.small_button(im_str!("Delete route")) {
+                            app_state.delete_route();
+                        }
+                    }
+
+                    let route = match app_state.active_route() {
+                        Some(route) => route.clone(),
+                        None => {
+                            ui.text(im_str!("No route selected"));
+                            return;
+                        }
+                    };
+
+                    add_header_separator(&ui, im_str!("Route"));
+
+                    let mut name = ImString::with_capacity(100);
+                    name.push_str(&route.name);
+                    if imgui::InputText::new(&ui, im_str!("Name"), &mut name).build() {
+                        if let Some(index) = app_state.active_route {
+                            app_state.routes.routes[index].name = name.to_string();
+                        }
+                    }
+                    // Save the name once it is entered instead of on every keystroke.
+                    if ui.is_item_deactivated_after_edit() {
+                        app_state.save_routes();
+                    }
+                    ui.checkbox(
+                        im_str!("Edit on the map"),
+                        &mut app_state.editing_route,
+                    );
+                    if app_state.editing_route {
+                        ui.text(im_str!(
+                            "Click to add a waypoint, or on a leg to insert one.\nDrag waypoints to move them, right click to delete them."
+                        ));
+                    }
+
+                    let legs = route.legs();
+                    for (index, waypoint) in route.waypoints.iter().enumerate() {
+                        if ui.small_button(&im_str!("Delete##waypoint{}", index)) {
+                            app_state.delete_waypoint(index);
+                        }
+                        ui.same_line(0.0);
+                        ui.text(im_str!("{} {}", index + 1, format_position(*waypoint)));
+
+                        if let Some(leg) = legs.get(index) {
+                            if ui.small_button(&im_str!("Insert##leg{}", index)) {
+                                app_state.split_leg(index);
+                            }
+                            ui.same_line(0.0);
+                            ui.text(im_str!(
+                                "    {:05.1}° {:.2} nm",
+                                leg.bearing,
+                                leg.distance
+                            ));
+                        }
+                    }
+                    ui.separator();
+                    ui.text(im_str!("Total {:.2} nm", route.total_distance()));
+                });
+
+            let window = imgui::Window::new(im_str!("Style"));
+            window
+                .position([520.0, 180.0], imgui::Condition::FirstUseEver)
+                .size([400.0, 150.0], imgui::Condition::FirstUseEver)
+                .build(&ui, || {
+                    if app_state.css_cache.has_unsaved_edits() {
+                        if ui.small_button(im_str!("Save style")) {
+                            app_state.save_style();
+                        }
+                        ui.same_line(0.0);
+                        ui.text(im_str!("The style has unsaved edits."));
+                    }
+
+                    // Show the problems found when the stylesheet was loaded last.
+                    let diagnostics = app_state.css_cache.diagnostics();
+                    if diagnostics.is_empty() {
+                        ui.text(im_str!("No problems in the stylesheet."));
+                    }
+                    for diagnostic in diagnostics {
+                        let color = match diagnostic.severity {
+                            Severity::Error => [1.0, 0.3, 0.3, 1.0],
+                            Severity::Warning => [1.0, 0.8, 0.2, 1.0],
+                        };
+                        ui.text_colored(color, im_str!("{}", diagnostic));
+                    }
                 });
 
             let window = imgui::Window::new(im_str!("Location Finder"));
@@ -233,6 +456,193 @@ impl HUD {
                         }
                     }
                 });
+            let window = imgui::Window::new(im_str!("Own ship"));
+            window
+                .position([960.0, 60.0], imgui::Condition::FirstUseEver)
+                .size([300.0, 240.0], imgui::Condition::FirstUseEver)
+                .build(&ui, || {
+                    let own_ship = &app_state.own_ship;
+                    let position = match own_ship.position {
+                        Some(position) => position,
+                        None => {
+                            ui.text(im_str!("No position"));
+                            return;
+                        }
+                    };
+                    let format_angle = |angle: Option<f32>| {
+                        angle.map_or("-".to_string(), |angle| format!("{:05.1}°", angle))
+                    };
+
+                    ui.text(im_str!("{}", format_position(position)));
+                    ui.text(im_str!("COG {}", format_angle(own_ship.course)));
+                    ui.text(im_str!(
+                        "SOG {}",
+                        own_ship
+                            .speed
+                            .map_or("-".to_string(), |speed| format!("{:.1} kn", speed))
+                    ));
+                    ui.text(im_str!("HDG {}", format_angle(own_ship.heading)));
+                    if let (Some(satellites), Some(hdop)) = (own_ship.satellites, own_ship.hdop) {
+                        ui.text(im_str!("{} satellites, HDOP {:.1}", satellites, hdop));
+                    }
+                    if let Some(age) = own_ship.fix_age() {
+                        let color = if own_ship.has_fix() {
+                            [1.0, 1.0, 1.0, 1.0]
+                        } else {
+                            [1.0, 0.3, 0.3, 1.0]
+                        };
+                        ui.text_colored(color, im_str!("Fix {:.0} s ago", age.as_secs_f32()));
+                    }
+
+                    if ui.button(im_str!("Center on boat"), [120.0, 25.0]) {
+                        app_state.set_center(position);
+                    }
+
+                    ui.separator();
+                    let mut follow = app_state.follow;
+                    ui.radio_button(im_str!("Free"), &mut follow, FollowMode::Off);
+                    ui.radio_button(im_str!("Follow north up"), &mut follow, FollowMode::NorthUp);
+                    ui.radio_button(
+                        im_str!("Follow course up"),
+                        &mut follow,
+                        FollowMode::CourseUp,
+                    );
+                    if follow != app_state.follow {
+                        app_state.set_follow(follow);
+                    }
+                });
+
+            let unacknowledged = app_state
+                .alarms
+                .iter()
+                .filter(|alarm| !alarm.acknowledged)
+                .count();
+            let title = im_str!(
+                "Collision alarms ({}/{})###Collision alarms",
+                unacknowledged,
+                app_state.alarms.len()
+            );
+            let window = imgui::Window::new(&title);
+            window
+                .position([520.0, 350.0], imgui::Condition::FirstUseEver)
+                .size([400.0, 150.0], imgui::Condition::FirstUseEver)
+                .build(&ui, || {
+                    if app_state.alarms.is_empty() {
+                        ui.text(im_str!("No dangerous targets"));
+                        return;
+                    }
+                    if unacknowledged > 0 && ui.small_button(im_str!("Acknowledge all")) {
+                        for alarm in &mut app_state.alarms {
+                            alarm.acknowledged = true;
+                        }
+                    }
+                    let mut selected = None;
+                    for alarm in &mut app_state.alarms {
+                        if !alarm.acknowledged {
+                            if ui.small_button(&im_str!("Acknowledge##{}", alarm.mmsi)) {
+                                alarm.acknowledged = true;
+                            }
+                            ui.same_line(0.0);
+                        }
+                        let name = app_state
+                            .ais_targets
+                            .get(alarm.mmsi)
+                            .map(|target| target.display_name())
+                            .unwrap_or_default();
+                        let color = if alarm.acknowledged {
+                            [1.0, 1.0, 1.0, 1.0]
+                        } else {
+                            [1.0, 0.3, 0.3, 1.0]
+                        };
+                        let text = match alarm.approach {
+                            Some(approach) => im_str!(
+                                "{}: CPA {:.2} nm in {:.1} min",
+                                name,
+                                approach.cpa,
+                                approach.tcpa
+                            ),
+                            None => im_str!("{}: {:.2} nm away, CPA unknown", name, alarm.distance),
+                        };
+                        ui.text_colored(color, text);
+                        if ui.is_item_clicked(imgui::MouseButton::Left) {
+                            selected = Some(alarm.mmsi);
+                        }
+                    }
+                    if selected.is_some() {
+                        app_state.selected_target = selected;
+                    }
+                });
+
+            let title = im_str!(
+                "AIS targets ({})###AIS targets",
+                app_state.ais_targets.len()
+            );
+            let window = imgui::Window::new(&title);
+            window
+                .position([960.0, 320.0], imgui::Condition::FirstUseEver)
+                .size([300.0, 400.0], imgui::Condition::FirstUseEver)
+                .build(&ui, || {
+                    let mut size = ui.window_size();
+                    size[1] = 150.0;
+                    let mut selected = app_state.selected_target;
+                    imgui::ChildWindow::new("Targets")
+                        .size(size)
+                        .build(&ui, || {
+                            for target in app_state.ais_targets.iter() {
+                                let clicked = Selectable::new(&im_str!(
+                                    "{}##{}",
+                                    target.display_name(),
+                                    target.mmsi
+                                ))
+                                .selected(selected == Some(target.mmsi))
+                                .build(&ui);
+                                if clicked {
+                                    selected = Some(target.mmsi);
+                                }
+                            }
+                        });
+                    app_state.selected_target = selected;
+
+                    add_header_separator(&ui, im_str!("Selected target"));
+
+                    let target = match selected.and_then(|mmsi| app_state.ais_targets.get(mmsi)) {
+                        Some(target) => target,
+                        None => {
+                            ui.text(im_str!("No target selected"));
+                            return;
+                        }
+                    };
+                    ui.text(im_str!("{}", target.display_name()));
+                    ui.text(im_str!("MMSI {} ({:?})", target.mmsi, target.class));
+                    if let Some(callsign) = &target.callsign {
+                        ui.text(im_str!("Callsign {}", callsign));
+                    }
+                    if let Some(position) = target.position {
+                        ui.text(im_str!("{}", format_position(position)));
+                    }
+                    if let (Some(course), Some(speed)) = (target.course, target.speed) {
+                        ui.text(im_str!("COG {:05.1}° SOG {:.1} kn", course, speed));
+                    }
+                    if let Some(heading) = target.heading {
+                        ui.text(im_str!("HDG {:05.1}°", heading));
+                    }
+                    if let Some((length, width)) = target.dimensions {
+                        ui.text(im_str!("{} m x {} m", length, width));
+                    }
+                    if let Some(destination) = &target.destination {
+                        ui.text(im_str!("Destination {}", destination));
+                    }
+                    ui.text(im_str!(
+                        "Received {:.0} s ago",
+                        target.last_seen.elapsed().as_secs_f32()
+                    ));
+                    if let Some(position) = target.position {
+                        if ui.button(im_str!("Center on target"), [120.0, 25.0]) {
+                            app_state.set_center(position);
+                        }
+                    }
+                });
+
             ruda.pop(&ui);
             // ui.show_demo_window(&mut false);
         }
@@ -268,84 +678,263 @@ impl HUD {
     }
 }
 
-fn add_color_picker(ui: &Ui, rule: &mut Rule, attribute: &str) {
-    let default_color = CSSValue::Color(Color::TRANSPARENT);
-    let color = if let Some(color) = rule.kvs.get(attribute) {
-        color
+/// Draws the routes over the map, the active one with its numbered waypoints.
+fn paint_routes(ui: &Ui, app_state: &AppState) {
+    let screen = &app_state.screen;
+    let zoom = app_state.zoom;
+    let scale = ui.io().display_framebuffer_scale[0];
+    let draw_list = ui.get_background_draw_list();
+    for (index, route) in app_state.routes.routes.iter().enumerate() {
+        let active = app_state.active_route == Some(index);
+        let color = if active {
+            [0.8, 0.2, 0.8, 1.0]
+        } else {
+            [0.5, 0.3, 0.6, 0.6]
+        };
+        let points = route
+            .global_waypoints()
+            .iter()
+            .map(|waypoint| {
+                let pixel = screen.global_to_pixel(zoom, waypoint);
+                [pixel.x / scale, pixel.y / scale]
+            })
+            .collect::<Vec<_>>();
+        for leg in points.windows(2) {
+            draw_list
+                .add_line(leg[0], leg[1], color)
+                .thickness(if active { 3.0 } else { 2.0 })
+                .build();
+        }
+        for (number, point) in points.iter().enumerate() {
+            let dragged = active && app_state.dragged_waypoint == Some(number);
+            draw_list
+                .add_circle(*point, if dragged { 7.0 } else { 5.0 }, color)
+                .filled(true)
+                .build();
+            if active {
+                draw_list.add_text(
+                    [point[0] + 8.0, point[1] - 16.0],
+                    color,
+                    (number + 1).to_string(),
+                );
+            }
+        }
+    }
+}
+
+/// Draws the AIS targets over the map, turned to their heading or course and labeled with their name.
+fn paint_ais_targets(ui: &Ui, app_state: &AppState) {
+    let screen = &app_state.screen;
+    let zoom = app_state.zoom;
+    let scale = ui.io().display_framebuffer_scale[0];
+    let draw_list = ui.get_background_draw_list();
+    for target in app_state.ais_targets.iter() {
+        let position = match target.global_position() {
+            Some(position) => screen.global_to_pixel(zoom, &position),
+            None => continue,
+        };
+        let center = [position.x / scale, position.y / scale];
+        let highlighted = app_state.selected_target == Some(target.mmsi)
+            || app_state.hovered_target == Some(target.mmsi);
+        let alarm = app_state.alarm(target.mmsi);
+        let color = match (highlighted, alarm, target.class) {
+            (true, _, _) => [1.0, 0.6, 0.0, 1.0],
+            (false, Some(_), _) => [0.9, 0.1, 0.1, 1.0],
+            (false, None, AisClass::AidToNavigation) => [0.9, 0.8, 0.1, 1.0],
+            (false, None, _) => [0.1, 0.6, 0.3, 1.0],
+        };
+        // Dangerous targets are circled, boldly until their alarm is acknowledged.
+        if let Some(alarm) = alarm {
+            let thickness = if alarm.acknowledged { 1.0 } else { 3.0 };
+            draw_list
+                .add_circle(center, 18.0, [0.9, 0.1, 0.1, 1.0])
+                .thickness(thickness)
+                .build();
+        }
+
+        let bearing = target.heading.or(target.course).unwrap_or(0.0) - screen.rotation;
+        let (sin, cos) = bearing.to_radians().sin_cos();
+        let corner =
+            |x: f32, y: f32| [center[0] + cos * x - sin * y, center[1] + sin * x + cos * y];
+        if target.class == AisClass::AidToNavigation {
+            let (top, right, bottom, left) = ([0.0, -7.0], [7.0, 0.0], [0.0, 7.0], [-7.0, 0.0]);
+            let at = |offset: [f32; 2]| [center[0] + offset[0], center[1] + offset[1]];
+            draw_list
+                .add_triangle(at(top), at(right), at(bottom), color)
+                .filled(true)
+                .build();
+            draw_list
+                .add_triangle(at(top), at(bottom), at(left), color)
+                .filled(true)
+                .build();
+        } else {
+            draw_list
+                .add_triangle(
+                    corner(0.0, -12.0),
+                    corner(6.0, 8.0),
+                    corner(-6.0, 8.0),
+                    color,
+                )
+                .filled(true)
+                .build();
+        }
+        draw_list.add_text(
+            [center[0] + 10.0, center[1] + 4.0],
+            color,
+            target.display_name(),
+        );
+    }
+}
+
+/// Draws the own ship over the map with its course and speed vector and the uncertainty of its position.
+fn paint_own_ship(ui: &Ui, app_state: &AppState) {
+    let own_ship = &app_state.own_ship;
+    let position = match own_ship.global_position() {
+        Some(position) => position,
+        None => return,
+    };
+    let screen = &app_state.screen;
+    let zoom = app_state.zoom;
+    // The screen is measured in physical pixels, the HUD in logical ones.
+    let scale = ui.io().display_framebuffer_scale[0];
+    let to_hud = |point: &lyon::math::Point| {
+        let pixel = screen.global_to_pixel(zoom, point);
+        [pixel.x / scale, pixel.y / scale]
+    };
+    let center = to_hud(&position);
+    let color = if own_ship.has_fix() {
+        [0.9, 0.1, 0.5, 1.0]
     } else {
-        &default_color
+        [0.5, 0.5, 0.5, 1.0]
     };
-    let color = match color {
-        CSSValue::String(string) => match &string[..] {
-            "red" => Color::RED,
-            "green" => Color::GREEN,
-            "blue" => Color::BLUE,
-            "black" => Color::BLACK,
-            "white" => Color::WHITE,
-            _ => Color::TRANSPARENT,
-        },
-        CSSValue::Color(color) => color.clone(),
-        _ => Color::TRANSPARENT, // This should never happen, but transparent should be a decent fallback
+    let draw_list = ui.get_background_draw_list();
+
+    if let Some(hdop) = own_ship.hdop {
+        let radius =
+            screen.meters_to_pixels(zoom, &position, hdop * CONFIG.nmea.hdop_error) / scale;
+        if radius > 2.0 {
+            draw_list
+                .add_circle(center, radius, [color[0], color[1], color[2], 0.15])
+                .filled(true)
+                .build();
+            draw_list.add_circle(center, radius, color).build();
+        }
+    }
+
+    if let (Some(course), Some(speed)) = (own_ship.course, own_ship.speed) {
+        let meters = speed * 1852.0 * CONFIG.nmea.vector_minutes / 60.0;
+        let end = to_hud(&project_global(&position, course, meters));
+        draw_list
+            .add_line(center, end, color)
+            .thickness(2.0)
+            .build();
+        draw_list.add_circle(end, 3.0, color).filled(true).build();
+    }
+
+    // The boat points to its heading, or its course without a compass.
+    let bearing = own_ship.heading.or(own_ship.course).unwrap_or(0.0) - screen.rotation;
+    let (sin, cos) = bearing.to_radians().sin_cos();
+    let corner = |x: f32, y: f32| [center[0] + cos * x - sin * y, center[1] + sin * x + cos * y];
+    let (bow, port, stern, starboard) = (
+        corner(0.0, -14.0),
+        corner(-7.0, 8.0),
+        corner(0.0, 4.0),
+        corner(7.0, 8.0),
+    );
+    draw_list
+        .add_triangle(bow, port, stern, color)
+        .filled(true)
+        .build();
+    draw_list
+        .add_triangle(bow, stern, starboard, color)
+        .filled(true)
+        .build();
+}
+
+fn add_color_picker(ui: &Ui, css_cache: &mut RulesCache, rule: usize, attribute: &str) {
+    if add_read_only_value(ui, &css_cache.rules[rule], attribute) {
+        return;
+    }
+    let color = match css_cache.rules[rule].kvs.get(attribute) {
+        Some(CSSValue::Color(color)) => color.clone(),
+        // The property is not set.
+        _ => Color::TRANSPARENT,
     };
     let mut color = [color.r, color.g, color.b, color.a];
     let label = im_str!("{}", attribute);
     let cp = ColorEdit::new(&label, EditableColor::Float4(&mut color));
-    cp.build(&ui);
 
-    rule.kvs.insert(
-        attribute.to_string(),
-        CSSValue::Color(Color {
+    let changed = cp.build(ui);
+    if ui.is_item_deactivated_after_edit() {
+        css_cache.finish_edit();
+    }
+    if changed {
+        let color = Color {
             r: color[0],
             g: color[1],
             b: color[2],
             a: color[3],
-        }),
-    );
+        };
+        css_cache.set_property(rule, attribute, Some(CSSValue::Color(color)));
+    }
 }
 
-fn add_slider_float(ui: &Ui, rule: &mut Rule, attribute: &str) {
-    let default_number = CSSValue::Number(Number::Px(0.0));
-    let value = if let Some(value) = rule.kvs.get(attribute) {
-        value
-    } else {
-        &default_number
-    };
-    let mut value = match value {
-        CSSValue::Number(number) => match &number {
-            Number::Px(px) => *px,
-            _ => 0.0,
-        },
-        _ => 0.0, // This should never happen, but transparent should be a decent fallback
+fn add_slider_float(ui: &Ui, css_cache: &mut RulesCache, rule: usize, attribute: &str) {
+    if add_read_only_value(ui, &css_cache.rules[rule], attribute) {
+        return;
+    }
+    let mut value = match css_cache.rules[rule].kvs.get(attribute) {
+        Some(CSSValue::Number(Number::Px(px))) => *px,
+        _ => 0.0,
     };
 
     let label = im_str!("{}", attribute);
-    imgui::Slider::new(&label)
+    let changed = imgui::Slider::new(&label)
         .range(0.0..=10.0)
         .build(&ui, &mut value);
+    if ui.is_item_deactivated_after_edit() {
+        css_cache.finish_edit();
+    }
 
-    rule.kvs
-        .insert(attribute.to_string(), CSSValue::Number(Number::Px(value)));
+    if changed {
+        css_cache.set_property(rule, attribute, Some(CSSValue::Number(Number::Px(value))));
+    }
 }
 
-fn add_display_none(ui: &Ui, rule: &mut Rule) {
-    let attribute = "display";
-    let mut value = if let Some(value) = rule.kvs.get(attribute) {
-        match value {
-            CSSValue::String(value) => !matches!(&value[..], "none"),
-            _ => true,
+/// Shows zoom dependent values and `var()` references, which can not be edited with a single picker.
+///
+/// Returns `true` if the value of `attribute` is shown read-only.
+fn add_read_only_value(ui: &Ui, rule: &Rule, attribute: &str) -> bool {
+    match rule.kvs.get(attribute) {
+        // Editing the custom property changes every rule using it, so it is not replaced here.
+        Some(value @ CSSValue::Var(..)) => {
+            ui.text(im_str!("{}: {}", attribute, value));
+            true
         }
-    } else {
-        true
+        Some(CSSValue::Function(function)) => {
+            ui.text(im_str!(
+                "{}: {:?} over {} stops",
+                attribute,
+                function.interpolation,
+                function.stops.len()
+            ));
+            true
+        }
+        _ => false,
+    }
+}
+
+fn add_display_none(ui: &Ui, css_cache: &mut RulesCache, rule: usize) {
+    let attribute = "display";
+    let mut value = match css_cache.rules[rule].kvs.get(attribute) {
+        Some(CSSValue::String(value)) => value != "none",
+        _ => true,
     };
 
     let label = im_str!("{}", attribute);
-    ui.checkbox(&label, &mut value);
-
-    if !value {
-        rule.kvs
-            .insert(attribute.to_string(), CSSValue::String("none".to_string()));
-    } else {
-        rule.kvs.remove(attribute);
+    if ui.checkbox(&label, &mut value) {
+        let value = Some(CSSValue::String("none".to_string())).filter(|_| !value);
+        css_cache.set_property(rule, attribute, value);
     }
 }
 
@@ -9,8 +9,8 @@ use osm::*;
 use winit::{
     dpi::LogicalPosition,
     event::{
-        ElementState, Event, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode,
-        WindowEvent,
+        ElementState, Event, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta,
+        VirtualKeyCode, WindowEvent,
     },
     event_loop::ControlFlow,
 };
@@ -40,6 +40,9 @@ fn main() {
     let mut hud = drawing::ui::HUD::new(&painter.window, &mut painter.device, &mut painter.queue);
 
     let mut mouse_down = false;
+    // The distance the mouse moved since the button was pressed, to tell clicks from drags.
+    let mut drag_distance = 0.0;
+    let mut modifiers = ModifiersState::default();
     let mut last_pos = winit::dpi::LogicalPosition::new(0.0, 0.0);
 
     event_loop.run(move |event, _, control_flow| {
@@ -59,10 +62,12 @@ fn main() {
                     app_state.screen.height = physical_size.height;
                     painter.resize(physical_size.width, physical_size.height);
                 }
+                WindowEvent::ModifiersChanged(state) => modifiers = state,
                 WindowEvent::KeyboardInput {
                     input:
                         KeyboardInput {
                             virtual_keycode: Some(keycode),
+                            state,
                             ..
                         },
                     ..
@@ -73,6 +78,16 @@ fn main() {
                                 *control_flow = ControlFlow::Exit;
                             }
                             VirtualKeyCode::Tab => app_state.advance_selected_object(),
+                            VirtualKeyCode::S
+                                if modifiers.ctrl() && state == ElementState::Pressed =>
+                            {
+                                app_state.save_style()
+                            }
+                            VirtualKeyCode::Z
+                                if modifiers.ctrl() && state == ElementState::Pressed =>
+                            {
+                                app_state.css_cache.undo();
+                            }
                             _ => {}
                         }
                     }
@@ -82,16 +97,27 @@ fn main() {
                 }
                 WindowEvent::MouseInput { state, button, .. } => {
                     if route_mouse {
-                        if let MouseButton::Left = button {
-                            match state {
-                                ElementState::Pressed => {
-                                    mouse_down = true;
-                                }
-                                ElementState::Released => {
-                                    mouse_down = false;
-                                    app_state.update_selected_hover_objects();
+                        let cursor = (last_pos.x as f32, last_pos.y as f32);
+                        match (button, state) {
+                            (MouseButton::Left, ElementState::Pressed) => {
+                                mouse_down = true;
+                                drag_distance = 0.0;
+                                app_state.grab_waypoint(cursor);
+                            }
+                            (MouseButton::Left, ElementState::Released) => {
+                                mouse_down = false;
+                                if !app_state.release_waypoint() {
+                                    if app_state.editing_route && drag_distance < 3.0 {
+                                        app_state.place_waypoint(cursor);
+                                    } else {
+                                        app_state.update_selected_hover_objects();
+                                    }
                                 }
                             }
+                            (MouseButton::Right, ElementState::Pressed) => {
+                                app_state.delete_waypoint_at(cursor)
+                            }
+                            _ => {}
                         }
                     }
                 }
@@ -106,32 +132,23 @@ fn main() {
                     }
                 }
                 WindowEvent::CursorMoved { position, .. } => {
-                    let logical_position = position.to_logical(painter.get_hidpi_factor());
-                    let size = app_state.screen.get_tile_size() as f32;
-                    let mut delta = vector(
+                    let hidpi_factor = painter.get_hidpi_factor();
+                    let logical_position = position.to_logical(hidpi_factor);
+                    // The screen is measured in physical pixels.
+                    let delta = vector(
                         (logical_position.x - last_pos.x) as f32,
                         (logical_position.y - last_pos.y) as f32,
-                    );
-                    let zoom_x = (app_state.screen.width as f32)
-                        / size
-                        / 2f32.powf(app_state.zoom)
-                        / size
-                        / 2.0
-                        / 1.3;
-                    let zoom_y = (app_state.screen.height as f32)
-                        / size
-                        / 2f32.powf(app_state.zoom)
-                        / size
-                        / 2.0
-                        / 1.3;
-                    delta.x *= zoom_x;
-                    delta.y *= zoom_y;
+                    ) * hidpi_factor as f32;
 
                     last_pos = logical_position;
 
                     if route_mouse {
                         if mouse_down {
-                            app_state.screen.center -= delta;
+                            drag_distance += delta.length();
+                            let cursor = (logical_position.x as f32, logical_position.y as f32);
+                            if !app_state.move_dragged_waypoint(cursor) {
+                                app_state.drag(delta);
+                            }
                         }
 
                         app_state.update_hovered_objects((
@@ -144,6 +161,9 @@ fn main() {
             },
             Event::MainEventsCleared => {
                 painter.update_shader();
+                app_state.css_cache.update();
+                app_state.update_theme();
+                app_state.update_nmea();
                 app_state.load_tiles();
                 painter.paint(&mut hud, &mut app_state);
 
@@ -1,109 +1,251 @@
 use super::*;
-use std::collections::HashMap;
+use crate::loader::*;
+use lyon::math::Point;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::mpsc::{channel, Receiver, Sender};
-use std::sync::{Arc, RwLock};
-use std::thread::{spawn, JoinHandle};
+use std::sync::{Arc, Condvar, Mutex, RwLock};
 
 #[derive(Debug, Clone)]
 pub struct CacheStats {
     cached_tiles: usize,
     loading_tiles: usize,
+    queued_tiles: usize,
+    cancelled_tiles: usize,
+    failed_tiles: usize,
+    loader_threads: usize,
     cached_objects: usize,
     cached_features: usize,
     cached_vertices: usize,
+    resident_bytes: usize,
+    memory_budget: usize,
+    evicted_tiles: usize,
     total_stats: TileStats,
 }
 
+/// A `Tile` held by the `TileCache` together with its bookkeeping for eviction.
+struct CachedTile {
+    tile: Arc<RwLock<Tile>>,
+    /// The estimated memory size of the tile in bytes.
+    size: usize,
+    /// The last eviction pass during which the tile was in use.
+    last_used: u64,
+}
+
 /// A cache structure to hold all loaded `Tile`s.
+///
+/// Tiles are loaded by a fixed number of loader threads, closest to the current focus first.
+/// Tiles which are not in use anymore are evicted least recently used first
+/// as soon as the cache grows beyond its memory budget.
 pub struct TileCache {
-    cache: HashMap<TileId, Arc<RwLock<Tile>>>,
-    loaders: Vec<(u64, JoinHandle<Option<Tile>>, TileId)>,
-    channel: (Sender<u64>, Receiver<u64>),
-    cache_location: String,
-    id: u64,
+    cache: HashMap<TileId, CachedTile>,
+    /// All tiles which are queued or currently being loaded.
+    pending: HashSet<TileId>,
+    /// All tiles which are currently drawn and must not be evicted.
+    in_use: HashSet<TileId>,
+    /// All tiles which could not be loaded. They are not requested again until they are retried.
+    failed: BTreeMap<TileId, TileError>,
+    queue: SharedLoadQueue,
+    channel: (Sender<LoadResult>, Receiver<LoadResult>),
+    source: Arc<dyn TileSource>,
+    focus: Option<LoadFocus>,
+    loader_threads: usize,
+    spawned_loaders: usize,
+    cancelled_tiles: usize,
+    memory_budget: usize,
+    resident_bytes: usize,
+    evicted_tiles: usize,
+    eviction_pass: u64,
 }
 
 impl TileCache {
-    /// Create a new `TileCache`.
-    pub fn new(cache_location: String) -> Self {
+    /// Create a new `TileCache` which loads its tiles from `source`.
+    pub fn new(source: Box<dyn TileSource>) -> Self {
         Self {
             cache: HashMap::new(),
-            loaders: vec![],
+            pending: HashSet::new(),
+            in_use: HashSet::new(),
+            failed: BTreeMap::new(),
+            queue: Arc::new((Mutex::new(LoadQueue::default()), Condvar::new())),
             channel: channel(),
-            cache_location,
-            id: 0,
+            source: Arc::from(source),
+            focus: None,
+            loader_threads: 4,
+            spawned_loaders: 0,
+            cancelled_tiles: 0,
+            memory_budget: usize::MAX,
+            resident_bytes: 0,
+            evicted_tiles: 0,
+            eviction_pass: 0,
         }
     }
 
+    /// Limits the estimated memory used by the cached tiles to `memory_budget` bytes.
+    ///
+    /// Tiles which are still in use are never evicted, so the budget can be exceeded temporarily.
+    pub fn with_memory_budget(mut self, memory_budget: usize) -> Self {
+        self.memory_budget = memory_budget;
+        self
+    }
+
+    /// Sets the number of threads which load tiles in parallel.
+    pub fn with_loader_threads(mut self, loader_threads: usize) -> Self {
+        self.loader_threads = loader_threads.max(1);
+        self
+    }
+
+    /// Returns the `TileSource` the cache loads its tiles from.
+    pub fn source(&self) -> &dyn TileSource {
+        self.source.as_ref()
+    }
+
     /// Check loaders for loaded tiles and insert them if there is any.
     pub fn finalize_loaded_tiles(&mut self) {
         // Get all pending messages and work them.
-        for id in self.channel.1.try_iter() {
-            let potential_loader = self.loaders.iter().enumerate().find(|(_, l)| l.0 == id);
-
-            // Try finalizing the complete loader.
-            if let Some((i, _)) = potential_loader {
-                let loader = self.loaders.remove(i);
-                match loader.1.join() {
-                    Ok(tile) => {
-                        if let Some(tile) = tile {
-                            self.cache.insert(loader.2, Arc::new(RwLock::new(tile)));
-                        }
-                    }
-                    Err(e) => {
-                        log::error!(
-                            "Failed to join tile loader thread for {}. Reason:\r\n{:?}",
-                            loader.2,
-                            e
-                        );
-                    }
+        for (tile_id, tile) in self.channel.1.try_iter() {
+            self.pending.remove(&tile_id);
+            match tile {
+                Ok(tile) => {
+                    let size = tile.stats().size;
+                    self.resident_bytes += size;
+                    self.cache.insert(
+                        tile_id,
+                        CachedTile {
+                            tile: Arc::new(RwLock::new(tile)),
+                            size,
+                            // Newly loaded tiles survive the upcoming pass so they can be picked up.
+                            last_used: self.eviction_pass + 1,
+                        },
+                    );
                 }
+                Err(e) => {
+                    self.failed.insert(tile_id, e);
+                }
+            }
+        }
+
+        self.evict_unused_tiles();
+    }
+
+    /// Sets the tiles which are currently drawn, which are kept in the cache until they are not in use anymore.
+    pub fn set_tiles_in_use(&mut self, tile_ids: impl IntoIterator<Item = TileId>) {
+        self.in_use = tile_ids.into_iter().collect();
+    }
+
+    /// Evicts the least recently used tiles which are not in use anymore until the memory budget is met.
+    ///
+    /// Tiles in use and tiles loaded since the last pass are never evicted.
+    fn evict_unused_tiles(&mut self) {
+        self.eviction_pass += 1;
+        for tile_id in &self.in_use {
+            if let Some(cached) = self.cache.get_mut(tile_id) {
+                cached.last_used = self.eviction_pass;
+            }
+        }
+
+        if self.resident_bytes <= self.memory_budget {
+            return;
+        }
+
+        let mut candidates = self
+            .cache
+            .iter()
+            .filter(|(_, cached)| cached.last_used < self.eviction_pass)
+            .map(|(tile_id, cached)| (cached.last_used, *tile_id))
+            .collect::<Vec<_>>();
+        candidates.sort();
+
+        for (_, tile_id) in candidates {
+            if self.resident_bytes <= self.memory_budget {
+                break;
+            }
+            if let Some(cached) = self.cache.remove(&tile_id) {
+                self.resident_bytes -= cached.size;
+                self.evicted_tiles += 1;
+                log::trace!("Evicted tile {} from the cache.", tile_id);
             }
         }
     }
 
+    /// Sets the area the user is currently looking at.
+    ///
+    /// Queued tiles are reprioritized by their distance to `center`
+    /// and tiles outside of `tile_field` which did not start loading yet are cancelled.
+    pub fn set_focus(&mut self, center: Point, tile_field: TileField) {
+        let focus = LoadFocus { center, tile_field };
+        self.focus = Some(focus);
+
+        let cancelled = self.queue.0.lock().unwrap().refocus(&focus);
+        self.cancelled_tiles += cancelled.len();
+        for tile_id in cancelled {
+            self.pending.remove(&tile_id);
+            log::trace!("Cancelled loading tile {}.", tile_id);
+        }
+    }
+
     /// Request a tile from the cache.
+    ///
+    /// The tile is queued for loading if it is neither cached nor already pending.
     pub fn request_tile(
         &mut self,
         tile_id: &TileId,
         feature_collection: Arc<RwLock<FeatureCollection>>,
         selection_tags: &[String],
     ) {
-        let id = self.id;
-        self.id += 1;
-
-        // Find the corresponding loader to the requested tile if there is any.
-        let loader = self.loaders.iter().find(|l| l.2 == *tile_id);
+        // Check if tile is not in the cache yet, is not currently being loaded, did not fail and can be provided by the source.
+        if self.cache.contains_key(tile_id)
+            || self.pending.contains(tile_id)
+            || self.failed.contains_key(tile_id)
+            || !self.source.covers(tile_id)
+        {
+            return;
+        }
 
-        // Check if tile is not in the cache yet and is not currently being loaded.
-        if !self.cache.contains_key(&tile_id) && loader.is_none() {
-            // Clone values to be moved into the thread.
-            let tile_id_clone = *tile_id;
-            let tx = self.channel.0.clone();
+        // Spawn the loader threads lazily.
+        while self.spawned_loaders < self.loader_threads {
+            spawn_loader(
+                self.queue.clone(),
+                self.source.clone(),
+                self.channel.0.clone(),
+            );
+            self.spawned_loaders += 1;
+        }
 
+        self.pending.insert(*tile_id);
+        let (lock, condvar) = &*self.queue;
+        lock.lock().unwrap().push(LoadRequest {
+            tile_id: *tile_id,
+            priority: self.focus.map_or(0.0, |focus| focus.priority(tile_id)),
+            feature_collection,
             // Make sure we load all tags we want to include.
-            let selection_tags = selection_tags.to_vec();
-            let cache_location = self.cache_location.clone();
-
-            // Store a new loader.
-            self.loaders.push((
-                id,
-                // Spawn a new loader.
-                spawn(move|| {
-                    // Try fetch and work the tile data.
-                    if let Some(data) = fetch_tile_data(cache_location, &tile_id_clone) {
-                        // Create a new Tile from the fetched data.
-                        let tile = Tile::from_mbvt(&tile_id_clone, &data, feature_collection, selection_tags);
-                        // Signalize that the end of the tile loading process could not be signalized.
-                       if tx.send(id).is_err() { log::debug!("Could not send the tile load message. This most likely happened because the app was terminated.") }
-                        Some(tile)
-                    } else {
-                        None
-                    }
-                }),
-                *tile_id,
-            ));
+            selection_tags: selection_tags.to_vec(),
+        });
+        condvar.notify_one();
+    }
+
+    /// Drops all cached and queued tiles, so they are loaded again the next time they are requested.
+    ///
+    /// Tiles which are being loaded right now are still inserted once they are done.
+    pub fn clear(&mut self) {
+        for tile_id in self.queue.0.lock().unwrap().clear() {
+            self.pending.remove(&tile_id);
         }
+        self.cache.clear();
+        self.resident_bytes = 0;
+    }
+
+    /// Returns all tiles which could not be loaded together with the reason.
+    pub fn failed_tiles(&self) -> &BTreeMap<TileId, TileError> {
+        &self.failed
+    }
+
+    /// Forgets that a tile failed to load, so it is loaded again the next time it is requested.
+    pub fn retry_tile(&mut self, tile_id: &TileId) {
+        self.failed.remove(tile_id);
+    }
+
+    /// Forgets all failed tiles, so they are loaded again the next time they are requested.
+    pub fn retry_failed_tiles(&mut self) {
+        self.failed.clear();
     }
 
     /// Get a `Tile` from the `TileCache`.
@@ -111,22 +253,143 @@ impl TileCache {
     /// Returns `None` if the tile is not in the cache.
     /// The user has to request the loading of the `Tile` on their own.
     pub fn try_get_tile(&self, tile_id: &TileId) -> Option<Arc<RwLock<Tile>>> {
-        self.cache.get(&tile_id).cloned()
+        self.cache.get(&tile_id).map(|cached| cached.tile.clone())
     }
 
     pub fn get_stats(&self) -> CacheStats {
         let mut total_stats = TileStats::new();
-        for tile in &self.cache {
-            let read_tile = tile.1.read().unwrap();
+        for cached in self.cache.values() {
+            let read_tile = cached.tile.read().unwrap();
             total_stats += *read_tile.stats();
         }
         CacheStats {
             cached_tiles: self.cache.len(),
-            loading_tiles: self.loaders.len(),
+            loading_tiles: self.pending.len(),
+            queued_tiles: self.queue.0.lock().unwrap().len(),
+            cancelled_tiles: self.cancelled_tiles,
+            failed_tiles: self.failed.len(),
+            loader_threads: self.spawned_loaders,
             cached_objects: 0,
             cached_features: 0,
             cached_vertices: 0,
+            resident_bytes: self.resident_bytes,
+            memory_budget: self.memory_budget,
+            evicted_tiles: self.evicted_tiles,
             total_stats,
         }
     }
 }
+
+impl Drop for TileCache {
+    fn drop(&mut self) {
+        shutdown_loaders(&self.queue);
+    }
+}
+
+#[test]
+fn load_tile_from_source() {
+    let tile_id = TileId::new(8, 142, 93);
+    let source =
+        MemoryTileSource::new().with_tile(tile_id, &include_bytes!("../../data/8_142_93.pbf")[..]);
+    let feature_collection = Arc::new(RwLock::new(FeatureCollection::new(500)));
+    let mut cache = TileCache::new(Box::new(source));
+
+    cache.request_tile(&tile_id, feature_collection.clone(), &[]);
+    // Tiles the source does not hold are never loaded.
+    cache.request_tile(&TileId::new(9, 0, 0), feature_collection, &[]);
+
+    let start = std::time::Instant::now();
+    while cache.try_get_tile(&tile_id).is_none() {
+        assert!(start.elapsed().as_secs() < 10, "Tile did not load in time.");
+        cache.finalize_loaded_tiles();
+        std::thread::yield_now();
+    }
+
+    assert!(cache.try_get_tile(&TileId::new(9, 0, 0)).is_none());
+    assert_eq!(cache.get_stats().cached_tiles, 1);
+}
+
+#[test]
+fn evict_unused_tiles_over_budget() {
+    let data = &include_bytes!("../../data/8_142_93.pbf")[..];
+    let tile_ids = [
+        TileId::new(8, 142, 93),
+        TileId::new(8, 143, 93),
+        TileId::new(8, 142, 94),
+    ];
+    let source = tile_ids
+        .iter()
+        .fold(MemoryTileSource::new(), |source, tile_id| {
+            source.with_tile(*tile_id, data)
+        });
+    let feature_collection = Arc::new(RwLock::new(FeatureCollection::new(500)));
+    let mut cache = TileCache::new(Box::new(source));
+
+    // Load the first tile to learn the size of a tile.
+    cache.request_tile(&tile_ids[0], feature_collection.clone(), &[]);
+    let start = std::time::Instant::now();
+    while cache.try_get_tile(&tile_ids[0]).is_none() {
+        assert!(start.elapsed().as_secs() < 10, "Tile did not load in time.");
+        cache.finalize_loaded_tiles();
+        std::thread::yield_now();
+    }
+    let tile_size = cache.get_stats().resident_bytes;
+
+    // Only leave room for two tiles and keep the first one in use.
+    cache.memory_budget = tile_size * 5 / 2;
+    cache.set_tiles_in_use(vec![tile_ids[0]]);
+
+    for tile_id in &tile_ids[1..] {
+        cache.request_tile(tile_id, feature_collection.clone(), &[]);
+        let start = std::time::Instant::now();
+        while cache.try_get_tile(tile_id).is_none() {
+            assert!(start.elapsed().as_secs() < 10, "Tile did not load in time.");
+            cache.finalize_loaded_tiles();
+            std::thread::yield_now();
+        }
+    }
+
+    // The unused tile loaded before the last one was evicted, the tile in use was kept.
+    let stats = cache.get_stats();
+    assert_eq!(stats.evicted_tiles, 1);
+    assert_eq!(stats.cached_tiles, 2);
+    assert!(stats.resident_bytes <= cache.memory_budget);
+    assert!(cache.try_get_tile(&tile_ids[0]).is_some());
+    assert!(cache.try_get_tile(&tile_ids[1]).is_none());
+}
+
+#[test]
+fn record_and_retry_failed_tiles() {
+    let broken = TileId::new(8, 142, 93);
+    let missing = TileId::new(8, 0, 0);
+    let source = MemoryTileSource::new().with_tile(broken, &b"not a vector tile"[..]);
+    let feature_collection = Arc::new(RwLock::new(FeatureCollection::new(500)));
+    let mut cache = TileCache::new(Box::new(source));
+
+    cache.request_tile(&broken, feature_collection.clone(), &[]);
+    cache.request_tile(&missing, feature_collection.clone(), &[]);
+
+    let start = std::time::Instant::now();
+    while cache.failed_tiles().len() < 2 {
+        assert!(
+            start.elapsed().as_secs() < 10,
+            "Tiles did not fail in time."
+        );
+        cache.finalize_loaded_tiles();
+        std::thread::yield_now();
+    }
+
+    assert_eq!(cache.failed_tiles()[&missing], TileError::Unavailable);
+    assert!(matches!(
+        cache.failed_tiles()[&broken],
+        TileError::Decode(_)
+    ));
+
+    // Failed tiles are not requested again until they are retried.
+    cache.request_tile(&broken, feature_collection.clone(), &[]);
+    assert_eq!(cache.get_stats().loading_tiles, 0);
+    cache.retry_tile(&broken);
+    cache.request_tile(&broken, feature_collection, &[]);
+    assert_eq!(cache.get_stats().loading_tiles, 1);
+    assert!(!cache.failed_tiles().contains_key(&broken));
+}
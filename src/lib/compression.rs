@@ -0,0 +1,104 @@
+use std::borrow::Cow;
+use std::io::{Read, Write};
+
+/// The compression of a tile payload as detected from its magic bytes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TileCompression {
+    None,
+    Gzip,
+    Zlib,
+}
+
+impl TileCompression {
+    /// Detects the compression of `data` from its first bytes.
+    ///
+    /// A raw MVT always starts with the tag of its first layer (`0x1a`),
+    /// so it can not be mistaken for a gzip or zlib header.
+    pub fn detect(data: &[u8]) -> Self {
+        match data {
+            [0x1f, 0x8b, ..] => TileCompression::Gzip,
+            // A zlib header uses deflate (CM = 8) and its first two bytes are a multiple of 31.
+            [cmf, flg, ..]
+                if cmf & 0x0f == 8 && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0 =>
+            {
+                TileCompression::Zlib
+            }
+            _ => TileCompression::None,
+        }
+    }
+}
+
+/// Decompresses gzip or zlib compressed tile data and passes uncompressed data through unchanged.
+pub fn decompress_tile_data(data: &[u8]) -> std::io::Result<Cow<'_, [u8]>> {
+    let mut decompressed = vec![];
+    match TileCompression::detect(data) {
+        TileCompression::None => return Ok(Cow::Borrowed(data)),
+        TileCompression::Gzip => {
+            flate2::read::GzDecoder::new(data).read_to_end(&mut decompressed)?;
+        }
+        TileCompression::Zlib => {
+            flate2::read::ZlibDecoder::new(data).read_to_end(&mut decompressed)?;
+        }
+    }
+    Ok(Cow::Owned(decompressed))
+}
+
+/// Gzip compresses tile data unless it is compressed already.
+pub fn compress_tile_data(data: &[u8]) -> std::io::Result<Cow<'_, [u8]>> {
+    if TileCompression::detect(data) != TileCompression::None {
+        return Ok(Cow::Borrowed(data));
+    }
+
+    let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+    encoder.write_all(data)?;
+    Ok(Cow::Owned(encoder.finish()?))
+}
+
+#[test]
+fn detect_and_decompress() {
+    let data = &include_bytes!("../../data/8_142_93.pbf")[..];
+    let gzip = &include_bytes!("../../data/8_142_93.pbf.gz")[..];
+    let mut encoder = flate2::write::ZlibEncoder::new(vec![], flate2::Compression::default());
+    encoder.write_all(data).unwrap();
+    let zlib = encoder.finish().unwrap();
+
+    assert_eq!(TileCompression::detect(data), TileCompression::None);
+    assert_eq!(TileCompression::detect(gzip), TileCompression::Gzip);
+    assert_eq!(TileCompression::detect(&zlib), TileCompression::Zlib);
+
+    assert_eq!(&decompress_tile_data(data).unwrap()[..], data);
+    assert_eq!(&decompress_tile_data(gzip).unwrap()[..], data);
+    assert_eq!(&decompress_tile_data(&zlib).unwrap()[..], data);
+    assert_eq!(
+        &decompress_tile_data(&compress_tile_data(data).unwrap()).unwrap()[..],
+        data
+    );
+    // Truncated data is reported instead of being passed on.
+    assert!(decompress_tile_data(&gzip[..gzip.len() / 2]).is_err());
+}
+
+#[test]
+fn load_compressed_tile() {
+    use crate::*;
+    use std::sync::{Arc, RwLock};
+
+    let tile_id = TileId::new(8, 142, 93);
+    let feature_collection = Arc::new(RwLock::new(FeatureCollection::new(500)));
+    let raw = Tile::from_mbvt(
+        &tile_id,
+        include_bytes!("../../data/8_142_93.pbf"),
+        feature_collection.clone(),
+        vec![],
+    )
+    .unwrap();
+    let compressed = Tile::from_mbvt(
+        &tile_id,
+        include_bytes!("../../data/8_142_93.pbf.gz"),
+        feature_collection,
+        vec![],
+    )
+    .unwrap();
+
+    assert_eq!(compressed.stats().vertices, raw.stats().vertices);
+    assert_eq!(compressed.stats().features, raw.stats().features);
+}
@@ -0,0 +1,497 @@
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_while, take_while_m_n},
+    character::complete::{char, multispace0},
+    combinator::{eof, opt, peek},
+    error::{ErrorKind, FromExternalError, ParseError},
+    number::complete::float,
+    sequence::{terminated, tuple},
+    IResult,
+};
+use std::num::ParseIntError;
+
+use super::*;
+
+/// A struct to represent any RGB color.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Color {
+    pub const TRANSPARENT: Color = Color {
+        r: 0.0,
+        g: 0.0,
+        b: 0.0,
+        a: 0.0,
+    };
+    pub const WHITE: Color = Color {
+        r: 1.0,
+        g: 1.0,
+        b: 1.0,
+        a: 1.0,
+    };
+    pub const BLACK: Color = Color {
+        r: 0.0,
+        g: 0.0,
+        b: 0.0,
+        a: 1.0,
+    };
+    pub const RED: Color = Color {
+        r: 1.0,
+        g: 0.0,
+        b: 0.0,
+        a: 1.0,
+    };
+    pub const GREEN: Color = Color {
+        r: 0.0,
+        g: 1.0,
+        b: 0.0,
+        a: 1.0,
+    };
+    pub const BLUE: Color = Color {
+        r: 0.0,
+        g: 0.0,
+        b: 1.0,
+        a: 1.0,
+    };
+
+    /// Creates a color from 8 bit RGB channels and an alpha between 0 and 1.
+    pub fn from_rgba8(r: u8, g: u8, b: u8, a: f32) -> Self {
+        Self {
+            r: r as f32 / 255.0,
+            g: g as f32 / 255.0,
+            b: b as f32 / 255.0,
+            a,
+        }
+    }
+
+    /// Creates a color from a hue in degrees and saturation, lightness and alpha between 0 and 1.
+    pub fn from_hsla(h: f32, s: f32, l: f32, a: f32) -> Self {
+        let s = s.clamp(0.0, 1.0);
+        let l = l.clamp(0.0, 1.0);
+
+        // The conversion from the CSS Color Module, which keeps the primary colors exact.
+        let chroma = s * l.min(1.0 - l);
+        let channel = |n: f32| {
+            let k = (n + h / 30.0).rem_euclid(12.0);
+            l - chroma * (k - 3.0).min(9.0 - k).clamp(-1.0, 1.0)
+        };
+
+        Self {
+            r: channel(0.0),
+            g: channel(8.0),
+            b: channel(4.0),
+            a,
+        }
+    }
+
+    /// Looks up a CSS named color, ignoring case.
+    ///
+    /// Includes `transparent`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase();
+        if name == "transparent" {
+            return Some(Color::TRANSPARENT);
+        }
+        NAMED_COLORS
+            .binary_search_by(|(n, _)| n.cmp(&&name[..]))
+            .ok()
+            .map(|i| {
+                let rgb = NAMED_COLORS[i].1;
+                Self::from_rgba8((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8, 1.0)
+            })
+    }
+}
+
+/// All CSS named colors sorted by name.
+const NAMED_COLORS: [(&str, u32); 148] = [
+    ("aliceblue", 0xf0f8ff),
+    ("antiquewhite", 0xfaebd7),
+    ("aqua", 0x00ffff),
+    ("aquamarine", 0x7fffd4),
+    ("azure", 0xf0ffff),
+    ("beige", 0xf5f5dc),
+    ("bisque", 0xffe4c4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xffebcd),
+    ("blue", 0x0000ff),
+    ("blueviolet", 0x8a2be2),
+    ("brown", 0xa52a2a),
+    ("burlywood", 0xdeb887),
+    ("cadetblue", 0x5f9ea0),
+    ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e),
+    ("coral", 0xff7f50),
+    ("cornflowerblue", 0x6495ed),
+    ("cornsilk", 0xfff8dc),
+    ("crimson", 0xdc143c),
+    ("cyan", 0x00ffff),
+    ("darkblue", 0x00008b),
+    ("darkcyan", 0x008b8b),
+    ("darkgoldenrod", 0xb8860b),
+    ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xa9a9a9),
+    ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b),
+    ("darkolivegreen", 0x556b2f),
+    ("darkorange", 0xff8c00),
+    ("darkorchid", 0x9932cc),
+    ("darkred", 0x8b0000),
+    ("darksalmon", 0xe9967a),
+    ("darkseagreen", 0x8fbc8f),
+    ("darkslateblue", 0x483d8b),
+    ("darkslategray", 0x2f4f4f),
+    ("darkslategrey", 0x2f4f4f),
+    ("darkturquoise", 0x00ced1),
+    ("darkviolet", 0x9400d3),
+    ("deeppink", 0xff1493),
+    ("deepskyblue", 0x00bfff),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff),
+    ("firebrick", 0xb22222),
+    ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22),
+    ("fuchsia", 0xff00ff),
+    ("gainsboro", 0xdcdcdc),
+    ("ghostwhite", 0xf8f8ff),
+    ("gold", 0xffd700),
+    ("goldenrod", 0xdaa520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xadff2f),
+    ("grey", 0x808080),
+    ("honeydew", 0xf0fff0),
+    ("hotpink", 0xff69b4),
+    ("indianred", 0xcd5c5c),
+    ("indigo", 0x4b0082),
+    ("ivory", 0xfffff0),
+    ("khaki", 0xf0e68c),
+    ("lavender", 0xe6e6fa),
+    ("lavenderblush", 0xfff0f5),
+    ("lawngreen", 0x7cfc00),
+    ("lemonchiffon", 0xfffacd),
+    ("lightblue", 0xadd8e6),
+    ("lightcoral", 0xf08080),
+    ("lightcyan", 0xe0ffff),
+    ("lightgoldenrodyellow", 0xfafad2),
+    ("lightgray", 0xd3d3d3),
+    ("lightgreen", 0x90ee90),
+    ("lightgrey", 0xd3d3d3),
+    ("lightpink", 0xffb6c1),
+    ("lightsalmon", 0xffa07a),
+    ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de),
+    ("lightyellow", 0xffffe0),
+    ("lime", 0x00ff00),
+    ("limegreen", 0x32cd32),
+    ("linen", 0xfaf0e6),
+    ("magenta", 0xff00ff),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66cdaa),
+    ("mediumblue", 0x0000cd),
+    ("mediumorchid", 0xba55d3),
+    ("mediumpurple", 0x9370db),
+    ("mediumseagreen", 0x3cb371),
+    ("mediumslateblue", 0x7b68ee),
+    ("mediumspringgreen", 0x00fa9a),
+    ("mediumturquoise", 0x48d1cc),
+    ("mediumvioletred", 0xc71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xf5fffa),
+    ("mistyrose", 0xffe4e1),
+    ("moccasin", 0xffe4b5),
+    ("navajowhite", 0xffdead),
+    ("navy", 0x000080),
+    ("oldlace", 0xfdf5e6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6b8e23),
+    ("orange", 0xffa500),
+    ("orangered", 0xff4500),
+    ("orchid", 0xda70d6),
+    ("palegoldenrod", 0xeee8aa),
+    ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee),
+    ("palevioletred", 0xdb7093),
+    ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9),
+    ("peru", 0xcd853f),
+    ("pink", 0xffc0cb),
+    ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xff0000),
+    ("rosybrown", 0xbc8f8f),
+    ("royalblue", 0x4169e1),
+    ("saddlebrown", 0x8b4513),
+    ("salmon", 0xfa8072),
+    ("sandybrown", 0xf4a460),
+    ("seagreen", 0x2e8b57),
+    ("seashell", 0xfff5ee),
+    ("sienna", 0xa0522d),
+    ("silver", 0xc0c0c0),
+    ("skyblue", 0x87ceeb),
+    ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xfffafa),
+    ("springgreen", 0x00ff7f),
+    ("steelblue", 0x4682b4),
+    ("tan", 0xd2b48c),
+    ("teal", 0x008080),
+    ("thistle", 0xd8bfd8),
+    ("tomato", 0xff6347),
+    ("turquoise", 0x40e0d0),
+    ("violet", 0xee82ee),
+    ("wheat", 0xf5deb3),
+    ("white", 0xffffff),
+    ("whitesmoke", 0xf5f5f5),
+    ("yellow", 0xffff00),
+    ("yellowgreen", 0x9acd32),
+];
+
+impl std::fmt::Display for Color {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let channel = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+        if self.a >= 1.0 {
+            write!(
+                f,
+                "#{:02x}{:02x}{:02x}",
+                channel(self.r),
+                channel(self.g),
+                channel(self.b)
+            )
+        } else {
+            write!(
+                f,
+                "rgba({}, {}, {}, {})",
+                channel(self.r),
+                channel(self.g),
+                channel(self.b),
+                self.a
+            )
+        }
+    }
+}
+
+/// Parses any CSS color value.
+/// E.g. `#FF0000`, `rgba(255, 0, 0, 0.5)`, `hsl(0, 100%, 50%)` or `red`.
+pub(super) fn color<'a, E>(input: &'a str) -> IResult<&'a str, CSSValue, E>
+where
+    E: ParseError<&'a str> + FromExternalError<&'a str, ParseIntError>,
+{
+    let (input, color) = alt((
+        hex_color,
+        rgba_color,
+        rgb_color,
+        hsla_color,
+        hsl_color,
+        named_color,
+    ))(input)?;
+    Ok((input, CSSValue::Color(color)))
+}
+
+/// Converts a hex string into an `u8`.
+fn from_hex(input: &str) -> Result<u8, std::num::ParseIntError> {
+    u8::from_str_radix(input, 16)
+}
+
+/// `true` if `c` is a hexadecimal valid digit.
+fn is_hex_digit(c: char) -> bool {
+    c.is_ascii_hexdigit()
+}
+
+/// Parse a single hex color code including the `#`.
+/// Supports the `#rgb`, `#rgba`, `#rrggbb` and `#rrggbbaa` notations.
+fn hex_color<'a, E>(input: &'a str) -> IResult<&'a str, Color, E>
+where
+    E: ParseError<&'a str> + FromExternalError<&'a str, ParseIntError>,
+{
+    let (remaining, _) = tag("#")(input)?;
+    let (remaining, digits) = take_while_m_n(3, 8, is_hex_digit)(remaining)?;
+
+    let channel = |digits: &str| {
+        let value = from_hex(digits)
+            .map_err(|e| nom::Err::Error(E::from_external_error(input, ErrorKind::HexDigit, e)))?;
+        // Short notations repeat every digit, so `f` is the same as `ff`.
+        Ok(if digits.len() == 1 { value * 17 } else { value })
+    };
+    let channels = match digits.len() {
+        3 | 4 => (0..digits.len())
+            .map(|i| channel(&digits[i..i + 1]))
+            .collect::<Result<Vec<_>, _>>()?,
+        6 | 8 => (0..digits.len() / 2)
+            .map(|i| channel(&digits[2 * i..2 * i + 2]))
+            .collect::<Result<Vec<_>, _>>()?,
+        _ => {
+            return Err(nom::Err::Error(E::from_error_kind(
+                input,
+                ErrorKind::HexDigit,
+            )))
+        }
+    };
+
+    let a = channels.get(3).map_or(1.0, |a| *a as f32 / 255.0);
+    Ok((
+        remaining,
+        Color::from_rgba8(channels[0], channels[1], channels[2], a),
+    ))
+}
+
+fn u8<'a, E>(input: &'a str) -> IResult<&'a str, u8, E>
+where
+    E: ParseError<&'a str> + FromExternalError<&'a str, ParseIntError>,
+{
+    use std::str::FromStr;
+    nom::combinator::map_res(take_while(|c: char| c.is_ascii_digit()), u8::from_str)(input)
+}
+
+/// Parse a single `rgba()` color.
+fn rgba_color<'a, E>(input: &'a str) -> IResult<&'a str, Color, E>
+where
+    E: ParseError<&'a str> + FromExternalError<&'a str, ParseIntError>,
+{
+    let (input, _) = whitespace(tag("rgba("))(input)?;
+    let (input, (r, _, g, _, b, _, a)) = tuple((
+        u8,
+        whitespace(char(',')),
+        u8,
+        whitespace(char(',')),
+        u8,
+        whitespace(char(',')),
+        float,
+    ))(input)?;
+    let (input, _) = tag(")")(input)?;
+    Ok((input, Color::from_rgba8(r, g, b, a)))
+}
+
+/// Parse a single `rgb()` color.
+fn rgb_color<'a, E>(input: &'a str) -> IResult<&'a str, Color, E>
+where
+    E: ParseError<&'a str> + FromExternalError<&'a str, ParseIntError>,
+{
+    let (input, _) = whitespace(tag("rgb("))(input)?;
+    let (input, (r, _, g, _, b)) =
+        tuple((u8, whitespace(char(',')), u8, whitespace(char(',')), u8))(input)?;
+    let (input, _) = tag(")")(input)?;
+    Ok((input, Color::from_rgba8(r, g, b, 1.0)))
+}
+
+/// Parses a percentage into a fraction between 0 and 1.
+/// E.g. `50%`.
+fn percentage<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, f32, E> {
+    let (input, value) = terminated(float, char('%'))(input)?;
+    Ok((input, value / 100.0))
+}
+
+/// Parses a hue in degrees with an optional `deg` unit.
+fn hue<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, f32, E> {
+    terminated(float, opt(tag("deg")))(input)
+}
+
+/// Parse a single `hsla()` color.
+/// E.g. `hsla(120, 100%, 50%, 0.5)`.
+fn hsla_color<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, Color, E> {
+    let (input, _) = whitespace(tag("hsla("))(input)?;
+    let (input, (h, _, s, _, l, _, a)) = tuple((
+        hue,
+        whitespace(char(',')),
+        percentage,
+        whitespace(char(',')),
+        percentage,
+        whitespace(char(',')),
+        float,
+    ))(input)?;
+    let (input, _) = tag(")")(input)?;
+    Ok((input, Color::from_hsla(h, s, l, a)))
+}
+
+/// Parse a single `hsl()` color.
+/// E.g. `hsl(120, 100%, 50%)`.
+fn hsl_color<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, Color, E> {
+    let (input, _) = whitespace(tag("hsl("))(input)?;
+    let (input, (h, _, s, _, l)) = tuple((
+        hue,
+        whitespace(char(',')),
+        percentage,
+        whitespace(char(',')),
+        percentage,
+    ))(input)?;
+    let (input, _) = tag(")")(input)?;
+    Ok((input, Color::from_hsla(h, s, l, 1.0)))
+}
+
+/// Parse a CSS named color or `transparent`.
+///
+/// The name has to be the entire value or function argument, so `red` matches but `red line` does not.
+fn named_color<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, Color, E> {
+    let (remaining, name) = terminated(
+        take_while(|c: char| c.is_ascii_alphabetic()),
+        peek(tuple((
+            multispace0,
+            alt((tag(";"), tag("}"), tag(","), tag(")"), eof)),
+        ))),
+    )(input)?;
+
+    match Color::from_name(name) {
+        Some(color) => Ok((remaining, color)),
+        None => Err(nom::Err::Error(E::from_error_kind(input, ErrorKind::Tag))),
+    }
+}
+
+#[cfg(test)]
+fn parse_color(input: &str) -> Option<Color> {
+    match color::<nom::error::VerboseError<&str>>(input) {
+        Ok((_, CSSValue::Color(color))) => Some(color),
+        _ => None,
+    }
+}
+
+#[test]
+fn parse_named_colors() {
+    assert_eq!(parse_color("red;"), Some(Color::RED));
+    assert_eq!(parse_color("Blue;"), Some(Color::BLUE));
+    assert_eq!(parse_color("transparent;"), Some(Color::TRANSPARENT));
+    assert_eq!(
+        parse_color("rebeccapurple;"),
+        Some(Color::from_rgba8(0x66, 0x33, 0x99, 1.0))
+    );
+    assert_eq!(parse_color("redish;"), None);
+    assert_eq!(parse_color("red line;"), None);
+    assert!(NAMED_COLORS.windows(2).all(|w| w[0].0 < w[1].0));
+}
+
+#[test]
+fn parse_hex_colors() {
+    assert_eq!(parse_color("#f00;"), Some(Color::RED));
+    assert_eq!(parse_color("#0000FF;"), Some(Color::BLUE));
+    assert_eq!(
+        parse_color("#0f08;"),
+        Some(Color::from_rgba8(0, 255, 0, 136.0 / 255.0))
+    );
+    assert_eq!(
+        parse_color("#00ff0080;"),
+        Some(Color::from_rgba8(0, 255, 0, 128.0 / 255.0))
+    );
+    assert_eq!(parse_color("#00ff0;"), None);
+}
+
+#[test]
+fn parse_hsl_colors() {
+    assert_eq!(parse_color("hsl(0, 100%, 50%);"), Some(Color::RED));
+    assert_eq!(parse_color("hsl(240deg, 100%, 50%);"), Some(Color::BLUE));
+    assert_eq!(
+        parse_color("hsla(120, 100%, 50%, 0.5);"),
+        Some(Color::from_rgba8(0, 255, 0, 0.5))
+    );
+    assert_eq!(parse_color("hsl(0, 0%, 100%);"), Some(Color::WHITE));
+}
@@ -0,0 +1,262 @@
+use std::path::{Path, PathBuf};
+
+use super::*;
+
+/// How bad a problem in a stylesheet is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The stylesheet is used, but parts of it have no effect.
+    Warning,
+    /// The stylesheet can not be used.
+    Error,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A problem found while loading a stylesheet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StyleDiagnostic {
+    pub file: PathBuf,
+    /// The line of the problem, starting at 1.
+    pub line: usize,
+    /// The column of the problem in characters, starting at 1.
+    pub column: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl StyleDiagnostic {
+    /// Creates a diagnostic for the position of `at`, which has to be a slice of `style`.
+    pub(super) fn new(
+        file: &Path,
+        style: &str,
+        at: &str,
+        severity: Severity,
+        message: impl Into<String>,
+    ) -> Self {
+        let offset = (at.as_ptr() as usize)
+            .saturating_sub(style.as_ptr() as usize)
+            .min(style.len());
+        let before = &style[..offset];
+        Self {
+            file: file.to_path_buf(),
+            line: before.matches('\n').count() + 1,
+            column: before.chars().rev().take_while(|c| *c != '\n').count() + 1,
+            severity,
+            message: message.into(),
+        }
+    }
+
+    /// Creates a diagnostic for an entire file.
+    pub(super) fn for_file(file: &Path, severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            file: file.to_path_buf(),
+            line: 1,
+            column: 1,
+            severity,
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for StyleDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {}: {}",
+            self.file.display(),
+            self.line,
+            self.column,
+            self.severity,
+            self.message
+        )
+    }
+}
+
+/// The kind of value a property accepts.
+#[derive(Debug, Clone, Copy)]
+enum ValueKind {
+    Color,
+    /// A number in `px`.
+    Pixels,
+    /// A number in `px` or `w`.
+    Width,
+    Unitless,
+    Keyword,
+    /// One of the given keywords.
+    OneOf(&'static [&'static str]),
+    /// `none` or one, two or four numbers without unit.
+    Dashes,
+}
+
+impl ValueKind {
+    fn accepts(&self, value: &CSSValue) -> bool {
+        match (self, value) {
+            (_, CSSValue::Function(function)) => function
+                .default
+                .iter()
+                .map(|default| &**default)
+                .chain(function.stops.iter().map(|(_, value)| value))
+                .all(|value| self.accepts(value)),
+            // The value of a custom property is only known once the style is computed.
+            (_, CSSValue::Var(..)) => true,
+            (ValueKind::Color, CSSValue::Color(_)) => true,
+            (ValueKind::Pixels, CSSValue::Number(Number::Px(_))) => true,
+            (ValueKind::Width, CSSValue::Number(Number::Px(_)))
+            | (ValueKind::Width, CSSValue::Number(Number::World(_))) => true,
+            (ValueKind::Unitless, CSSValue::Number(Number::Unitless(_))) => true,
+            (ValueKind::Keyword, CSSValue::String(_)) => true,
+            (ValueKind::OneOf(keywords), CSSValue::String(value)) => keywords.contains(&&value[..]),
+            (ValueKind::Dashes, CSSValue::String(value)) => value == "none",
+            (ValueKind::Dashes, CSSValue::Number(Number::Unitless(_))) => true,
+            (ValueKind::Dashes, CSSValue::Numbers(numbers)) => {
+                matches!(numbers.len(), 2 | 4)
+                    && numbers.iter().all(|n| matches!(n, Number::Unitless(_)))
+            }
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Display for ValueKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValueKind::Color => write!(f, "a color"),
+            ValueKind::Pixels => write!(f, "a number in px"),
+            ValueKind::Width => write!(f, "a number in px or w"),
+            ValueKind::Unitless => write!(f, "a number without unit"),
+            ValueKind::Keyword => write!(f, "a keyword"),
+            ValueKind::OneOf(keywords) => {
+                let (last, others) = keywords.split_last().unwrap_or((&"", &[]));
+                let others = others
+                    .iter()
+                    .map(|k| format!("'{}'", k))
+                    .collect::<Vec<_>>();
+                write!(f, "one of {} or '{}'", others.join(", "), last)
+            }
+            ValueKind::Dashes => write!(f, "'none' or one, two or four numbers without unit"),
+        }
+    }
+}
+
+/// All properties the renderer understands.
+const PROPERTIES: &[(&str, ValueKind)] = &[
+    ("background-color", ValueKind::Color),
+    ("border-color", ValueKind::Color),
+    ("border-width", ValueKind::Pixels),
+    ("display", ValueKind::Keyword),
+    ("line-cap", ValueKind::OneOf(&["butt", "round", "square"])),
+    ("line-dasharray", ValueKind::Dashes),
+    ("line-join", ValueKind::OneOf(&["miter", "bevel", "round"])),
+    ("line-width", ValueKind::Width),
+    ("z-index", ValueKind::Unitless),
+];
+
+/// Checks a single declaration for unknown properties and values of the wrong kind.
+///
+/// Returns where the problem is and a description of it.
+pub(super) fn check_declaration<'a>(declaration: &Declaration<'a>) -> Option<(&'a str, String)> {
+    let key = declaration.key;
+    if key.starts_with("--") {
+        return None;
+    }
+    match PROPERTIES.iter().find(|(property, _)| *property == key) {
+        Some((_, kind)) if kind.accepts(&declaration.value) => None,
+        Some((_, kind)) => Some((
+            declaration.value_at,
+            format!(
+                "'{}' expects {}, but the value is {:?}.",
+                key, kind, declaration.value
+            ),
+        )),
+        None => Some((key, format!("Unknown property '{}'.", key))),
+    }
+}
+
+/// Checks whether a selector can match any object.
+pub(super) fn check_selector(selector: &Selector) -> Option<String> {
+    let unknown_theme = selector
+        .any
+        .iter()
+        .find(|a| a.key == "theme" && Theme::from_name(&a.value).is_none());
+    if let Some(attribute) = unknown_theme {
+        return Some(format!(
+            "Unknown theme '{}', use 'day', 'dusk' or 'night'.",
+            attribute.value
+        ));
+    }
+
+    match selector.typ.as_deref() {
+        None | Some("background") => None,
+        Some("layer") => selector
+            .any
+            .iter()
+            .filter(|a| a.key == "name" && a.operator == Operator::Eq && !a.negated)
+            .find(|a| !crate::KNOWN_LAYERS.contains(&&a.value[..]))
+            .map(|a| format!("There is no layer named '{}'.", a.value)),
+        Some(typ) => Some(format!(
+            "Unknown type '{}', use 'layer' or 'background'.",
+            typ
+        )),
+    }
+}
+
+#[test]
+fn report_style_problems() {
+    let style = "layer[name=water] {\n    line-width: red;\n    colour: red;\n}\n\nlayer[name=sea] { }\nnode { }\nlayer { line-width 2px; }";
+    let (rules, diagnostics) = parse_styles(style, Path::new("style.css"));
+    assert!(rules.is_none());
+
+    let locations = diagnostics
+        .iter()
+        .map(|d| (d.line, d.column, d.severity))
+        .collect::<Vec<_>>();
+    assert_eq!(locations, vec![(8, 20, Severity::Error)]);
+    assert_eq!(
+        diagnostics[0].to_string(),
+        "style.css:8:20: error: Expected ':'."
+    );
+
+    let (rules, diagnostics) = parse_styles(
+        &style[..style.rfind("layer {").unwrap()],
+        Path::new("style.css"),
+    );
+    assert_eq!(rules.unwrap().len(), 3);
+    let locations = diagnostics
+        .iter()
+        .map(|d| (d.line, d.column, d.severity))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        locations,
+        vec![
+            (2, 17, Severity::Warning),
+            (3, 5, Severity::Warning),
+            (6, 1, Severity::Warning),
+            (7, 1, Severity::Warning),
+        ]
+    );
+}
+
+#[test]
+fn check_line_styles() {
+    let style = "layer { line-dasharray: 4 2; line-cap: round; line-join: bevel; }\nlayer { line-dasharray: 1 2 3; line-cap: rounded; }";
+    let (rules, diagnostics) = parse_styles(style, Path::new("style.css"));
+    assert_eq!(rules.unwrap().len(), 2);
+
+    let locations = diagnostics
+        .iter()
+        .map(|d| (d.line, d.column))
+        .collect::<Vec<_>>();
+    assert_eq!(locations, vec![(2, 25), (2, 42)]);
+    assert_eq!(
+        diagnostics[1].message,
+        "'line-cap' expects one of 'butt', 'round' or 'square', but the value is String(\"rounded\")."
+    );
+}
@@ -0,0 +1,245 @@
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::char,
+    combinator::{map, opt, value},
+    error::{FromExternalError, ParseError},
+    multi::many1,
+    number::complete::float,
+    sequence::{preceded, separated_pair, tuple},
+    IResult,
+};
+use std::num::ParseIntError;
+
+use super::*;
+
+/// How a zoom dependent value changes between two stops.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interpolation {
+    /// Changes linearly from one stop to the next.
+    Linear,
+    /// Changes exponentially with the given base, which is `1.0` for linear.
+    Exponential(f32),
+    /// Jumps to the value of a stop once it is reached.
+    Step,
+}
+
+/// A value which depends on the fractional zoom level.
+/// E.g. `interpolate(linear, zoom, 8: 1px, 14: 4px)` or `step(zoom, 1px, 10: 2px)`.
+#[derive(Debug, Clone)]
+pub struct ZoomFunction {
+    pub interpolation: Interpolation,
+    /// The value before the first stop.
+    /// Only used by `step()`, `interpolate()` uses the value of the first stop instead.
+    pub default: Option<Box<CSSValue>>,
+    /// The zoom levels and their values, sorted by zoom.
+    pub stops: Vec<(f32, CSSValue)>,
+}
+
+impl ZoomFunction {
+    /// Evaluates the function at the fractional zoom level `zoom`.
+    pub fn evaluate(&self, zoom: f32) -> CSSValue {
+        let upper = self.stops.iter().position(|(z, _)| *z > zoom);
+        let (lower, upper) = match upper {
+            Some(0) => {
+                return match &self.default {
+                    Some(default) => (**default).clone(),
+                    None => self.stops[0].1.clone(),
+                }
+            }
+            Some(i) => (&self.stops[i - 1], &self.stops[i]),
+            None => match self.stops.last() {
+                Some((_, value)) => return value.clone(),
+                None => return CSSValue::String(String::new()),
+            },
+        };
+
+        let t = match self.interpolation {
+            Interpolation::Step => return lower.1.clone(),
+            Interpolation::Linear => (zoom - lower.0) / (upper.0 - lower.0),
+            Interpolation::Exponential(base) if (base - 1.0).abs() < f32::EPSILON => {
+                (zoom - lower.0) / (upper.0 - lower.0)
+            }
+            Interpolation::Exponential(base) => {
+                (base.powf(zoom - lower.0) - 1.0) / (base.powf(upper.0 - lower.0) - 1.0)
+            }
+        };
+
+        interpolate(&lower.1, &upper.1, t)
+    }
+}
+
+impl std::fmt::Display for ZoomFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.interpolation {
+            Interpolation::Linear => write!(f, "interpolate(linear, zoom")?,
+            Interpolation::Exponential(base) => {
+                write!(f, "interpolate(exponential {}, zoom", base)?
+            }
+            Interpolation::Step => write!(f, "step(zoom")?,
+        }
+        if let Some(default) = &self.default {
+            write!(f, ", {}", default)?;
+        }
+        for (zoom, value) in &self.stops {
+            write!(f, ", {}: {}", zoom, value)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Interpolates between two values with `t` between 0 and 1.
+///
+/// Values which can not be interpolated, like numbers with different units,
+/// keep the lower value until the upper stop is reached.
+fn interpolate(lower: &CSSValue, upper: &CSSValue, t: f32) -> CSSValue {
+    let lerp = |a: f32, b: f32| a + (b - a) * t;
+    match (lower, upper) {
+        (CSSValue::Number(Number::Px(a)), CSSValue::Number(Number::Px(b))) => {
+            CSSValue::Number(Number::Px(lerp(*a, *b)))
+        }
+        (CSSValue::Number(Number::World(a)), CSSValue::Number(Number::World(b))) => {
+            CSSValue::Number(Number::World(lerp(*a, *b)))
+        }
+        (CSSValue::Number(Number::Unitless(a)), CSSValue::Number(Number::Unitless(b))) => {
+            CSSValue::Number(Number::Unitless(lerp(*a, *b)))
+        }
+        (CSSValue::Color(a), CSSValue::Color(b)) => CSSValue::Color(Color {
+            r: lerp(a.r, b.r),
+            g: lerp(a.g, b.g),
+            b: lerp(a.b, b.b),
+            a: lerp(a.a, b.a),
+        }),
+        (lower, _) => lower.clone(),
+    }
+}
+
+/// Parses a zoom dependent value.
+/// E.g. `interpolate(linear, zoom, 8: 1px, 14: 4w)` or `step(zoom, 1px, 10: 2px)`.
+pub(super) fn zoom_function<'a, E>(input: &'a str) -> IResult<&'a str, CSSValue, E>
+where
+    E: ParseError<&'a str> + FromExternalError<&'a str, ParseIntError>,
+{
+    let (input, function) = alt((interpolate_function, step_function))(input)?;
+    Ok((input, CSSValue::Function(function)))
+}
+
+/// Parse an `interpolate()` function.
+fn interpolate_function<'a, E>(input: &'a str) -> IResult<&'a str, ZoomFunction, E>
+where
+    E: ParseError<&'a str> + FromExternalError<&'a str, ParseIntError>,
+{
+    let (input, _) = whitespace(tag("interpolate("))(input)?;
+    let (input, interpolation) = alt((
+        value(Interpolation::Linear, tag("linear")),
+        map(
+            preceded(whitespace(tag("exponential")), float),
+            Interpolation::Exponential,
+        ),
+    ))(input)?;
+    let (input, _) = tuple((whitespace(char(',')), tag("zoom")))(input)?;
+    let (input, stops) = stops(input)?;
+    let (input, _) = whitespace(char(')'))(input)?;
+    Ok((
+        input,
+        ZoomFunction {
+            interpolation,
+            default: None,
+            stops,
+        },
+    ))
+}
+
+/// Parse a `step()` function.
+fn step_function<'a, E>(input: &'a str) -> IResult<&'a str, ZoomFunction, E>
+where
+    E: ParseError<&'a str> + FromExternalError<&'a str, ParseIntError>,
+{
+    let (input, _) = tuple((whitespace(tag("step(")), tag("zoom")))(input)?;
+    let (input, default) = opt(preceded(whitespace(char(',')), stop_value))(input)?;
+    let (input, stops) = stops(input)?;
+    let (input, _) = whitespace(char(')'))(input)?;
+    Ok((
+        input,
+        ZoomFunction {
+            interpolation: Interpolation::Step,
+            default: default.map(Box::new),
+            stops,
+        },
+    ))
+}
+
+/// Parse the `zoom: value` stops of a function, each preceded by a comma.
+fn stops<'a, E>(input: &'a str) -> IResult<&'a str, Vec<(f32, CSSValue)>, E>
+where
+    E: ParseError<&'a str> + FromExternalError<&'a str, ParseIntError>,
+{
+    let (input, mut stops) = many1(preceded(
+        whitespace(char(',')),
+        separated_pair(float, whitespace(char(':')), stop_value),
+    ))(input)?;
+    stops.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+    Ok((input, stops))
+}
+
+/// Parse the value of a single stop.
+fn stop_value<'a, E>(input: &'a str) -> IResult<&'a str, CSSValue, E>
+where
+    E: ParseError<&'a str> + FromExternalError<&'a str, ParseIntError>,
+{
+    alt((
+        whitespace(color),
+        whitespace(px_value),
+        whitespace(world_value),
+        whitespace(unitless_value),
+    ))(input)
+}
+
+#[cfg(test)]
+fn parse_function(input: &str) -> ZoomFunction {
+    match zoom_function::<nom::error::VerboseError<&str>>(input) {
+        Ok((_, CSSValue::Function(function))) => function,
+        result => panic!("Failed to parse '{}': {:?}", input, result),
+    }
+}
+
+#[test]
+fn evaluate_interpolate() {
+    let function = parse_function("interpolate(linear, zoom, 8: 1px, 14: 4px, 16: 4w)");
+    let px = |zoom| match function.evaluate(zoom) {
+        CSSValue::Number(Number::Px(px)) => px,
+        value => panic!("Unexpected value {:?}", value),
+    };
+    assert_eq!(px(2.0), 1.0);
+    assert_eq!(px(8.0), 1.0);
+    assert_eq!(px(11.0), 2.5);
+    assert_eq!(px(13.5), 3.75);
+    // Different units are not interpolated.
+    assert_eq!(px(15.9), 4.0);
+    assert!(matches!(
+        function.evaluate(16.0),
+        CSSValue::Number(Number::World(w)) if w == 4.0
+    ));
+
+    let function = parse_function("interpolate(linear, zoom, 10: #000000, 12: white)");
+    match function.evaluate(11.0) {
+        CSSValue::Color(color) => assert_eq!(color.r, 0.5),
+        value => panic!("Unexpected value {:?}", value),
+    }
+
+    let function = parse_function("interpolate(exponential 2, zoom, 10: 0, 12: 3)");
+    assert!(matches!(
+        function.evaluate(11.0),
+        CSSValue::Number(Number::Unitless(u)) if u == 1.0
+    ));
+}
+
+#[test]
+fn evaluate_step() {
+    let function = parse_function("step(zoom, 1px, 10: 2px, 14: 4w)");
+    let value = |zoom| function.evaluate(zoom);
+    assert!(matches!(value(9.9), CSSValue::Number(Number::Px(px)) if px == 1.0));
+    assert!(matches!(value(10.0), CSSValue::Number(Number::Px(px)) if px == 2.0));
+    assert!(matches!(value(13.9), CSSValue::Number(Number::Px(px)) if px == 2.0));
+    assert!(matches!(value(14.5), CSSValue::Number(Number::World(w)) if w == 4.0));
+}
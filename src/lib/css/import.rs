@@ -0,0 +1,338 @@
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use super::*;
+
+/// The statements of a stylesheet file, to write the file back after editing its rules.
+#[derive(Debug, Clone)]
+pub(super) struct Sheet {
+    file: PathBuf,
+    items: Vec<SheetItem>,
+}
+
+#[derive(Debug, Clone)]
+enum SheetItem {
+    /// The path of an `@import` as it was written.
+    Import(String),
+    /// The indices of the rules of a selector list in the loaded rules.
+    Rules(Range<usize>),
+}
+
+/// The result of loading a stylesheet and all stylesheets it imports.
+pub(super) struct LoadedStyles {
+    /// The rules, or `None` if there were errors.
+    pub(super) rules: Option<Vec<Rule>>,
+    pub(super) diagnostics: Vec<StyleDiagnostic>,
+    /// All files which were read.
+    pub(super) files: Vec<PathBuf>,
+    /// The stylesheets in the order they were imported, starting with the loaded one.
+    pub(super) sheets: Vec<Sheet>,
+}
+
+/// Collects the rules and problems of a stylesheet and all stylesheets it imports.
+#[derive(Default)]
+pub(super) struct StyleLoader {
+    rules: Vec<Rule>,
+    pub(super) diagnostics: Vec<StyleDiagnostic>,
+    files: Vec<PathBuf>,
+    sheets: Vec<Sheet>,
+    /// The files which are currently being loaded, to detect import cycles.
+    loading: Vec<PathBuf>,
+    /// The canonical paths of all loaded files, so a file imported more than once is loaded once.
+    loaded: BTreeSet<PathBuf>,
+    /// The names of all declared custom properties.
+    variables: BTreeSet<String>,
+    /// The custom properties used by `var()` and a warning in case they are never declared.
+    references: Vec<(String, StyleDiagnostic)>,
+}
+
+impl StyleLoader {
+    /// Reads and parses a stylesheet file.
+    pub(super) fn load_file(&mut self, file: &Path) -> std::io::Result<()> {
+        let style = std::fs::read_to_string(file)?;
+        self.files.push(file.to_path_buf());
+        self.loaded.insert(canonical(file));
+        self.parse(&style, file);
+        Ok(())
+    }
+
+    /// Parses a stylesheet and loads its imports relative to `file`.
+    ///
+    /// Imported rules are inserted in place of the import, so rules after it win on a tie.
+    pub(super) fn parse(&mut self, style: &str, file: &Path) {
+        let statements = match parse_statements(style, file) {
+            Ok(statements) => statements,
+            Err(diagnostic) => {
+                self.diagnostics.push(diagnostic);
+                return;
+            }
+        };
+
+        self.loading.push(canonical(file));
+        let sheet = self.sheets.len();
+        self.sheets.push(Sheet {
+            file: file.to_path_buf(),
+            items: vec![],
+        });
+        let mut items = vec![];
+        let mut diagnostics = vec![];
+        for statement in statements {
+            match statement {
+                Statement::Rule(rule) => {
+                    let first = self.rules.len();
+                    self.add_rule(rule, style, file, &mut diagnostics);
+                    items.push(SheetItem::Rules(first..self.rules.len()));
+                }
+                Statement::Import { at, path } => {
+                    items.push(SheetItem::Import(path.into()));
+                    let import = file.parent().unwrap_or_else(|| Path::new("")).join(path);
+                    let error =
+                        |message| StyleDiagnostic::new(file, style, at, Severity::Error, message);
+                    if self.loading.contains(&canonical(&import)) {
+                        diagnostics.push(error(format!("'{}' imports itself.", path)));
+                    } else if self.loaded.contains(&canonical(&import)) {
+                        // Its rules are merged already.
+                    } else if let Err(err) = self.load_file(&import) {
+                        diagnostics.push(error(format!("Cannot import '{}': {}", path, err)));
+                    }
+                }
+            }
+        }
+        self.loading.pop();
+        self.sheets[sheet].items = items;
+
+        diagnostics.sort_by_key(|d| (d.line, d.column));
+        self.diagnostics.extend(diagnostics);
+    }
+
+    /// Adds one rule per selector of a parsed rule and checks it for problems.
+    fn add_rule(
+        &mut self,
+        rule: ParsedRule,
+        style: &str,
+        file: &Path,
+        diagnostics: &mut Vec<StyleDiagnostic>,
+    ) {
+        let warning = |at: &str, message: String| {
+            StyleDiagnostic::new(file, style, at, Severity::Warning, message)
+        };
+
+        let mut kvs = BTreeMap::new();
+        let mut important = BTreeSet::new();
+        for declaration in rule.declarations {
+            if let Some((at, message)) = check_declaration(&declaration) {
+                diagnostics.push(warning(at, message));
+            }
+            if declaration.key.starts_with("--") {
+                self.variables.insert(declaration.key.to_string());
+            }
+            if let CSSValue::Var(name, None) = &declaration.value {
+                let message = format!("The custom property '{}' is never declared.", name);
+                self.references
+                    .push((name.clone(), warning(declaration.value_at, message)));
+            }
+
+            // A later declaration of the same key replaces the earlier one.
+            if declaration.important {
+                important.insert(declaration.key.to_string());
+            } else {
+                important.remove(declaration.key);
+            }
+            kvs.insert(declaration.key.to_string(), declaration.value);
+        }
+
+        for (at, selector) in rule.selectors {
+            if let Some(message) = check_selector(&selector) {
+                diagnostics.push(warning(at, message));
+            }
+            self.rules.push(Rule {
+                selector,
+                kvs: kvs.clone(),
+                important: important.clone(),
+            });
+        }
+    }
+
+    /// Checks the references to custom properties and returns the rules if no errors were found.
+    pub(super) fn finish(mut self) -> LoadedStyles {
+        // Custom properties can be declared in any stylesheet, so they are checked last.
+        for (name, diagnostic) in self.references {
+            if !self.variables.contains(&name) {
+                self.diagnostics.push(diagnostic);
+            }
+        }
+
+        let has_errors = self
+            .diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.severity == Severity::Error);
+        LoadedStyles {
+            rules: if has_errors { None } else { Some(self.rules) },
+            diagnostics: self.diagnostics,
+            files: self.files,
+            sheets: self.sheets,
+        }
+    }
+}
+
+/// Writes the stylesheets which contain one of the `edited` rules.
+///
+/// Untouched rules and imports keep their order.
+/// Rules which are in no stylesheet, because they were added after loading, are appended to the first one.
+/// Returns the contents of every file to write.
+pub(super) fn serialize_sheets(
+    sheets: &[Sheet],
+    rules: &[Rule],
+    edited: &BTreeSet<usize>,
+) -> Vec<(PathBuf, String)> {
+    let mut added = (0..rules.len()).collect::<BTreeSet<_>>();
+    for item in sheets.iter().flat_map(|sheet| &sheet.items) {
+        if let SheetItem::Rules(indices) = item {
+            for index in indices.clone() {
+                added.remove(&index);
+            }
+        }
+    }
+
+    let mut written: Vec<(PathBuf, String)> = vec![];
+    for (i, sheet) in sheets.iter().enumerate() {
+        let mut indices = sheet
+            .items
+            .iter()
+            .flat_map(|item| match item {
+                SheetItem::Rules(indices) => indices.clone(),
+                SheetItem::Import(_) => 0..0,
+            })
+            .collect::<BTreeSet<_>>();
+        if i == 0 {
+            indices.extend(&added);
+        }
+        if indices.is_disjoint(edited) {
+            continue;
+        }
+
+        let items = sheet.items.iter().map(|item| match item {
+            SheetItem::Import(path) => format!("@import \"{}\";\n", path),
+            SheetItem::Rules(indices) => write_selector_list(&rules[indices.clone()]),
+        });
+        let added = added
+            .iter()
+            .filter(|_| i == 0)
+            .map(|index| rules[*index].to_string());
+        let contents = items.chain(added).collect::<Vec<_>>().join("\n");
+        written.push((sheet.file.clone(), contents));
+    }
+    written
+}
+
+/// Writes the rules of a selector list like `a, b { }`.
+///
+/// The selectors stay together as long as their declarations were not edited apart.
+fn write_selector_list(rules: &[Rule]) -> String {
+    let bodies = rules.iter().map(Rule::body).collect::<Vec<_>>();
+    let mut written = vec![];
+    let mut start = 0;
+    while start < rules.len() {
+        let end = start
+            + bodies[start..]
+                .iter()
+                .take_while(|body| **body == bodies[start])
+                .count();
+        let selectors = rules[start..end]
+            .iter()
+            .map(|rule| rule.selector.to_css())
+            .collect::<Vec<_>>();
+        written.push(format!("{} {}", selectors.join(", "), bodies[start]));
+        start = end;
+    }
+    written.join("\n")
+}
+
+/// Returns the canonical path of a file to compare it to others, or the path itself if it does not exist.
+fn canonical(file: &Path) -> PathBuf {
+    file.canonicalize().unwrap_or_else(|_| file.to_path_buf())
+}
+
+#[test]
+fn load_imported_stylesheets() {
+    let dir = std::env::temp_dir().join(format!("sailor-import-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("layers")).unwrap();
+    let write = |name: &str, style: &str| std::fs::write(dir.join(name), style).unwrap();
+    write(
+        "style.css",
+        "@import \"palette.css\";\n@import url(layers/water.css);\nlayer { background-color: var(--land); }",
+    );
+    write("palette.css", ":root { --water: #0eb5df; }");
+    write(
+        "layers/water.css",
+        "@import '../palette.css';\nlayer[name=water] { background-color: var(--water); }",
+    );
+
+    let mut loader = StyleLoader::default();
+    loader.load_file(&dir.join("style.css")).unwrap();
+    let LoadedStyles {
+        rules,
+        diagnostics,
+        files,
+        sheets,
+    } = loader.finish();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    // Imported rules come first and a stylesheet which is imported twice is only loaded once.
+    let rules = rules.unwrap();
+    assert_eq!(rules.len(), 3);
+    assert_eq!(rules[1].selector.to_string(), "(layer[name=water])");
+    assert_eq!(files.len(), 3);
+    assert_eq!(sheets.len(), 3);
+
+    let locations = diagnostics
+        .iter()
+        .map(|d| (d.file.ends_with("style.css"), d.line, d.column, d.severity))
+        .collect::<Vec<_>>();
+    assert_eq!(locations, vec![(true, 3, 27, Severity::Warning)]);
+
+    // Only the stylesheet containing the edited rule is written, imports are kept.
+    let edited = [1].iter().copied().collect();
+    let written = serialize_sheets(&sheets, &rules, &edited);
+    assert_eq!(written.len(), 1);
+    assert!(written[0].0.ends_with("layers/water.css"));
+    assert_eq!(
+        written[0].1,
+        "@import \"../palette.css\";\n\nlayer[name=water] {\n    background-color: var(--water);\n}\n"
+    );
+
+    let mut loader = StyleLoader::default();
+    loader.parse("@import \"missing.css\";", &dir.join("style.css"));
+    let loaded = loader.finish();
+    assert!(loaded.rules.is_none());
+    let diagnostic = &loaded.diagnostics[0];
+    assert_eq!((diagnostic.line, diagnostic.column), (1, 9));
+}
+
+#[test]
+fn write_selector_lists() {
+    let mut loader = StyleLoader::default();
+    loader.parse(
+        "layer[name=water], layer[name=waterway] { line-width: 2px; }\nlayer { line-width: 1px; }",
+        Path::new("style.css"),
+    );
+    let LoadedStyles { rules, sheets, .. } = loader.finish();
+    let mut rules = rules.unwrap();
+    assert_eq!(rules.len(), 3);
+
+    // The selector list is written once, not once per selector.
+    let edited = [2].iter().copied().collect();
+    let written = serialize_sheets(&sheets, &rules, &edited);
+    assert_eq!(
+        written[0].1,
+        "layer[name=water], layer[name=waterway] {\n    line-width: 2px;\n}\n\nlayer {\n    line-width: 1px;\n}\n"
+    );
+
+    // A selector whose declarations were edited apart is written on its own.
+    let width = rules[2].kvs["line-width"].clone();
+    rules[1].kvs.insert("line-width".into(), width);
+    let written = serialize_sheets(&sheets, &rules, &edited);
+    assert!(written[0].1.starts_with(
+        "layer[name=water] {\n    line-width: 2px;\n}\n\nlayer[name=waterway] {\n    line-width: 1px;\n}\n"
+    ));
+}
@@ -0,0 +1,571 @@
+use serde_json::Value;
+
+use super::*;
+
+/// The rules converted from a Mapbox GL or MapLibre style.
+#[derive(Debug, Default)]
+pub struct MapboxImport {
+    pub rules: Vec<Rule>,
+    /// Everything in the style which could not be converted.
+    pub unsupported: Vec<Unsupported>,
+}
+
+/// A part of a Mapbox GL style which could not be converted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unsupported {
+    /// The id of the style layer.
+    pub layer: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.layer, self.message)
+    }
+}
+
+/// Alternative sets of attributes, of which one set has to match.
+type Conditions = Vec<Vec<Attribute>>;
+
+/// Converts the layers of a Mapbox GL or MapLibre style JSON into rules.
+///
+/// Only fails if `json` is not valid JSON.
+/// Everything which can not be converted is listed in `MapboxImport::unsupported`.
+pub fn import_mapbox_style(json: &str) -> Result<MapboxImport, serde_json::Error> {
+    let style: Value = serde_json::from_str(json)?;
+    let mut import = MapboxImport::default();
+
+    match style["layers"].as_array() {
+        Some(layers) => {
+            for layer in layers {
+                import.add_layer(layer);
+            }
+        }
+        None => import.report("", "The style has no layers."),
+    }
+
+    Ok(import)
+}
+
+impl MapboxImport {
+    fn report(&mut self, layer: &str, message: impl Into<String>) {
+        self.unsupported.push(Unsupported {
+            layer: layer.into(),
+            message: message.into(),
+        });
+    }
+
+    /// Converts a single style layer into one rule per alternative of its filter.
+    fn add_layer(&mut self, layer: &Value) {
+        let id = layer["id"].as_str().unwrap_or("<unnamed>");
+        let typ = layer["type"].as_str().unwrap_or_default();
+
+        let mut selector = match (typ, layer["source-layer"].as_str()) {
+            ("background", _) => Selector::new().with_type("background"),
+            ("fill", Some(source_layer)) | ("line", Some(source_layer)) => Selector::new()
+                .with_type("layer")
+                .with_any("name", source_layer),
+            ("fill", None) | ("line", None) => {
+                return self.report(id, "The layer has no source-layer.");
+            }
+            (typ, _) => {
+                return self.report(id, format!("Layers of type '{}' are not supported.", typ));
+            }
+        };
+
+        let zooms = [
+            ("minzoom", Operator::Ge, 0.0),
+            ("maxzoom", Operator::Lt, 24.0),
+        ];
+        for (key, operator, default) in zooms.iter() {
+            match layer[key].as_f64() {
+                Some(zoom) if zoom != *default => selector.add_attribute(Attribute {
+                    key: "zoom".into(),
+                    operator: *operator,
+                    value: zoom.to_string(),
+                    negated: false,
+                }),
+                _ => {}
+            }
+        }
+
+        let mut notes = vec![];
+        let conditions = match &layer["filter"] {
+            Value::Null => vec![vec![]],
+            value => match filter(value, &mut notes) {
+                Ok(conditions) => conditions,
+                Err(message) => {
+                    return self.report(id, format!("{} The layer is left out.", message));
+                }
+            },
+        };
+        for note in notes {
+            self.report(id, note);
+        }
+
+        let kvs = self.properties(id, typ, layer);
+        if kvs.is_empty() {
+            return;
+        }
+
+        for attributes in conditions {
+            let mut selector = selector.clone();
+            for attribute in attributes {
+                selector.add_attribute(attribute);
+            }
+            self.rules.push(Rule {
+                selector,
+                kvs: kvs.clone(),
+                important: BTreeSet::new(),
+            });
+        }
+    }
+
+    /// Converts the paint and layout properties of a style layer.
+    fn properties(&mut self, id: &str, typ: &str, layer: &Value) -> BTreeMap<String, CSSValue> {
+        let mut kvs = BTreeMap::new();
+        let mut opacity = None;
+
+        let paint = layer["paint"].as_object().into_iter().flatten();
+        for (name, value) in paint {
+            let converted = match (typ, &name[..]) {
+                ("background", "background-color")
+                | ("fill", "fill-color")
+                | ("line", "line-color") => {
+                    convert(value, color_value).map(|v| ("background-color", v))
+                }
+                ("fill", "fill-outline-color") => {
+                    kvs.insert("border-width".into(), CSSValue::Number(Number::Px(1.0)));
+                    convert(value, color_value).map(|v| ("border-color", v))
+                }
+                ("line", "line-width") => convert(value, px_number).map(|v| ("line-width", v)),
+                ("line", "line-dasharray") => {
+                    convert(value, dash_numbers).map(|v| ("line-dasharray", v))
+                }
+                ("background", "background-opacity")
+                | ("fill", "fill-opacity")
+                | ("line", "line-opacity") => {
+                    opacity = Some((name, value));
+                    continue;
+                }
+                _ => Err(format!("The property '{}' is not supported.", name)),
+            };
+            match converted {
+                Ok((key, value)) => {
+                    kvs.insert(key.into(), value);
+                }
+                Err(message) => self.report(id, message),
+            }
+        }
+
+        // The opacity is folded into the colors.
+        if let Some((name, value)) = opacity {
+            match value.as_f64() {
+                Some(opacity) => {
+                    for key in &["background-color", "border-color"] {
+                        if let Some(color) = kvs.get_mut(*key) {
+                            multiply_alpha(color, opacity as f32);
+                        }
+                    }
+                }
+                None => self.report(id, format!("Only a constant '{}' is supported.", name)),
+            }
+        }
+
+        let layout = layer["layout"].as_object().into_iter().flatten();
+        for (name, value) in layout {
+            match (&name[..], value.as_str()) {
+                ("visibility", Some("none")) => {
+                    kvs.insert("display".into(), CSSValue::String("none".into()));
+                }
+                ("visibility", _) => {}
+                ("line-cap", Some(cap)) if crate::LineCap::from_name(cap).is_some() => {
+                    kvs.insert("line-cap".into(), CSSValue::String(cap.into()));
+                }
+                ("line-join", Some(join)) if crate::LineJoin::from_name(join).is_some() => {
+                    kvs.insert("line-join".into(), CSSValue::String(join.into()));
+                }
+                _ => self.report(id, format!("The property '{}' is not supported.", name)),
+            }
+        }
+
+        kvs
+    }
+}
+
+/// Converts a filter into the alternative attribute sets matching it.
+///
+/// Conditions on the geometry type are always true, as objects are only selected by their tags.
+fn filter(value: &Value, notes: &mut Vec<String>) -> Result<Conditions, String> {
+    let unsupported = || format!("The filter {} is not supported.", value);
+    let parts = value.as_array().ok_or_else(unsupported)?;
+    let (op, args) = match parts.split_first() {
+        Some((Value::String(op), args)) => (&op[..], args),
+        _ => return Err(unsupported()),
+    };
+
+    let filters = |notes: &mut Vec<String>| {
+        args.iter()
+            .map(|arg| filter(arg, notes))
+            .collect::<Result<Vec<_>, _>>()
+    };
+    match op {
+        "all" => return Ok(all(filters(notes)?)),
+        "any" => return Ok(any(filters(notes)?)),
+        "none" => return Ok(negate(any(filters(notes)?))),
+        "!" if args.len() == 1 => return Ok(negate(filter(&args[0], notes)?)),
+        "match" => return match_filter(args).ok_or_else(unsupported),
+        _ => {}
+    }
+
+    let key = args.first().and_then(filter_key).ok_or_else(unsupported)?;
+    if key == "$type" {
+        notes.push(format!(
+            "The geometry type condition of {} is ignored.",
+            value
+        ));
+        return Ok(vec![vec![]]);
+    }
+
+    let attribute = |operator, value: &Value| Attribute {
+        key: key.into(),
+        operator,
+        value: value_string(value),
+        negated: false,
+    };
+    let single = |operator| match args {
+        [_, value] => Ok(vec![vec![attribute(operator, value)]]),
+        _ => Err(unsupported()),
+    };
+    match op {
+        "has" => Ok(vec![vec![attribute(Operator::Exists, &Value::Null)]]),
+        "!has" => Ok(negate(vec![vec![attribute(
+            Operator::Exists,
+            &Value::Null,
+        )]])),
+        "==" => single(Operator::Eq),
+        "!=" => single(Operator::Ne),
+        "<" => single(Operator::Lt),
+        "<=" => single(Operator::Le),
+        ">" => single(Operator::Gt),
+        ">=" => single(Operator::Ge),
+        "in" => Ok(args[1..]
+            .iter()
+            .map(|value| vec![attribute(Operator::Eq, value)])
+            .collect()),
+        "!in" => Ok(vec![args[1..]
+            .iter()
+            .map(|value| attribute(Operator::Ne, value))
+            .collect()]),
+        _ => Err(unsupported()),
+    }
+}
+
+/// Converts `["match", ["get", key], labels, true, ..., fallback]` with boolean outputs.
+fn match_filter(args: &[Value]) -> Option<Conditions> {
+    let key = filter_key(args.first()?)?;
+    let fallback = args.last()?.as_bool()?;
+    let branches = args.get(1..args.len() - 1)?;
+    if branches.len() % 2 != 0 {
+        return None;
+    }
+
+    let mut labels = vec![];
+    for branch in branches.chunks(2) {
+        if branch[1].as_bool()? != fallback {
+            match &branch[0] {
+                Value::Array(values) => labels.extend(values),
+                value => labels.push(value),
+            }
+        }
+    }
+    let matches = labels
+        .into_iter()
+        .map(|value| {
+            vec![Attribute {
+                key: key.into(),
+                operator: Operator::Eq,
+                value: value_string(value),
+                negated: false,
+            }]
+        })
+        .collect();
+    Some(if fallback { negate(matches) } else { matches })
+}
+
+/// Returns the tag a filter compares, either `"key"` or `["get", "key"]`.
+fn filter_key(value: &Value) -> Option<&str> {
+    match value {
+        Value::String(key) => Some(key),
+        Value::Array(parts) => match &parts[..] {
+            [op, Value::String(key)] if op == "get" => Some(key),
+            [op] if op == "geometry-type" => Some("$type"),
+            [op] if op == "zoom" => Some("zoom"),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn value_string(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        Value::Null => String::new(),
+        value => value.to_string(),
+    }
+}
+
+/// Requires all of the conditions to match.
+fn all(parts: Vec<Conditions>) -> Conditions {
+    parts.into_iter().fold(vec![vec![]], |conditions, part| {
+        conditions
+            .iter()
+            .flat_map(|attributes| {
+                part.iter()
+                    .map(move |other| attributes.iter().chain(other).cloned().collect())
+            })
+            .collect()
+    })
+}
+
+/// Requires any of the conditions to match.
+fn any(parts: Vec<Conditions>) -> Conditions {
+    parts.into_iter().flatten().collect()
+}
+
+/// Inverts conditions by De Morgan's laws.
+fn negate(conditions: Conditions) -> Conditions {
+    all(conditions
+        .into_iter()
+        .map(|attributes| {
+            attributes
+                .into_iter()
+                .map(|attribute| {
+                    vec![Attribute {
+                        negated: !attribute.negated,
+                        ..attribute
+                    }]
+                })
+                .collect()
+        })
+        .collect())
+}
+
+/// Converts a constant, a legacy `{ "stops": [] }` zoom function or a zoom expression.
+fn convert(value: &Value, constant: fn(&Value) -> Option<CSSValue>) -> Result<CSSValue, String> {
+    let unsupported = || format!("The value {} is not supported.", value);
+    if let Some(value) = constant(value) {
+        return Ok(value);
+    }
+
+    let stop = |zoom: &Value, value: &Value| Some((zoom.as_f64()? as f32, constant(value)?));
+    let function = match value {
+        Value::Object(function) if function.get("property").is_none() => {
+            let stops = function.get("stops").and_then(Value::as_array);
+            let stops = stops
+                .into_iter()
+                .flatten()
+                .map(|s| match s.as_array().map(|s| &s[..]) {
+                    Some([zoom, value]) => stop(zoom, value),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(unsupported)?;
+            let base = function.get("base").and_then(Value::as_f64).unwrap_or(1.0) as f32;
+            let interpolation = match function.get("type").and_then(Value::as_str) {
+                Some("interval") => Interpolation::Step,
+                _ if (base - 1.0).abs() < f32::EPSILON => Interpolation::Linear,
+                _ => Interpolation::Exponential(base),
+            };
+            ZoomFunction {
+                interpolation,
+                default: None,
+                stops,
+            }
+        }
+        Value::Array(parts) => {
+            let (op, args) = parts.split_first().ok_or_else(unsupported)?;
+            let (interpolation, default, stops) = match (op.as_str(), args) {
+                (Some("interpolate"), [Value::Array(interpolation), zoom, stops @ ..]) => {
+                    let interpolation = match &interpolation[..] {
+                        [kind] if kind == "linear" => Interpolation::Linear,
+                        [kind, base] if kind == "exponential" => {
+                            Interpolation::Exponential(base.as_f64().ok_or_else(unsupported)? as f32)
+                        }
+                        _ => return Err(unsupported()),
+                    };
+                    (interpolation, None, (zoom, stops))
+                }
+                (Some("step"), [zoom, default, stops @ ..]) => {
+                    let default = constant(default).ok_or_else(unsupported)?;
+                    (Interpolation::Step, Some(Box::new(default)), (zoom, stops))
+                }
+                _ => return Err(unsupported()),
+            };
+            let (zoom, stops) = stops;
+            if filter_key(zoom) != Some("zoom") || stops.len() % 2 != 0 {
+                return Err(unsupported());
+            }
+            let stops = stops
+                .chunks(2)
+                .map(|s| stop(&s[0], &s[1]))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(unsupported)?;
+            ZoomFunction {
+                interpolation,
+                default,
+                stops,
+            }
+        }
+        _ => return Err(unsupported()),
+    };
+
+    if function.stops.is_empty() {
+        return Err(unsupported());
+    }
+    Ok(CSSValue::Function(function))
+}
+
+/// Parses a color string like `#0eb5df` or `hsl(205, 56%, 73%)`.
+fn color_value(value: &Value) -> Option<CSSValue> {
+    match color::<VerboseError<&str>>(value.as_str()?.trim()) {
+        Ok((remaining, color)) if remaining.trim().is_empty() => Some(color),
+        _ => None,
+    }
+}
+
+fn px_number(value: &Value) -> Option<CSSValue> {
+    Some(CSSValue::Number(Number::Px(value.as_f64()? as f32)))
+}
+
+/// Converts dash lengths like `[4, 2]` or `["literal", [4, 2]]`.
+fn dash_numbers(value: &Value) -> Option<CSSValue> {
+    let numbers = match value.as_array()?.as_slice() {
+        [literal, Value::Array(numbers)] if literal == "literal" => numbers,
+        numbers => numbers,
+    };
+    if !matches!(numbers.len(), 1 | 2 | 4) {
+        return None;
+    }
+    let numbers = numbers
+        .iter()
+        .map(|number| Some(Number::Unitless(number.as_f64()? as f32)))
+        .collect::<Option<Vec<_>>>()?;
+    Some(CSSValue::Numbers(numbers))
+}
+
+/// Multiplies the alpha of a color or all colors of a zoom function.
+fn multiply_alpha(value: &mut CSSValue, opacity: f32) {
+    match value {
+        CSSValue::Color(color) => color.a *= opacity,
+        CSSValue::Function(function) => {
+            let defaults = function.default.iter_mut().map(|default| &mut **default);
+            let stops = function.stops.iter_mut().map(|(_, value)| value);
+            for value in defaults.chain(stops) {
+                multiply_alpha(value, opacity);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[test]
+fn import_mapbox_layers() {
+    let style = r##"{
+        "version": 8,
+        "layers": [
+            { "id": "background", "type": "background", "paint": { "background-color": "#f8f4f0" } },
+            {
+                "id": "water",
+                "type": "fill",
+                "source-layer": "water",
+                "filter": ["all", ["!=", "brunnel", "tunnel"], ["==", "$type", "Polygon"]],
+                "paint": { "fill-color": "hsl(205, 56%, 73%)", "fill-opacity": 0.5 }
+            },
+            {
+                "id": "road_major",
+                "type": "line",
+                "source-layer": "transportation",
+                "minzoom": 6,
+                "filter": ["in", "class", "primary", "trunk"],
+                "layout": { "line-cap": "round", "line-join": "round" },
+                "paint": {
+                    "line-color": "#fea",
+                    "line-dasharray": [4, 2],
+                    "line-width": { "base": 1.2, "stops": [[6.5, 0], [20, 18]] }
+                }
+            },
+            {
+                "id": "river",
+                "type": "line",
+                "source-layer": "waterway",
+                "filter": ["match", ["get", "class"], ["river", "canal"], true, false],
+                "paint": { "line-width": ["interpolate", ["linear"], ["zoom"], 8, 1, 14, 4] }
+            },
+            { "id": "labels", "type": "symbol", "source-layer": "place" },
+            { "id": "hills", "type": "fill", "source-layer": "hillshade", "filter": ["within", {}] }
+        ]
+    }"##;
+    let import = import_mapbox_style(style).unwrap();
+
+    let selectors = import
+        .rules
+        .iter()
+        .map(|rule| rule.selector.to_css())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        selectors,
+        vec![
+            "background",
+            "layer[brunnel!=tunnel][name=water]",
+            "layer.primary[name=transportation][zoom>=6]",
+            "layer.trunk[name=transportation][zoom>=6]",
+            "layer.river[name=waterway]",
+            "layer.canal[name=waterway]",
+        ]
+    );
+    assert_eq!(
+        import.rules[1].kvs["background-color"].to_string(),
+        "rgba(148, 193, 225, 0.5)"
+    );
+    assert_eq!(
+        import.rules[2].kvs["line-width"].to_string(),
+        "interpolate(exponential 1.2, zoom, 6.5: 0px, 20: 18px)"
+    );
+    assert_eq!(import.rules[2].kvs["line-dasharray"].to_string(), "4 2");
+    assert_eq!(import.rules[2].kvs["line-cap"].to_string(), "round");
+    assert_eq!(
+        import.rules[4].kvs["line-width"].to_string(),
+        "interpolate(linear, zoom, 8: 1px, 14: 4px)"
+    );
+
+    // The converted rules can be written as a stylesheet.
+    let css = import
+        .rules
+        .iter()
+        .map(|rule| rule.to_string())
+        .collect::<String>();
+    assert_eq!(try_parse_styles(&css).unwrap().len(), import.rules.len());
+
+    let layers = import
+        .unsupported
+        .iter()
+        .map(|u| &u.layer[..])
+        .collect::<Vec<_>>();
+    assert_eq!(layers, vec!["water", "labels", "hills"]);
+}
+
+#[test]
+fn negate_filters() {
+    let mut notes = vec![];
+    let conditions = filter(
+        &serde_json::json!(["none", ["==", "class", "river"], ["has", "intermittent"]]),
+        &mut notes,
+    )
+    .unwrap();
+    let attributes = conditions
+        .iter()
+        .map(|a| a.iter().map(|a| a.to_string()).collect::<String>())
+        .collect::<Vec<_>>();
+    assert_eq!(attributes, vec![":not(.river):not([intermittent])"]);
+}
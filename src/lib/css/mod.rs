@@ -0,0 +1,1598 @@
+mod color;
+mod diagnostic;
+mod function;
+mod import;
+mod mapbox;
+mod theme;
+
+pub use color::*;
+pub use diagnostic::*;
+pub use function::*;
+pub use mapbox::*;
+pub use theme::*;
+
+use import::*;
+
+use crossbeam_channel::{unbounded, TryRecvError};
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_while, take_while1},
+    character::complete::{multispace0, multispace1},
+    character::{complete::char, is_alphanumeric},
+    combinator::{cut, map, opt, recognize, value},
+    error::FromExternalError,
+    error::{ParseError, VerboseError, VerboseErrorKind},
+    multi::{many0, many1, separated_list1},
+    number::complete::float,
+    sequence::{delimited, preceded, terminated, tuple},
+    AsChar, Err, IResult, InputTakeAtPosition,
+};
+use notify::{event::ModifyKind, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    num::ParseIntError,
+    path::{Path, PathBuf},
+};
+
+/// Tries to parse an entire stylesheet.
+///
+/// All problems found in the stylesheet are logged.
+pub fn try_parse_styles(style: &str) -> Option<Vec<Rule>> {
+    let (rules, diagnostics) = parse_styles(style, Path::new("<style>"));
+    for diagnostic in diagnostics {
+        log::warn!("{}", diagnostic);
+    }
+    rules
+}
+
+/// Parses an entire stylesheet and checks it for problems.
+///
+/// Imports are resolved relative to `file`.
+/// Returns no rules if the stylesheet contains errors.
+pub fn parse_styles(style: &str, file: &Path) -> (Option<Vec<Rule>>, Vec<StyleDiagnostic>) {
+    let mut loader = StyleLoader::default();
+    loader.parse(style, file);
+    let loaded = loader.finish();
+    (loaded.rules, loaded.diagnostics)
+}
+
+/// Writes rules as a stylesheet which `try_parse_styles` parses back into the same rules.
+pub fn serialize_styles(rules: &[Rule]) -> String {
+    rules
+        .iter()
+        .map(|rule| rule.to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Parses the statements of a stylesheet or describes the syntax error that stopped parsing.
+fn parse_statements<'a>(
+    style: &'a str,
+    file: &Path,
+) -> Result<Vec<Statement<'a>>, StyleDiagnostic> {
+    let error = match statements::<VerboseError<&str>>(style) {
+        Ok((remaining, statements)) if remaining.trim().is_empty() => return Ok(statements),
+        // Parsing stops at the first broken statement, so parse it again to find the actual error.
+        Ok((remaining, _)) => statement::<VerboseError<&str>>(remaining)
+            .err()
+            .ok_or_else(|| {
+                StyleDiagnostic::new(file, style, remaining, Severity::Error, "Expected a rule.")
+            }),
+        Err(e) => Ok(e),
+    };
+
+    Err(match error {
+        Ok(Err::Error(e)) | Ok(Err::Failure(e)) => match e.errors.first() {
+            Some((at, kind)) => {
+                let message = match kind {
+                    VerboseErrorKind::Char(c) => format!("Expected '{}'.", c),
+                    VerboseErrorKind::Context(context) => format!("Expected {}.", context),
+                    VerboseErrorKind::Nom(kind) => format!("Invalid {}.", kind.description()),
+                };
+                StyleDiagnostic::new(file, style, at, Severity::Error, message)
+            }
+            None => StyleDiagnostic::for_file(file, Severity::Error, "Invalid stylesheet."),
+        },
+        Ok(Err::Incomplete(_)) => StyleDiagnostic::new(
+            file,
+            style,
+            &style[style.len()..],
+            Severity::Error,
+            "Unexpected end of the stylesheet.",
+        ),
+        Err(diagnostic) => diagnostic,
+    })
+}
+
+pub struct RulesCache {
+    pub rules: Vec<Rule>,
+    /// The stylesheet the rules were loaded from.
+    file: Option<PathBuf>,
+    /// The stylesheet and all stylesheets it imports, which are watched for changes.
+    files: Vec<PathBuf>,
+    /// The statements of every stylesheet, to save edited rules.
+    sheets: Vec<Sheet>,
+    /// The edits of rules which can be undone, the last one on top.
+    undo: Vec<StyleEdit>,
+    /// Whether the last edit is finished, so the next change starts a new edit even of the same property.
+    edit_finished: bool,
+    /// The indices of the rules edited since they were loaded or saved.
+    edited: BTreeSet<usize>,
+    /// The problems found when the stylesheet was loaded last.
+    diagnostics: Vec<StyleDiagnostic>,
+    /// The theme selected by `:theme()` in the stylesheet.
+    theme: Theme,
+    rx: crossbeam_channel::Receiver<std::result::Result<notify::event::Event, notify::Error>>,
+    watcher: Option<RecommendedWatcher>,
+}
+
+impl RulesCache {
+    /// Creates a new CSS rule cache from a given CSS file path.
+    ///
+    /// The file and all files it imports are watched for changes.
+    /// Problems with the stylesheet are reported by `diagnostics()`.
+    /// If the stylesheet can not be used, the cache starts out without rules.
+    pub fn load_from_file(filename: impl AsRef<Path>) -> Self {
+        let filename = filename.as_ref();
+        let (tx, rx) = unbounded();
+        let (watcher, watcher_error) = match Watcher::new_immediate(move |res| {
+            let _ = tx.send(res);
+        }) {
+            Ok(watcher) => (Some(watcher), None),
+            Err(err) => (None, Some(err)),
+        };
+        let mut cache = Self {
+            rules: vec![],
+            file: Some(filename.to_path_buf()),
+            files: vec![],
+            sheets: vec![],
+            undo: vec![],
+            edit_finished: false,
+            edited: BTreeSet::new(),
+            diagnostics: vec![],
+            theme: Theme::Day,
+            rx,
+            watcher,
+        };
+        cache.reload();
+
+        if let Some(err) = watcher_error {
+            cache.diagnostics.push(StyleDiagnostic::for_file(
+                filename,
+                Severity::Warning,
+                format!("Changes to the stylesheet are not reloaded: {}", err),
+            ));
+        }
+
+        cache
+    }
+
+    /// Creates a CSS rule cache from already parsed rules which is never updated.
+    pub fn from_rules(rules: Vec<Rule>) -> Self {
+        let (_, rx) = unbounded();
+        Self {
+            rules,
+            file: None,
+            files: vec![],
+            sheets: vec![],
+            undo: vec![],
+            edit_finished: false,
+            edited: BTreeSet::new(),
+            diagnostics: vec![],
+            theme: Theme::Day,
+            rx,
+            watcher: None,
+        }
+    }
+
+    /// Returns the theme which is currently used to compute styles.
+    pub fn theme(&self) -> Theme {
+        self.theme
+    }
+
+    /// Changes the theme which is used to compute styles.
+    ///
+    /// Rules with a `:theme()` only apply while their theme is selected.
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
+
+    /// Returns the problems found when the stylesheet was loaded last.
+    pub fn diagnostics(&self) -> &[StyleDiagnostic] {
+        &self.diagnostics
+    }
+
+    /// Reloads the rules from the stylesheet file and the files it imports.
+    ///
+    /// The current rules are kept if the stylesheet contains errors.
+    /// Edits which are not saved are lost, unless the reloaded rules are the same, e.g. after saving.
+    /// Returns the problems found in the stylesheet.
+    pub fn reload(&mut self) -> &[StyleDiagnostic] {
+        let file = match &self.file {
+            Some(file) => file.clone(),
+            None => return &self.diagnostics,
+        };
+
+        let mut loader = StyleLoader::default();
+        if let Err(err) = loader.load_file(&file) {
+            loader.diagnostics.push(StyleDiagnostic::for_file(
+                &file,
+                Severity::Error,
+                format!("Cannot read the stylesheet: {}", err),
+            ));
+        }
+        let loaded = loader.finish();
+
+        for diagnostic in &loaded.diagnostics {
+            log::warn!("{}", diagnostic);
+        }
+        if let Some(rules) = loaded.rules {
+            if serialize_styles(&rules) != serialize_styles(&self.rules) {
+                self.rules = rules;
+                self.undo.clear();
+                self.edited.clear();
+            }
+            self.sheets = loaded.sheets;
+        }
+        self.diagnostics = loaded.diagnostics;
+        self.watch(loaded.files);
+        &self.diagnostics
+    }
+
+    /// Sets or removes the property `key` of the rule at `index` so it can be undone.
+    ///
+    /// Consecutive changes of the same property, like dragging a slider, are undone at once
+    /// until [`RulesCache::finish_edit`] is called.
+    pub fn set_property(&mut self, index: usize, key: &str, value: Option<CSSValue>) {
+        let rule = match self.rules.get_mut(index) {
+            Some(rule) => rule,
+            None => return,
+        };
+        let previous = match value {
+            Some(value) => rule.kvs.insert(key.into(), value),
+            None => rule.kvs.remove(key),
+        };
+
+        let merge = !self.edit_finished
+            && matches!(self.undo.last(), Some(edit) if edit.rule == index && edit.key == key);
+        self.edit_finished = false;
+        if !merge {
+            self.undo.push(StyleEdit {
+                rule: index,
+                key: key.into(),
+                previous,
+            });
+        }
+        self.edited.insert(index);
+    }
+
+    /// Finishes the current edit, e.g. when a slider is released.
+    pub fn finish_edit(&mut self) {
+        self.edit_finished = true;
+    }
+
+    /// Reverts the last edit of a property.
+    ///
+    /// Returns `false` if there is nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        let edit = match self.undo.pop() {
+            Some(edit) => edit,
+            None => return false,
+        };
+        let rule = &mut self.rules[edit.rule];
+        match edit.previous {
+            Some(value) => rule.kvs.insert(edit.key, value),
+            None => rule.kvs.remove(&edit.key),
+        };
+        self.edited.insert(edit.rule);
+        true
+    }
+
+    /// Returns whether there are edits which can be undone.
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    /// Returns whether rules were edited since they were loaded or saved.
+    pub fn has_unsaved_edits(&self) -> bool {
+        !self.edited.is_empty()
+    }
+
+    /// Writes the edited rules back to the stylesheets they were loaded from.
+    ///
+    /// Only stylesheets with edited rules are written.
+    /// A failure is also reported in `diagnostics()`.
+    pub fn save(&mut self) -> std::io::Result<()> {
+        // Without a loaded stylesheet there is nothing to keep the rules in their order.
+        if self.sheets.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "No stylesheet was loaded, so the rules can not be saved.",
+            ));
+        }
+
+        for (path, contents) in serialize_sheets(&self.sheets, &self.rules, &self.edited) {
+            if let Err(err) = std::fs::write(&path, contents) {
+                self.diagnostics.push(StyleDiagnostic::for_file(
+                    &path,
+                    Severity::Error,
+                    format!("Cannot save the stylesheet: {}", err),
+                ));
+                return Err(err);
+            }
+            log::info!("Saved the stylesheet {:?}.", path);
+        }
+        self.edited.clear();
+        Ok(())
+    }
+
+    /// Watches exactly the given files for changes.
+    fn watch(&mut self, files: Vec<PathBuf>) {
+        let watcher = match &mut self.watcher {
+            Some(watcher) => watcher,
+            None => return,
+        };
+
+        for file in self.files.iter().filter(|file| !files.contains(file)) {
+            let _ = watcher.unwatch(file);
+        }
+        let mut watched = vec![];
+        for file in files {
+            if self.files.contains(&file) {
+                watched.push(file);
+                continue;
+            }
+            match watcher.watch(&file, RecursiveMode::NonRecursive) {
+                Ok(()) => watched.push(file),
+                Err(err) => self.diagnostics.push(StyleDiagnostic::for_file(
+                    &file,
+                    Severity::Warning,
+                    format!("Changes to the stylesheet are not reloaded: {}", err),
+                )),
+            }
+        }
+        self.files = watched;
+    }
+
+    /// Returns all Rules that match a given selector.
+    ///
+    /// E.g. `layer` does not match the `layer[zoom=5]` rule selector.
+    /// On the contrary, `layer[zoom=5]` matches the `layer` rule selector.
+    pub fn get_matching_rules(&self, selector: &Selector) -> Vec<&Rule> {
+        self.rules
+            .iter()
+            .filter(|rule| selector.matches(&rule.selector))
+            .collect()
+    }
+
+    /// Resolves the value of every property for a given selector.
+    ///
+    /// Like in CSS, `!important` values win over normal ones,
+    /// then the more specific selector wins and on a tie the rule defined last wins.
+    /// The current theme is added to the selector unless it already asks for one.
+    pub fn computed_style(&self, selector: &Selector) -> BTreeMap<String, ComputedValue<'_>> {
+        let themed;
+        let selector = if selector.any.iter().any(|a| a.key == "theme") {
+            selector
+        } else {
+            themed = selector.clone().with_any("theme", self.theme.name());
+            &themed
+        };
+
+        let mut style: BTreeMap<String, (_, ComputedValue)> = BTreeMap::new();
+        for (order, rule) in self.rules.iter().enumerate() {
+            if !selector.matches(&rule.selector) {
+                continue;
+            }
+
+            let specificity = rule.selector.specificity();
+            for (key, value) in &rule.kvs {
+                let important = rule.important.contains(key);
+                let priority = (important, specificity, order);
+                match style.get(key) {
+                    Some((winner, _)) if *winner > priority => {}
+                    _ => {
+                        style.insert(
+                            key.clone(),
+                            (
+                                priority,
+                                ComputedValue {
+                                    value,
+                                    rule,
+                                    important,
+                                },
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+
+        let style = style
+            .into_iter()
+            .map(|(key, (_, value))| (key, value))
+            .collect::<BTreeMap<_, _>>();
+
+        // Substitute `var()` with the computed values of the custom properties.
+        // Properties referring to an undefined custom property are left out.
+        style
+            .iter()
+            .filter_map(|(key, computed)| {
+                let value = resolve_variable(&style, computed.value)?;
+                Some((key.clone(), ComputedValue { value, ..*computed }))
+            })
+            .collect()
+    }
+
+    /// Returns the indices of all rules that match a given selector.
+    pub fn get_matching_rule_indices(&self, selector: &Selector) -> Vec<usize> {
+        self.rules
+            .iter()
+            .enumerate()
+            .filter(|(_, rule)| selector.matches(&rule.selector))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    pub fn get_matching_rules_mut(&mut self, selector: &Selector) -> Vec<&mut Rule> {
+        self.rules
+            .iter_mut()
+            .filter(|rule| selector.matches(&rule.selector))
+            .collect()
+    }
+
+    /// Returns the keys of all tags the rules select on.
+    ///
+    /// Objects need these tags in their selector for the rules to match.
+    /// `name`, `class`, `zoom` and `theme` are left out as the name of the layer, the classes, the zoom and the theme are always known.
+    pub fn selection_tags(&self) -> Vec<String> {
+        self.rules
+            .iter()
+            .flat_map(|rule| rule.selector.any.iter())
+            .map(|attribute| &attribute.key)
+            .filter(|key| !matches!(&key[..], "name" | "class" | "zoom" | "theme"))
+            .cloned()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    pub fn add_rule(&mut self, rule: Rule) {
+        self.rules.push(rule);
+    }
+
+    pub fn try_get_rule_mut(&mut self, selector: Selector) -> Option<&mut Rule> {
+        self.rules.iter_mut().find(|rule| selector == rule.selector)
+    }
+
+    /// Updates the CSS cache from the watched file if there was any changes.
+    ///
+    /// Returns whether a successful update happened.
+    /// Returns false if there was no changes or if the update failed.
+    pub fn update(&mut self) -> bool {
+        match self.rx.try_recv() {
+            Ok(Ok(notify::event::Event {
+                kind: EventKind::Modify(ModifyKind::Data(_)),
+                ..
+            })) => self
+                .reload()
+                .iter()
+                .all(|diagnostic| diagnostic.severity != Severity::Error),
+            // Everything is alright but file wasn't actually changed.
+            Ok(Ok(_)) => false,
+            Ok(Err(err)) => {
+                log::info!(
+                    "Something went wrong with the CSS file watcher:\r\n{:?}",
+                    err
+                );
+                false
+            }
+            // This happens all the time when there is no new message.
+            Err(TryRecvError::Empty) => false,
+            // Caches which are not loaded from a file have nothing to watch.
+            Err(TryRecvError::Disconnected) if self.watcher.is_none() => false,
+            Err(err) => {
+                log::info!(
+                    "Something went wrong with the CSS file watcher:\r\n{:?}",
+                    err
+                );
+                false
+            }
+        }
+    }
+}
+
+/// A change of a single property of a rule, which can be undone.
+#[derive(Debug, Clone)]
+struct StyleEdit {
+    rule: usize,
+    key: String,
+    /// The value before the change, `None` if the property was not set.
+    previous: Option<CSSValue>,
+}
+
+/// Follows `var()` references through the computed custom properties of `style`.
+///
+/// Returns `None` if a custom property is not defined or the references form a cycle.
+fn resolve_variable<'a>(
+    style: &BTreeMap<String, ComputedValue<'a>>,
+    mut value: &'a CSSValue,
+) -> Option<&'a CSSValue> {
+    // A chain of references longer than the number of properties has to be a cycle.
+    for _ in 0..=style.len() {
+        match value {
+            CSSValue::Var(name, fallback) => {
+                value = match style.get(name) {
+                    Some(computed) => computed.value,
+                    None => fallback.as_deref()?,
+                }
+            }
+            value => return Some(value),
+        }
+    }
+    None
+}
+
+/// A single CSS rule including it's selector.
+///
+/// A rule with a selector list like `a, b { }` is split into one rule per selector.
+#[derive(Debug)]
+pub struct Rule {
+    /// The selector that the rule is intended for.
+    pub selector: Selector,
+    /// The key/value pairs the rule holds.
+    pub kvs: BTreeMap<String, CSSValue>,
+    /// The keys of all values marked as `!important`.
+    pub important: BTreeSet<String>,
+}
+
+impl Rule {
+    /// Returns the declarations of the rule in braces as they are written in a stylesheet.
+    fn body(&self) -> String {
+        let mut body = String::from("{\n");
+        for (key, value) in &self.kvs {
+            let important = if self.important.contains(key) {
+                " !important"
+            } else {
+                ""
+            };
+            body += &format!("    {}: {}{};\n", key, value, important);
+        }
+        body + "}\n"
+    }
+}
+
+impl std::fmt::Display for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.selector.to_css(), self.body())
+    }
+}
+
+/// The value of a property after resolving the cascade.
+#[derive(Debug, Clone, Copy)]
+pub struct ComputedValue<'a> {
+    pub value: &'a CSSValue,
+    /// The rule the value was taken from.
+    pub rule: &'a Rule,
+    pub important: bool,
+}
+
+/// A single CSS selector.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, malloc_size_of_derive::MallocSizeOf)]
+pub struct Selector {
+    /// The type a selector matches.
+    /// E.g. `"layer"`.
+    pub typ: Option<String>,
+    /// The id a selector matches.
+    /// E.g. `"0"`.
+    pub id: Option<String>,
+    /// The classes a selector matches.
+    /// E.g. `["landmark", "forest"]`.
+    pub classes: Vec<String>,
+    /// The attributes a selector matches, sorted by key.
+    /// E.g. `[name=water]` or `[zoom>=10]`.
+    pub any: Vec<Attribute>,
+}
+
+impl Default for Selector {
+    fn default() -> Self {
+        Self {
+            typ: None,
+            id: None,
+            classes: vec![],
+            any: vec![],
+        }
+    }
+}
+
+impl std::fmt::Display for Selector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({})", self.to_css())
+    }
+}
+
+impl Selector {
+    /// Creates a new empty selector.
+    pub fn new() -> Self {
+        Self {
+            typ: None,
+            id: None,
+            classes: vec![],
+            any: vec![],
+        }
+    }
+
+    /// Returns the selector as it is written in a stylesheet.
+    /// E.g. `layer[name=water].river`.
+    pub fn to_css(&self) -> String {
+        let mut selector = self.typ.clone().unwrap_or_default();
+
+        if let Some(id) = self.id.as_ref() {
+            selector += "#";
+            selector += id;
+        }
+
+        for class in &self.classes {
+            selector += ".";
+            selector += class;
+        }
+        for attribute in &self.any {
+            selector += &attribute.to_string();
+        }
+        if selector.is_empty() {
+            // An empty selector matches everything.
+            selector += ":root";
+        }
+        selector
+    }
+
+    /// Makes the selector require the type `typ`.
+    pub fn with_type(mut self, typ: impl Into<String>) -> Self {
+        self.typ = Some(typ.into());
+        self
+    }
+
+    /// Makes the selector require the id `id`.
+    pub fn _with_id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Makes the selector require the class `class`.
+    pub fn _with_class(mut self, class: impl Into<String>) -> Self {
+        self.classes.push(class.into());
+        self
+    }
+
+    /// Makes the selector require the kv `key`/`value`.
+    ///
+    /// Replaces any previous requirement on `key`.
+    pub fn with_any(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        let key = key.into();
+        self.any.retain(|attribute| attribute.key != key);
+        self.add_attribute(Attribute {
+            key,
+            operator: Operator::Eq,
+            value: value.into(),
+            negated: false,
+        });
+        self
+    }
+
+    /// Adds an attribute requirement while keeping the attributes sorted by key.
+    fn add_attribute(&mut self, attribute: Attribute) {
+        let index = self
+            .any
+            .iter()
+            .position(|a| a.key > attribute.key)
+            .unwrap_or(self.any.len());
+        self.any.insert(index, attribute);
+    }
+
+    /// Checks if a subset of criteria of this selector matches all the criteria of another.
+    ///
+    /// Use example: layer.selector.matches(&landmark_selector)`.
+    pub fn matches(&self, other: &Selector) -> bool {
+        if let Some(t1) = &other.typ {
+            if let Some(t2) = &self.typ {
+                if t1 != t2 {
+                    return false;
+                }
+            } else {
+                return false;
+            }
+        }
+
+        if let Some(i1) = &other.id {
+            if let Some(i2) = &self.id {
+                if i1 != i2 {
+                    return false;
+                }
+            } else {
+                return false;
+            }
+        }
+
+        for attribute in &other.any {
+            // The `class` tag of an object is stored in its classes.
+            let values = if attribute.key == "class" {
+                self.classes.iter().map(|c| &c[..]).collect::<Vec<_>>()
+            } else {
+                self.any
+                    .iter()
+                    .filter(|a| a.key == attribute.key)
+                    .map(|a| &a.value[..])
+                    .collect()
+            };
+            if !attribute.matches(&values) {
+                return false;
+            }
+        }
+
+        for c in &other.classes {
+            if !self.classes.contains(c) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Returns the specificity of the selector as (ids, classes and attributes, types).
+    ///
+    /// A higher specificity wins when multiple rules set the same property.
+    pub fn specificity(&self) -> (usize, usize, usize) {
+        (
+            self.id.iter().count(),
+            self.classes.len() + self.any.len(),
+            self.typ.iter().count(),
+        )
+    }
+
+    pub fn size(&self) -> usize {
+        use parity_util_mem::MallocSizeOfExt;
+        std::mem::size_of::<Selector>() + self.malloc_size_of()
+    }
+}
+
+// impl wr_malloc_size_of::MallocSizeOf for Selector {
+//     /// Returns the memory size of the selector.
+//     fn size_of(&self, ops: &mut wr_malloc_size_of::MallocSizeOfOps) -> usize {
+//         let size_any =
+//             self.any.len() * (
+//               std::mem::size_of::<String>()
+//             + std::mem::size_of::<String>()
+//             + std::mem::size_of::<usize>()
+//             )
+//           + self.any.iter().map(|(k, v)| k.len() + v.len()).sum();
+
+//         let size_classes =
+//             self.classes.len() * std::mem::size_of::<String>()
+//           + self.classes.iter().map(|v| v.len()).sum();
+
+//         let base_len =
+//             2 * std::mem::size_of::<Option<String>>()
+//           + self.typ.map_or(0, |v| v.len())
+//           + self.id.map_or(0, |v| v.len());
+
+//         size_any + size_classes + base_len
+//     }
+// }
+
+/// The comparison of an attribute selector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, malloc_size_of_derive::MallocSizeOf)]
+pub enum Operator {
+    /// `[key]`
+    Exists,
+    /// `[key=value]`
+    Eq,
+    /// `[key!=value]`, also matches if the key is not set.
+    Ne,
+    /// `[key^=prefix]`
+    Prefix,
+    /// `[key*=substring]`
+    Contains,
+    /// `[key<value]`
+    Lt,
+    /// `[key<=value]`
+    Le,
+    /// `[key>value]`
+    Gt,
+    /// `[key>=value]`
+    Ge,
+}
+
+impl std::fmt::Display for Operator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let operator = match self {
+            Operator::Exists => "",
+            Operator::Eq => "=",
+            Operator::Ne => "!=",
+            Operator::Prefix => "^=",
+            Operator::Contains => "*=",
+            Operator::Lt => "<",
+            Operator::Le => "<=",
+            Operator::Gt => ">",
+            Operator::Ge => ">=",
+        };
+        write!(f, "{}", operator)
+    }
+}
+
+/// A single attribute requirement of a selector.
+/// E.g. `[zoom>=10]` or `:not([brunnel=bridge])`.
+///
+/// The key `class` refers to the classes of an object, so `:not(.bridge)` is stored as `:not([class=bridge])`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, malloc_size_of_derive::MallocSizeOf)]
+pub struct Attribute {
+    pub key: String,
+    pub operator: Operator,
+    pub value: String,
+    /// Inverts the requirement.
+    pub negated: bool,
+}
+
+impl Attribute {
+    /// Checks if the values an object has for the key fulfill this requirement.
+    ///
+    /// `values` is empty if the object does not have the key.
+    pub fn matches(&self, values: &[&str]) -> bool {
+        let matches = match self.operator {
+            Operator::Exists => !values.is_empty(),
+            Operator::Ne => values.iter().all(|value| *value != self.value),
+            _ => values.iter().any(|value| self.matches_value(value)),
+        };
+        matches != self.negated
+    }
+
+    /// Compares a single value of an object.
+    ///
+    /// Ordering comparisons are numeric and never match non-numeric values.
+    fn matches_value(&self, value: &str) -> bool {
+        let numbers = (value.parse::<f32>(), self.value.parse::<f32>());
+        match (self.operator, numbers) {
+            // The zoom is fractional, so `[zoom=13]` matches all zooms from 13 up to 14.
+            (Operator::Eq, (Ok(value), Ok(expected))) if self.key == "zoom" => {
+                value.floor() == expected
+            }
+            (Operator::Eq, _) => value == self.value,
+            (Operator::Prefix, _) => value.starts_with(&self.value),
+            (Operator::Contains, _) => value.contains(&self.value),
+            (Operator::Lt, (Ok(value), Ok(expected))) => value < expected,
+            (Operator::Le, (Ok(value), Ok(expected))) => value <= expected,
+            (Operator::Gt, (Ok(value), Ok(expected))) => value > expected,
+            (Operator::Ge, (Ok(value), Ok(expected))) => value >= expected,
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Display for Attribute {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let simple_value = self
+            .value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_');
+        let attribute = match self.operator {
+            Operator::Eq if self.key == "class" => format!(".{}", self.value),
+            Operator::Eq if self.key == "theme" => format!(":theme({})", self.value),
+            Operator::Exists => format!("[{}]", self.key),
+            operator if simple_value => format!("[{}{}{}]", self.key, operator, self.value),
+            operator => format!("[{}{}\"{}\"]", self.key, operator, self.value),
+        };
+        if self.negated {
+            write!(f, ":not({})", attribute)
+        } else {
+            write!(f, "{}", attribute)
+        }
+    }
+}
+
+/// A single part of a selector.
+/// Used for parsing only.
+#[derive(Debug)]
+enum SelectorPart {
+    Class(String),
+    Id(String),
+    Any(Attribute),
+}
+
+/// A top level statement of a stylesheet as it was parsed.
+enum Statement<'a> {
+    /// `@import "path";` with the stylesheet starting at the path.
+    Import {
+        at: &'a str,
+        path: &'a str,
+    },
+    Rule(ParsedRule<'a>),
+}
+
+/// A rule as it was parsed, which still refers to the stylesheet to locate problems.
+struct ParsedRule<'a> {
+    selectors: Vec<(&'a str, Selector)>,
+    declarations: Vec<Declaration<'a>>,
+}
+
+/// A single k/v pair as it was parsed.
+struct Declaration<'a> {
+    key: &'a str,
+    /// The stylesheet starting at the value.
+    value_at: &'a str,
+    value: CSSValue,
+    important: bool,
+}
+
+/// Parses an entire set of imports and rules.
+fn statements<'a, E>(input: &'a str) -> IResult<&'a str, Vec<Statement<'a>>, E>
+where
+    E: ParseError<&'a str> + FromExternalError<&'a str, ParseIntError>,
+{
+    many0(statement)(input)
+}
+
+/// Parses a single import or rule.
+fn statement<'a, E>(input: &'a str) -> IResult<&'a str, Statement<'a>, E>
+where
+    E: ParseError<&'a str> + FromExternalError<&'a str, ParseIntError>,
+{
+    alt((import, map(rule, Statement::Rule)))(input)
+}
+
+/// Parses an import of another stylesheet.
+/// E.g. `@import "palette.css";` or `@import url(palette.css);`.
+fn import<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, Statement<'a>, E> {
+    let (remaining, _) = whitespace(tag("@import"))(input)?;
+    let (remaining, (at, path)) = cut(terminated(located(import_path), char(';')))(remaining)?;
+    Ok((remaining, Statement::Import { at, path }))
+}
+
+/// Parses the quoted or `url()` path of an import.
+fn import_path<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, &'a str, E> {
+    alt((
+        quoted,
+        delimited(tag("url("), whitespace(url_path), char(')')),
+    ))(input)
+}
+
+/// Parses the optionally quoted path of `url()`.
+fn url_path<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, &'a str, E> {
+    alt((
+        quoted,
+        take_while1(|c: char| c != ')' && !c.is_whitespace()),
+    ))(input)
+}
+
+/// Parses a string in double or single quotes.
+fn quoted<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, &'a str, E> {
+    alt((
+        delimited(char('"'), take_while(|c| c != '"'), char('"')),
+        delimited(char('\''), take_while(|c| c != '\''), char('\'')),
+    ))(input)
+}
+
+/// Munch all whitespace before and after `f`.
+fn whitespace<I, O, E, F>(f: F) -> impl FnMut(I) -> IResult<I, O, E>
+where
+    I: Clone + PartialEq + InputTakeAtPosition,
+    <I as InputTakeAtPosition>::Item: AsChar + Clone,
+    F: Fn(I) -> IResult<I, O, E>,
+    E: ParseError<I>,
+{
+    delimited(multispace0, f, multispace0)
+}
+
+/// Munch all whitespace before and after `f` and also return the input `f` started at.
+fn located<'a, O, E, F>(f: F) -> impl FnMut(&'a str) -> IResult<&'a str, (&'a str, O), E>
+where
+    F: Fn(&'a str) -> IResult<&'a str, O, E>,
+    E: ParseError<&'a str>,
+{
+    move |input| {
+        let (input, _) = multispace0(input)?;
+        let (remaining, output) = f(input)?;
+        let (remaining, _) = multispace0(remaining)?;
+        Ok((remaining, (input, output)))
+    }
+}
+
+/// Parse a single rule with its selector list.
+/// E.g. `layer[name=water], layer[name=waterway] { background-color: #FF0000; }`.
+fn rule<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, ParsedRule<'a>, E>
+where
+    E: ParseError<&'a str> + ParseError<&'a str> + FromExternalError<&'a str, ParseIntError>,
+{
+    let (remaining, (selectors, _, declarations, _)) = tuple((
+        separated_list1(char(','), located(selector)),
+        whitespace(char('{')),
+        body,
+        whitespace(char('}')),
+    ))(input)?;
+
+    Ok((
+        remaining,
+        ParsedRule {
+            selectors,
+            declarations,
+        },
+    ))
+}
+
+/// Parse a single selector.
+/// E.g. `layer[name=water].class#id`.
+fn selector<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, Selector, E> {
+    let mut selector: Selector = Default::default();
+
+    // `:root` matches everything, like a selector without any parts.
+    let (input, _) = opt(tag(":root"))(input)?;
+
+    // Try parsing the type (Html tag) of a selector.
+    let (remaining, typ) = take_while(|c| is_alphanumeric(c as u8))(input)?;
+
+    // The type is optional. So if no type was found, set the type to `None`.
+    selector.typ = if !typ.is_empty() {
+        Some(typ.into())
+    } else {
+        None
+    };
+
+    // Parse all the remaining selector parts.
+    let (remaining, pairs) = many0(alt((class, id, any, theme, not)))(remaining)?;
+
+    for pair in pairs {
+        match pair {
+            SelectorPart::Class(v) => selector.classes.push(v),
+            SelectorPart::Id(v) => selector.id = Some(v),
+            SelectorPart::Any(attribute) => selector.add_attribute(attribute),
+        }
+    }
+
+    Ok((remaining, selector))
+}
+
+/// Parse a single class name.
+/// E.g. `.class`.
+fn class<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, SelectorPart, E> {
+    preceded(char('.'), take_while(|c| is_alphanumeric(c as u8)))(input)
+        .map(|(r, v)| (r, SelectorPart::Class(v.into())))
+}
+
+/// Parse a single id name.
+/// E.g. `#id`.
+fn id<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, SelectorPart, E> {
+    preceded(char('#'), take_while(|c| is_alphanumeric(c as u8)))(input)
+        .map(|(r, v)| (r, SelectorPart::Id(v.into())))
+}
+
+/// Parse any CSS selector k/v pair.
+/// E.g. `[name=water]`, `[zoom>=10]`, `[name:en^="Lake "]` or `[bridge]`.
+fn any<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, SelectorPart, E> {
+    let (remaining, _) = char('[')(input)?;
+    let (remaining, name) =
+        take_while1(|c: char| is_alphanumeric(c as u8) || c == '_' || c == ':')(remaining)?;
+    let (remaining, comparison) = opt(tuple((
+        operator,
+        alt((
+            delimited(char('"'), take_while(|c| c != '"'), char('"')),
+            take_while(|c: char| is_alphanumeric(c as u8) || c == '.' || c == '-' || c == '_'),
+        )),
+    )))(remaining)?;
+    let (remaining, _) = char(']')(remaining)?;
+    let (operator, value) = comparison.unwrap_or((Operator::Exists, ""));
+    Ok((
+        remaining,
+        SelectorPart::Any(Attribute {
+            key: name.into(),
+            operator,
+            value: value.into(),
+            negated: false,
+        }),
+    ))
+}
+
+/// Parse a theme the selector only applies to.
+/// E.g. `:theme(night)`.
+fn theme<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, SelectorPart, E> {
+    delimited(
+        tag(":theme("),
+        take_while1(|c| is_alphanumeric(c as u8)),
+        char(')'),
+    )(input)
+    .map(|(r, v)| {
+        (
+            r,
+            SelectorPart::Any(Attribute {
+                key: "theme".into(),
+                operator: Operator::Eq,
+                value: v.into(),
+                negated: false,
+            }),
+        )
+    })
+}
+
+/// Parse a negated class, k/v pair or theme.
+/// E.g. `:not(.bridge)`, `:not([brunnel=bridge])` or `:not(:theme(day))`.
+fn not<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, SelectorPart, E> {
+    let (remaining, part) = delimited(tag(":not("), alt((class, any, theme)), char(')'))(input)?;
+    let attribute = match part {
+        SelectorPart::Class(class) => Attribute {
+            key: "class".into(),
+            operator: Operator::Eq,
+            value: class,
+            negated: true,
+        },
+        SelectorPart::Any(attribute) => Attribute {
+            negated: true,
+            ..attribute
+        },
+        SelectorPart::Id(_) => unreachable!(),
+    };
+    Ok((remaining, SelectorPart::Any(attribute)))
+}
+
+/// Parse the comparison operator of an attribute selector.
+/// E.g. `>=`.
+fn operator<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, Operator, E> {
+    alt((
+        value(Operator::Le, tag("<=")),
+        value(Operator::Ge, tag(">=")),
+        value(Operator::Ne, tag("!=")),
+        value(Operator::Prefix, tag("^=")),
+        value(Operator::Contains, tag("*=")),
+        value(Operator::Lt, char('<')),
+        value(Operator::Gt, char('>')),
+        value(Operator::Eq, char('=')),
+    ))(input)
+}
+
+/// Parses the body of a CSS rule.
+/// E.g. `{}`.
+fn body<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, Vec<Declaration<'a>>, E>
+where
+    E: ParseError<&'a str> + ParseError<&'a str> + FromExternalError<&'a str, ParseIntError>,
+{
+    many0(kv)(input)
+}
+
+/// Parses a single CSS k/v pair and whether it is marked as `!important`.
+/// E.g. `background-color: #FF0000 !important;`.
+///
+/// Once the key is parsed the rest has to follow, so errors point at the broken value.
+fn kv<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, Declaration<'a>, E>
+where
+    E: ParseError<&'a str> + ParseError<&'a str> + FromExternalError<&'a str, ParseIntError>,
+{
+    let (remaining, key) = css_name(input)?;
+    let (remaining, (_, (value_at, value), important, _)) = cut(tuple((
+        char(':'),
+        located(css_value),
+        opt(whitespace(tag("!important"))),
+        char(';'),
+    )))(remaining)?;
+    Ok((
+        remaining,
+        Declaration {
+            key,
+            value_at,
+            value,
+            important: important.is_some(),
+        },
+    ))
+}
+
+/// Parses a CSS qualified name.
+/// Can contain alphanumeric characters and '-'.
+fn css_name<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, &'a str, E> {
+    whitespace(take_while1(|c| is_alphanumeric(c as u8) || c == '-'))(input)
+}
+
+/// Parses a single CSS qualified value.
+fn css_value<'a, E>(input: &'a str) -> IResult<&'a str, CSSValue, E>
+where
+    E: ParseError<&'a str> + ParseError<&'a str> + FromExternalError<&'a str, ParseIntError>,
+{
+    alt((
+        whitespace(zoom_function),
+        whitespace(var_value),
+        whitespace(color),
+        whitespace(number_list),
+        whitespace(px_value),
+        whitespace(world_value),
+        whitespace(unitless_value),
+        whitespace(string),
+    ))(input)
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum Number {
+    Px(f32),
+    Unitless(f32),
+    World(f32),
+}
+
+impl std::fmt::Display for Number {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Number::Px(px) => write!(f, "{}px", px),
+            Number::World(world) => write!(f, "{}w", world),
+            Number::Unitless(unitless) => write!(f, "{}", unitless),
+        }
+    }
+}
+
+/// Any type of CSS value.
+#[derive(Debug, Clone)]
+pub enum CSSValue {
+    /// Represents any value as a string.
+    String(String),
+    /// Represents a color.
+    Color(Color),
+    Number(Number),
+    /// Represents a list of numbers separated by spaces.
+    /// E.g. `4 2` for `line-dasharray`.
+    Numbers(Vec<Number>),
+    /// Represents a value which depends on the zoom level.
+    Function(ZoomFunction),
+    /// Represents a reference to a custom property and the value used if it is not defined.
+    /// E.g. `var(--water)` or `var(--water, blue)`.
+    Var(String, Option<Box<CSSValue>>),
+}
+
+impl std::fmt::Display for CSSValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CSSValue::String(value) => write!(f, "{}", value),
+            CSSValue::Color(color) => write!(f, "{}", color),
+            CSSValue::Number(number) => write!(f, "{}", number),
+            CSSValue::Numbers(numbers) => {
+                let numbers = numbers.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+                write!(f, "{}", numbers.join(" "))
+            }
+            CSSValue::Function(function) => write!(f, "{}", function),
+            CSSValue::Var(name, None) => write!(f, "var({})", name),
+            CSSValue::Var(name, Some(fallback)) => write!(f, "var({}, {})", name, fallback),
+        }
+    }
+}
+
+impl CSSValue {
+    /// Returns the value at the fractional zoom level `zoom`.
+    ///
+    /// Only zoom functions are evaluated, all other values are returned as they are.
+    pub fn at_zoom(&self, zoom: f32) -> CSSValue {
+        match self {
+            CSSValue::Function(function) => function.evaluate(zoom),
+            value => value.clone(),
+        }
+    }
+}
+
+/// Parses a single CSS qualified string.
+/// Can contain alphanumeric characters, '-' and spaces.
+fn string<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, CSSValue, E> {
+    let (input, value) = whitespace(take_while(|c| {
+        is_alphanumeric(c as u8) || c == '-' || c == ' '
+    }))(input)?;
+
+    Ok((input, CSSValue::String(value.trim_end().into())))
+}
+
+/// Parses a reference to a custom property.
+/// E.g. `var(--water)` or `var(--water, blue)`.
+fn var_value<'a, E>(input: &'a str) -> IResult<&'a str, CSSValue, E>
+where
+    E: ParseError<&'a str> + ParseError<&'a str> + FromExternalError<&'a str, ParseIntError>,
+{
+    let (input, (_, name, fallback, _)) = tuple((
+        tag("var("),
+        delimited(multispace0, custom_property, multispace0),
+        opt(preceded(char(','), css_value)),
+        char(')'),
+    ))(input)?;
+    Ok((input, CSSValue::Var(name.into(), fallback.map(Box::new))))
+}
+
+/// Parses the name of a custom property.
+/// E.g. `--water`.
+fn custom_property<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, &'a str, E> {
+    recognize(preceded(
+        tag("--"),
+        take_while1(|c| is_alphanumeric(c as u8) || c == '-'),
+    ))(input)
+}
+
+/// Parses at least two numbers separated by spaces.
+/// E.g. `4 2` or `2px 1px`.
+fn number_list<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, CSSValue, E> {
+    let (input, (first, rest)) = tuple((number, many1(preceded(multispace1, number))))(input)?;
+    let mut numbers = vec![first];
+    numbers.extend(rest);
+    Ok((input, CSSValue::Numbers(numbers)))
+}
+
+/// Parses a single number with or without unit.
+fn number<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, Number, E> {
+    let (input, (value, unit)) = tuple((float, opt(alt((tag("px"), tag("w"))))))(input)?;
+    let number = match unit {
+        Some("px") => Number::Px(value),
+        Some(_) => Number::World(value),
+        None => Number::Unitless(value),
+    };
+    Ok((input, number))
+}
+
+/// Parses a single CSS px value.
+fn px_value<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, CSSValue, E> {
+    let (input, (value, _)) = tuple((float, tag("px")))(input)?;
+
+    Ok((input, CSSValue::Number(Number::Px(value))))
+}
+
+fn world_value<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, CSSValue, E> {
+    let (input, (value, _)) = tuple((float, tag("w")))(input)?;
+    Ok((input, CSSValue::Number(Number::World(value))))
+}
+
+/// Parses a single CSS unitless value.
+fn unitless_value<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, CSSValue, E> {
+    let (input, value) = float(input)?;
+
+    Ok((input, CSSValue::Number(Number::Unitless(value))))
+}
+
+#[test]
+fn selector_size() {
+    let selector = super::Selector::default();
+    assert_eq!(selector.size(), 96);
+}
+
+#[test]
+fn match_zoom_ranges() {
+    let rules = try_parse_styles(
+        "layer[name=water][zoom>=10][zoom<14] { line-width: 1px; } layer[zoom=13] { line-width: 2px; }",
+    )
+    .unwrap();
+    let water = |zoom: f32| {
+        Selector::new()
+            .with_type("layer")
+            .with_any("name", "water")
+            .with_any("zoom", zoom.to_string())
+    };
+
+    assert!(!water(9.9).matches(&rules[0].selector));
+    assert!(water(10.0).matches(&rules[0].selector));
+    assert!(water(13.9).matches(&rules[0].selector));
+    assert!(!water(14.0).matches(&rules[0].selector));
+    assert!(water(13.5).matches(&rules[1].selector));
+    assert!(!water(14.0).matches(&rules[1].selector));
+    assert_eq!(
+        rules[0].selector.to_string(),
+        "(layer[name=water][zoom>=10][zoom<14])"
+    );
+}
+
+#[test]
+fn cascade_by_specificity_and_order() {
+    let rules = try_parse_styles(
+        "layer[name=water] { background-color: red; border-width: 1px; }
+         layer { background-color: blue; border-width: 2px !important; }
+         layer.river, background { line-width: 1px; }
+         layer.river { line-width: 3px; }",
+    )
+    .unwrap();
+    assert_eq!(rules.len(), 5);
+
+    let cache = RulesCache::from_rules(rules);
+    let water = Selector::new()
+        .with_type("layer")
+        .with_any("name", "water")
+        ._with_class("river");
+    let style = cache.computed_style(&water);
+
+    // The more specific rule wins although it comes first.
+    assert!(matches!(
+        style["background-color"].value,
+        CSSValue::Color(c) if *c == Color::RED
+    ));
+    // Important values win over more specific ones.
+    assert!(style["border-width"].important);
+    assert_eq!(
+        style["border-width"].rule.selector,
+        Selector::new().with_type("layer")
+    );
+    // On equal specificity the last rule wins.
+    assert!(matches!(
+        style["line-width"].value,
+        CSSValue::Number(Number::Px(px)) if *px == 3.0
+    ));
+}
+
+#[test]
+fn match_attribute_operators() {
+    let rules = try_parse_styles(
+        "layer[name=transportation].primary[brunnel=bridge] { line-width: 1px; }
+         layer[brunnel] { line-width: 1px; }
+         layer[brunnel!=bridge] { line-width: 1px; }
+         layer[name:en^=\"Lake \"] { line-width: 1px; }
+         layer[ref*=A1] { line-width: 1px; }
+         layer[rank<5] { line-width: 1px; }
+         layer:not(.primary):not([brunnel=tunnel]) { line-width: 1px; }",
+    )
+    .unwrap();
+    let matching = |selector: Selector| {
+        rules
+            .iter()
+            .enumerate()
+            .filter(|(_, rule)| selector.matches(&rule.selector))
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>()
+    };
+
+    let bridge = Selector::new()
+        .with_type("layer")
+        .with_any("name", "transportation")
+        ._with_class("primary")
+        .with_any("brunnel", "bridge")
+        .with_any("ref", "A1a")
+        .with_any("rank", "3");
+    assert_eq!(matching(bridge), vec![0, 1, 4, 5]);
+
+    let lake = Selector::new()
+        .with_type("layer")
+        .with_any("name", "water_name")
+        .with_any("name:en", "Lake Zurich")
+        .with_any("rank", "high");
+    assert_eq!(matching(lake), vec![2, 3, 6]);
+
+    assert_eq!(
+        rules[6].selector.to_string(),
+        "(layer:not([brunnel=tunnel]):not(.primary))"
+    );
+    assert_eq!(rules[3].selector.to_string(), "(layer[name:en^=\"Lake \"])");
+    assert_eq!(
+        RulesCache::from_rules(rules).selection_tags(),
+        vec!["brunnel", "name:en", "rank", "ref"]
+    );
+}
+
+#[test]
+fn resolve_custom_properties() {
+    let rules = try_parse_styles(
+        ":root { --water: #0eb5df; --land: var(--water); --loop: var(--loop); }
+         layer[name=water] { --water: red; }
+         layer {
+             background-color: var(--land);
+             border-color: var(--missing, blue);
+             border-width: var(--loop);
+         }",
+    )
+    .unwrap();
+
+    let cache = RulesCache::from_rules(rules);
+    let water = Selector::new().with_type("layer").with_any("name", "water");
+    let style = cache.computed_style(&water);
+
+    // Variables are resolved with the cascade of the selector that uses them.
+    assert!(matches!(
+        style["background-color"].value,
+        CSSValue::Color(c) if *c == Color::RED
+    ));
+    assert!(matches!(
+        style["border-color"].value,
+        CSSValue::Color(c) if *c == Color::BLUE
+    ));
+    assert!(!style.contains_key("border-width"));
+
+    let land = cache.computed_style(&Selector::new().with_type("layer"));
+    assert!(matches!(
+        land["background-color"].value,
+        CSSValue::Color(c) if *c == Color::from_rgba8(0x0e, 0xb5, 0xdf, 1.0)
+    ));
+}
+
+#[test]
+fn select_themes() {
+    let rules = try_parse_styles(
+        ":root { --water: #0eb5df; }
+         :root:theme(night) { --water: #04242d; }
+         layer[name=water] { background-color: var(--water); }
+         layer:not(:theme(day)) { border-color: red; }",
+    )
+    .unwrap();
+    assert_eq!(rules[1].selector.to_css(), ":theme(night)");
+    assert_eq!(rules[3].selector.to_css(), "layer:not(:theme(day))");
+
+    let mut cache = RulesCache::from_rules(rules);
+    let water = Selector::new().with_type("layer").with_any("name", "water");
+    let style = cache.computed_style(&water);
+    assert!(matches!(
+        style["background-color"].value,
+        CSSValue::Color(c) if *c == Color::from_rgba8(0x0e, 0xb5, 0xdf, 1.0)
+    ));
+    assert!(!style.contains_key("border-color"));
+
+    cache.set_theme(Theme::Night);
+    let style = cache.computed_style(&water);
+    assert!(matches!(
+        style["background-color"].value,
+        CSSValue::Color(c) if *c == Color::from_rgba8(0x04, 0x24, 0x2d, 1.0)
+    ));
+    assert!(style.contains_key("border-color"));
+
+    // An explicit theme in the selector wins over the current one.
+    let style = cache.computed_style(&water.with_any("theme", "dusk"));
+    assert!(matches!(
+        style["background-color"].value,
+        CSSValue::Color(c) if *c == Color::from_rgba8(0x0e, 0xb5, 0xdf, 1.0)
+    ));
+    assert_eq!(cache.selection_tags(), Vec::<String>::new());
+}
+
+#[test]
+fn serialize_round_trip() {
+    let style = try_parse_styles(
+        ":root { --water: #0eb5df; }
+         background { background-color: rgba(255, 255, 255, 0.5); }
+         layer[name=water]:not(.river), layer#3[name:en^=\"Lake \"][zoom>=10] {
+             background-color: var(--water, red) !important;
+             line-width: interpolate(exponential 1.2, zoom, 8: 1px, 14: 4.5w);
+             border-width: step(zoom, 0px, 12: 1px);
+             line-dasharray: 4 2;
+             display: none;
+             z-index: -2;
+         }",
+    )
+    .unwrap();
+
+    let serialized = serialize_styles(&style);
+    let parsed = try_parse_styles(&serialized).unwrap();
+    assert_eq!(parsed.len(), style.len());
+    assert_eq!(serialize_styles(&parsed), serialized);
+    assert_eq!(
+        style[3].to_string(),
+        "layer#3[name:en^=\"Lake \"][zoom>=10] {
+    background-color: var(--water, #ff0000) !important;
+    border-width: step(zoom, 0px, 12: 1px);
+    display: none;
+    line-dasharray: 4 2;
+    line-width: interpolate(exponential 1.2, zoom, 8: 1px, 14: 4.5w);
+    z-index: -2;
+}
+"
+    );
+}
+
+#[test]
+fn undo_edits() {
+    let rules = try_parse_styles("layer { line-width: 1px; }").unwrap();
+    let mut cache = RulesCache::from_rules(rules);
+    let px = |cache: &RulesCache| match cache.rules[0].kvs.get("line-width") {
+        Some(CSSValue::Number(Number::Px(px))) => Some(*px),
+        _ => None,
+    };
+
+    // Dragging a slider changes the same property many times, which is undone at once.
+    cache.set_property(0, "line-width", Some(CSSValue::Number(Number::Px(2.0))));
+    cache.set_property(0, "line-width", Some(CSSValue::Number(Number::Px(3.0))));
+    cache.set_property(0, "display", Some(CSSValue::String("none".into())));
+    cache.set_property(0, "line-width", None);
+    assert!(cache.has_unsaved_edits());
+
+    // Releasing the slider and dragging it again is a separate edit.
+    cache.finish_edit();
+    cache.set_property(0, "line-width", Some(CSSValue::Number(Number::Px(4.0))));
+    cache.finish_edit();
+    cache.set_property(0, "line-width", Some(CSSValue::Number(Number::Px(5.0))));
+    assert!(cache.undo());
+    assert_eq!(px(&cache), Some(4.0));
+    assert!(cache.undo());
+    assert_eq!(px(&cache), None);
+
+    assert!(cache.undo());
+    assert_eq!(px(&cache), Some(3.0));
+    assert!(cache.undo());
+    assert!(!cache.rules[0].kvs.contains_key("display"));
+    assert!(cache.undo());
+    assert_eq!(px(&cache), Some(1.0));
+    assert!(!cache.undo());
+}
@@ -0,0 +1,45 @@
+/// A variant of the chart colors, selected in the stylesheet with `:theme(night)`.
+///
+/// Like the ECDIS palettes, dusk and night keep the screen dark to preserve night vision.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Theme {
+    Day,
+    Dusk,
+    Night,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 3] = [Theme::Day, Theme::Dusk, Theme::Night];
+
+    /// The name used by `:theme()`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Theme::Day => "day",
+            Theme::Dusk => "dusk",
+            Theme::Night => "night",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|theme| theme.name() == name)
+    }
+
+    /// Returns the theme for the elevation of the sun in degrees.
+    ///
+    /// It is day until sunset, dusk during the civil twilight and night when the sun is lower.
+    pub fn for_sun_elevation(elevation: f32) -> Self {
+        if elevation > -0.833 {
+            Theme::Day
+        } else if elevation > -6.0 {
+            Theme::Dusk
+        } else {
+            Theme::Night
+        }
+    }
+}
+
+impl std::fmt::Display for Theme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
@@ -0,0 +1,700 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde_derive::Deserialize;
+
+use super::*;
+
+/// Limits on the contents of the `DiskCache`.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct CachePolicy {
+    /// Tiles fetched longer ago than this are revalidated with the server before they are used.
+    pub max_age: Option<Duration>,
+    /// The least recently used tiles are pruned once the cache grows beyond this many bytes.
+    ///
+    /// Prefetched tiles do not count towards the limit and are never pruned for it.
+    pub max_bytes: Option<u64>,
+    /// Stores the tiles gzip compressed on disk.
+    pub compress: bool,
+}
+
+/// The `[cache]` section of the config.
+#[derive(Debug, Copy, Clone, Deserialize)]
+pub struct CacheConfig {
+    pub max_age_days: u64,
+    /// The size limit in MiB.
+    pub max_size: u64,
+    pub compress: bool,
+}
+
+impl CacheConfig {
+    /// Converts the configured limits into a `CachePolicy`. Zero disables a limit.
+    pub fn policy(&self) -> CachePolicy {
+        CachePolicy {
+            max_age: Some(Duration::from_secs(self.max_age_days * 24 * 60 * 60))
+                .filter(|_| self.max_age_days > 0),
+            max_bytes: Some(self.max_size * 1024 * 1024).filter(|_| self.max_size > 0),
+            compress: self.compress,
+        }
+    }
+}
+
+/// The index entry of a tile in the `DiskCache`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiskCacheEntry {
+    pub tile_id: TileId,
+    /// The time the tile was last fetched or revalidated in seconds since the unix epoch.
+    pub fetched_at: u64,
+    /// The time the tile was last read in seconds since the unix epoch.
+    pub last_used: u64,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// The size of the tile file in bytes.
+    pub size: u64,
+}
+
+impl DiskCacheEntry {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            tile_id: TileId::new(row.get(0)?, row.get(1)?, row.get(2)?),
+            fetched_at: row.get::<_, i64>(3)? as u64,
+            last_used: row.get::<_, i64>(4)? as u64,
+            etag: row.get(5)?,
+            last_modified: row.get(6)?,
+            size: row.get::<_, i64>(7)? as u64,
+        })
+    }
+}
+
+/// The result of pruning the `DiskCache`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PruneReport {
+    pub removed_tiles: usize,
+    pub removed_bytes: u64,
+}
+
+/// The problems found when verifying the `DiskCache` against the files on disk.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// Tiles in the index whose file is gone.
+    pub missing: Vec<TileId>,
+    /// Tiles whose file size does not match the index.
+    pub size_mismatch: Vec<TileId>,
+    /// Tile files which are not in the index.
+    pub orphaned: Vec<TileId>,
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.missing.is_empty() && self.size_mismatch.is_empty() && self.orphaned.is_empty()
+    }
+}
+
+/// The tile cache below `{root}/cache` which keeps the downloaded tiles of a `HttpTileSource`.
+///
+/// Every tile is stored in its own file at `tile_cache_path`.
+/// An SQLite index at `{root}/cache/index.sqlite` records when, and with which ETag and
+/// Last-Modified header, each tile was fetched as well as when it was last used.
+/// Tiles which were prefetched for offline use are recorded in a separate table.
+pub struct DiskCache {
+    root: PathBuf,
+    connection: Mutex<Connection>,
+    policy: CachePolicy,
+}
+
+impl DiskCache {
+    /// Opens the disk cache below `root`, creating the index if it does not exist yet.
+    ///
+    /// Falls back to an in-memory index if the index can not be opened,
+    /// in which case tiles are still cached but not remembered across runs.
+    pub fn open(root: impl Into<PathBuf>) -> Self {
+        let root = root.into();
+        let path = root.join("cache/index.sqlite");
+        let connection = std::fs::create_dir_all(root.join("cache"))
+            .map_err(|e| e.to_string())
+            .and_then(|_| Self::open_index(&path).map_err(|e| e.to_string()))
+            .unwrap_or_else(|e| {
+                log::error!(
+                    "Unable to open the cache index {:?}. Using an in-memory index instead. Reason:\r\n{}",
+                    path,
+                    e
+                );
+                Self::open_index(":memory:").expect("Could not create an in-memory cache index.")
+            });
+
+        Self {
+            root,
+            connection: Mutex::new(connection),
+            policy: CachePolicy::default(),
+        }
+    }
+
+    fn open_index(path: impl AsRef<Path>) -> rusqlite::Result<Connection> {
+        let connection = Connection::open(path)?;
+        // The app and the sailor-cache tool may use the index at the same time.
+        connection.busy_timeout(Duration::from_secs(5))?;
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS tiles (
+                 zoom_level INTEGER, tile_column INTEGER, tile_row INTEGER,
+                 fetched_at INTEGER, last_used INTEGER, etag TEXT, last_modified TEXT, size INTEGER,
+                 PRIMARY KEY (zoom_level, tile_column, tile_row)
+             );
+             CREATE INDEX IF NOT EXISTS last_used_index ON tiles (last_used);
+             CREATE TABLE IF NOT EXISTS prefetched (
+                 zoom_level INTEGER, tile_column INTEGER, tile_row INTEGER,
+                 PRIMARY KEY (zoom_level, tile_column, tile_row)
+             );",
+        )?;
+        Ok(connection)
+    }
+
+    /// Sets the limits the cache enforces.
+    pub fn with_policy(mut self, policy: CachePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn policy(&self) -> &CachePolicy {
+        &self.policy
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Returns the index entry of a tile.
+    ///
+    /// Tile files written before the index existed are adopted into the index on first access.
+    pub fn entry(&self, tile_id: &TileId) -> Option<DiskCacheEntry> {
+        let entry = self.query_entry(tile_id);
+        if entry.is_none() && tile_cache_path(&self.root, tile_id).exists() {
+            self.adopt(tile_id);
+            return self.query_entry(tile_id);
+        }
+        entry
+    }
+
+    fn query_entry(&self, tile_id: &TileId) -> Option<DiskCacheEntry> {
+        let result = self
+            .connection
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT zoom_level, tile_column, tile_row, fetched_at, last_used, etag, last_modified, size
+                 FROM tiles WHERE zoom_level = ?1 AND tile_column = ?2 AND tile_row = ?3",
+                params![tile_id.z, tile_id.x, tile_id.y],
+                DiskCacheEntry::from_row,
+            )
+            .optional();
+        self.log_error(result, "read the cache index entry of", tile_id)
+            .flatten()
+    }
+
+    /// Returns all entries of the index.
+    pub fn entries(&self) -> Vec<DiskCacheEntry> {
+        self.query_entries("1")
+    }
+
+    /// Returns the entries of the index which match an SQL `condition` on the `tiles` table.
+    fn query_entries(&self, condition: &str) -> Vec<DiskCacheEntry> {
+        let connection = self.connection.lock().unwrap();
+        let result = connection
+            .prepare(&format!(
+                "SELECT zoom_level, tile_column, tile_row, fetched_at, last_used, etag, last_modified, size
+                 FROM tiles WHERE {} ORDER BY zoom_level, tile_column, tile_row",
+                condition
+            ))
+            .and_then(|mut statement| {
+                statement
+                    .query_map(params![], DiskCacheEntry::from_row)?
+                    .collect::<rusqlite::Result<Vec<_>>>()
+            });
+        match result {
+            Ok(entries) => entries,
+            Err(e) => {
+                log::error!("Unable to read the cache index. Reason:\r\n{}", e);
+                vec![]
+            }
+        }
+    }
+
+    /// Checks if a tile has to be revalidated with the server according to the policy.
+    pub fn is_expired(&self, entry: &DiskCacheEntry) -> bool {
+        self.is_expired_at(entry, unix_now())
+    }
+
+    fn is_expired_at(&self, entry: &DiskCacheEntry, now: u64) -> bool {
+        match self.policy.max_age {
+            Some(max_age) => now.saturating_sub(entry.fetched_at) > max_age.as_secs(),
+            None => false,
+        }
+    }
+
+    /// Reads a tile from the cache and marks it as used.
+    ///
+    /// The data is returned as stored, so it may be gzip compressed.
+    /// Returns `None` and drops the index entry if the tile file is gone.
+    pub fn read(&self, tile_id: &TileId) -> Option<Vec<u8>> {
+        let path = tile_cache_path(&self.root, tile_id);
+        if !path.exists() {
+            self.remove(tile_id);
+            return None;
+        }
+
+        let data = read_tile_file(&path)?;
+        let result = self.connection.lock().unwrap().execute(
+            "UPDATE tiles SET last_used = ?4 WHERE zoom_level = ?1 AND tile_column = ?2 AND tile_row = ?3",
+            params![tile_id.z, tile_id.x, tile_id.y, unix_now() as i64],
+        );
+        self.log_error(result, "mark as used", tile_id);
+        Some(data)
+    }
+
+    /// Writes a freshly fetched tile to the cache, compressing it if the policy asks for it.
+    ///
+    /// Prunes the least recently used tiles afterwards if the cache grew beyond its size limit.
+    pub fn store(
+        &self,
+        tile_id: &TileId,
+        data: &[u8],
+        etag: Option<&str>,
+        last_modified: Option<&str>,
+    ) -> bool {
+        let data = if self.policy.compress {
+            match compress_tile_data(data) {
+                Ok(compressed) => compressed,
+                Err(e) => {
+                    log::warn!("Could not compress {}. Reason:\r\n{}", tile_id, e);
+                    Cow::Borrowed(data)
+                }
+            }
+        } else {
+            Cow::Borrowed(data)
+        };
+
+        let path = tile_cache_path(&self.root, tile_id);
+        ensure_cache_structure(&self.root, tile_id);
+        if let Err(e) = std::fs::write(&path, &data) {
+            log::error!("Unable to write pbf {:?}. Reason:\r\n{}", path, e);
+            return false;
+        }
+
+        let now = unix_now() as i64;
+        let result = self.connection.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO tiles VALUES (?1, ?2, ?3, ?4, ?4, ?5, ?6, ?7)",
+            params![
+                tile_id.z,
+                tile_id.x,
+                tile_id.y,
+                now,
+                etag,
+                last_modified,
+                data.len() as i64
+            ],
+        );
+        if self
+            .log_error(result, "add to the cache index", tile_id)
+            .is_none()
+        {
+            return false;
+        }
+
+        if let Some(max_bytes) = self.policy.max_bytes {
+            if self.prunable_bytes() > max_bytes {
+                self.prune_lru(max_bytes);
+            }
+        }
+        true
+    }
+
+    /// Keeps a tile for offline use, so it is not pruned when the cache grows beyond its size limit.
+    pub fn mark_prefetched(&self, tile_id: &TileId) {
+        let result = self.connection.lock().unwrap().execute(
+            "INSERT OR IGNORE INTO prefetched VALUES (?1, ?2, ?3)",
+            params![tile_id.z, tile_id.x, tile_id.y],
+        );
+        self.log_error(result, "mark as prefetched", tile_id);
+    }
+
+    /// Marks a tile as fresh after the server confirmed it did not change.
+    pub fn refresh(&self, tile_id: &TileId) {
+        let result = self.connection.lock().unwrap().execute(
+            "UPDATE tiles SET fetched_at = ?4 WHERE zoom_level = ?1 AND tile_column = ?2 AND tile_row = ?3",
+            params![tile_id.z, tile_id.x, tile_id.y, unix_now() as i64],
+        );
+        self.log_error(result, "refresh", tile_id);
+    }
+
+    /// Removes a tile from the index and the disk.
+    pub fn remove(&self, tile_id: &TileId) {
+        let connection = self.connection.lock().unwrap();
+        let result = connection.execute(
+            "DELETE FROM tiles WHERE zoom_level = ?1 AND tile_column = ?2 AND tile_row = ?3",
+            params![tile_id.z, tile_id.x, tile_id.y],
+        );
+        self.log_error(result, "remove from the cache index", tile_id);
+        let result = connection.execute(
+            "DELETE FROM prefetched WHERE zoom_level = ?1 AND tile_column = ?2 AND tile_row = ?3",
+            params![tile_id.z, tile_id.x, tile_id.y],
+        );
+        self.log_error(result, "remove from the prefetched tiles", tile_id);
+        drop(connection);
+
+        let path = tile_cache_path(&self.root, tile_id);
+        if path.exists() {
+            if let Err(e) = std::fs::remove_file(&path) {
+                log::warn!("Unable to remove {:?}. Reason:\r\n{}", path, e);
+            }
+        }
+    }
+
+    /// Returns the total size of all tiles in the index in bytes.
+    pub fn total_bytes(&self) -> u64 {
+        self.query_bytes("1")
+    }
+
+    /// Returns the size of all tiles which were not prefetched in bytes, which is limited by the policy.
+    pub fn prunable_bytes(&self) -> u64 {
+        self.query_bytes(NOT_PREFETCHED)
+    }
+
+    /// Returns the size of the tiles which match an SQL `condition` on the `tiles` table.
+    fn query_bytes(&self, condition: &str) -> u64 {
+        let result = self.connection.lock().unwrap().query_row(
+            &format!(
+                "SELECT COALESCE(SUM(size), 0) FROM tiles WHERE {}",
+                condition
+            ),
+            params![],
+            |row| row.get::<_, i64>(0),
+        );
+        match result {
+            Ok(bytes) => bytes as u64,
+            Err(e) => {
+                log::error!("Unable to read the cache size. Reason:\r\n{}", e);
+                0
+            }
+        }
+    }
+
+    /// Removes all tiles older than the maximum age and then the least recently used tiles
+    /// until the cache fits its size limit.
+    ///
+    /// Expired tiles are only removed by an explicit prune,
+    /// during normal operation they are revalidated and kept for offline use.
+    pub fn prune(&self) -> PruneReport {
+        self.prune_at(unix_now())
+    }
+
+    fn prune_at(&self, now: u64) -> PruneReport {
+        let mut report = PruneReport::default();
+        for entry in self.entries() {
+            if self.is_expired_at(&entry, now) {
+                self.remove(&entry.tile_id);
+                report.removed_tiles += 1;
+                report.removed_bytes += entry.size;
+            }
+        }
+
+        if let Some(max_bytes) = self.policy.max_bytes {
+            let lru = self.prune_lru(max_bytes);
+            report.removed_tiles += lru.removed_tiles;
+            report.removed_bytes += lru.removed_bytes;
+        }
+        report
+    }
+
+    /// Removes the least recently used tiles until the tiles which were not prefetched take at most `max_bytes`.
+    fn prune_lru(&self, max_bytes: u64) -> PruneReport {
+        let mut report = PruneReport::default();
+        let mut total = self.prunable_bytes();
+
+        let mut entries = self.query_entries(NOT_PREFETCHED);
+        entries.sort_by_key(|entry| (entry.last_used, entry.fetched_at));
+        for entry in entries {
+            if total <= max_bytes {
+                break;
+            }
+            self.remove(&entry.tile_id);
+            total = total.saturating_sub(entry.size);
+            report.removed_tiles += 1;
+            report.removed_bytes += entry.size;
+            log::trace!("Pruned tile {} from the disk cache.", entry.tile_id);
+        }
+        report
+    }
+
+    /// Compares the index with the tile files on disk.
+    ///
+    /// If `repair` is set, entries of missing files are dropped, mismatching tiles are removed
+    /// and orphaned files are adopted into the index.
+    pub fn verify(&self, repair: bool) -> VerifyReport {
+        let mut report = VerifyReport::default();
+        let entries = self.entries();
+
+        for entry in &entries {
+            match std::fs::metadata(tile_cache_path(&self.root, &entry.tile_id)) {
+                Ok(metadata) if metadata.len() != entry.size => {
+                    report.size_mismatch.push(entry.tile_id)
+                }
+                Ok(_) => {}
+                Err(_) => report.missing.push(entry.tile_id),
+            }
+        }
+
+        let indexed = entries
+            .iter()
+            .map(|entry| entry.tile_id)
+            .collect::<HashSet<_>>();
+        report.orphaned = self
+            .tile_files()
+            .into_iter()
+            .filter(|tile_id| !indexed.contains(tile_id))
+            .collect();
+
+        if repair {
+            for tile_id in report.missing.iter().chain(&report.size_mismatch) {
+                self.remove(tile_id);
+            }
+            for tile_id in &report.orphaned {
+                self.adopt(tile_id);
+            }
+        }
+
+        report
+    }
+
+    /// Lists all tiles present as files in the cache directory.
+    fn tile_files(&self) -> Vec<TileId> {
+        let mut tiles = vec![];
+        let numbered = |path: &Path| -> Vec<(u32, PathBuf)> {
+            std::fs::read_dir(path)
+                .map(|entries| {
+                    entries
+                        .filter_map(|entry| entry.ok())
+                        .filter_map(|entry| {
+                            let path = entry.path();
+                            let number = path.file_stem()?.to_str()?.parse().ok()?;
+                            Some((number, path))
+                        })
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        for (z, z_path) in numbered(&self.root.join("cache")) {
+            for (x, x_path) in numbered(&z_path) {
+                for (y, y_path) in numbered(&x_path) {
+                    if y_path.extension() == Some("pbf".as_ref()) {
+                        tiles.push(TileId::new(z, x, y));
+                    }
+                }
+            }
+        }
+        tiles
+    }
+
+    /// Adds an existing tile file to the index, using its modification time as fetch time.
+    fn adopt(&self, tile_id: &TileId) {
+        let metadata = match std::fs::metadata(tile_cache_path(&self.root, tile_id)) {
+            Ok(metadata) => metadata,
+            Err(_) => return,
+        };
+        let fetched_at = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |duration| duration.as_secs()) as i64;
+
+        let result = self.connection.lock().unwrap().execute(
+            "INSERT OR IGNORE INTO tiles VALUES (?1, ?2, ?3, ?4, ?4, NULL, NULL, ?5)",
+            params![
+                tile_id.z,
+                tile_id.x,
+                tile_id.y,
+                fetched_at,
+                metadata.len() as i64
+            ],
+        );
+        self.log_error(result, "adopt into the cache index", tile_id);
+    }
+
+    fn log_error<T>(
+        &self,
+        result: rusqlite::Result<T>,
+        action: &str,
+        tile_id: &TileId,
+    ) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(e) => {
+                log::error!("Unable to {} {}. Reason:\r\n{}", action, tile_id, e);
+                None
+            }
+        }
+    }
+}
+
+/// The SQL condition on the `tiles` table which excludes prefetched tiles.
+const NOT_PREFETCHED: &str = "NOT EXISTS (SELECT 1 FROM prefetched
+     WHERE prefetched.zoom_level = tiles.zoom_level
+     AND prefetched.tile_column = tiles.tile_column
+     AND prefetched.tile_row = tiles.tile_row)";
+
+/// Returns the current time in seconds since the unix epoch.
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+#[cfg(test)]
+fn test_cache(name: &str) -> DiskCache {
+    let root = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    DiskCache::open(root)
+}
+
+#[test]
+fn store_read_and_prune_lru() {
+    let cache = test_cache("sailor-test-disk-cache-lru").with_policy(CachePolicy {
+        max_age: None,
+        max_bytes: Some(25),
+        ..Default::default()
+    });
+    let tiles = [
+        TileId::new(4, 1, 1),
+        TileId::new(4, 1, 2),
+        TileId::new(4, 2, 1),
+    ];
+
+    assert!(cache.store(&tiles[0], &[0; 10], Some("\"a\""), None));
+    assert!(cache.store(&tiles[1], &[1; 10], None, Some("yesterday")));
+    assert_eq!(cache.read(&tiles[0]), Some(vec![0; 10]));
+    assert_eq!(
+        cache.entry(&tiles[0]).unwrap().etag.as_deref(),
+        Some("\"a\"")
+    );
+
+    // Make the first tile the most recently used one.
+    cache
+        .connection
+        .lock()
+        .unwrap()
+        .execute(
+            "UPDATE tiles SET last_used = last_used + 10 WHERE tile_row = 1 AND tile_column = 1",
+            params![],
+        )
+        .unwrap();
+
+    // Storing a third tile exceeds the limit and prunes the least recently used tile.
+    assert!(cache.store(&tiles[2], &[2; 10], None, None));
+    assert_eq!(cache.total_bytes(), 20);
+    assert!(cache.entry(&tiles[0]).is_some());
+    assert!(cache.entry(&tiles[1]).is_none());
+    assert!(!tile_cache_path(cache.root(), &tiles[1]).exists());
+    assert!(cache.entry(&tiles[2]).is_some());
+    std::fs::remove_dir_all(cache.root()).unwrap();
+}
+
+#[test]
+fn keep_prefetched_tiles_when_pruning() {
+    let cache = test_cache("sailor-test-disk-cache-prefetched").with_policy(CachePolicy {
+        max_bytes: Some(15),
+        ..Default::default()
+    });
+    let prefetched = TileId::new(4, 1, 1);
+    assert!(cache.store(&prefetched, &[0; 10], None, None));
+    cache.mark_prefetched(&prefetched);
+
+    // The prefetched tile is the least recently used one, but only the other tiles are limited.
+    assert!(cache.store(&TileId::new(4, 1, 2), &[1; 10], None, None));
+    assert!(cache.store(&TileId::new(4, 2, 1), &[2; 10], None, None));
+    assert!(cache.entry(&prefetched).is_some());
+    assert!(cache.entry(&TileId::new(4, 1, 2)).is_none());
+    assert_eq!(cache.total_bytes(), 20);
+    assert_eq!(cache.prunable_bytes(), 10);
+    assert_eq!(cache.prune().removed_tiles, 0);
+
+    // Removing a tile forgets that it was prefetched, so storing it again prunes one of the tiles.
+    cache.remove(&prefetched);
+    assert!(cache.store(&prefetched, &[0; 10], None, None));
+    assert_eq!(cache.prunable_bytes(), 10);
+    assert_eq!(cache.total_bytes(), 10);
+    std::fs::remove_dir_all(cache.root()).unwrap();
+}
+
+#[test]
+fn prune_expired_tiles() {
+    let cache = test_cache("sailor-test-disk-cache-age").with_policy(CachePolicy {
+        max_age: Some(Duration::from_secs(60)),
+        ..Default::default()
+    });
+    let tile_id = TileId::new(4, 1, 1);
+    assert!(cache.store(&tile_id, &[0; 10], None, None));
+
+    let entry = cache.entry(&tile_id).unwrap();
+    assert!(!cache.is_expired(&entry));
+    assert_eq!(cache.prune().removed_tiles, 0);
+
+    let report = cache.prune_at(unix_now() + 120);
+    assert_eq!(
+        report,
+        PruneReport {
+            removed_tiles: 1,
+            removed_bytes: 10
+        }
+    );
+    assert!(cache.entry(&tile_id).is_none());
+    std::fs::remove_dir_all(cache.root()).unwrap();
+}
+
+#[test]
+fn verify_and_repair_index() {
+    let cache = test_cache("sailor-test-disk-cache-verify");
+    let tiles = [
+        TileId::new(4, 1, 1),
+        TileId::new(4, 1, 2),
+        TileId::new(4, 2, 1),
+    ];
+    for tile_id in &tiles {
+        assert!(cache.store(tile_id, &[0; 10], None, None));
+    }
+
+    std::fs::remove_file(tile_cache_path(cache.root(), &tiles[0])).unwrap();
+    std::fs::write(tile_cache_path(cache.root(), &tiles[1]), [0; 5]).unwrap();
+    let orphan = TileId::new(5, 3, 3);
+    ensure_cache_structure(cache.root(), &orphan);
+    std::fs::write(tile_cache_path(cache.root(), &orphan), [0; 7]).unwrap();
+
+    let report = cache.verify(true);
+    assert_eq!(report.missing, vec![tiles[0]]);
+    assert_eq!(report.size_mismatch, vec![tiles[1]]);
+    assert_eq!(report.orphaned, vec![orphan]);
+
+    assert!(cache.verify(false).is_ok());
+    assert_eq!(cache.entries().len(), 2);
+    assert_eq!(cache.total_bytes(), 17);
+    std::fs::remove_dir_all(cache.root()).unwrap();
+}
+
+#[test]
+fn store_compressed_tiles() {
+    let cache = test_cache("sailor-test-disk-cache-compress").with_policy(CachePolicy {
+        compress: true,
+        ..Default::default()
+    });
+    let tile_id = TileId::new(8, 142, 93);
+    let data = &include_bytes!("../../data/8_142_93.pbf")[..];
+    assert!(cache.store(&tile_id, data, None, None));
+
+    let stored = cache.read(&tile_id).unwrap();
+    assert_eq!(TileCompression::detect(&stored), TileCompression::Gzip);
+    assert!(cache.total_bytes() < data.len() as u64);
+    assert_eq!(&decompress_tile_data(&stored).unwrap()[..], data);
+    std::fs::remove_dir_all(cache.root()).unwrap();
+}
@@ -1,177 +1,229 @@
-use lyon::{lyon_tessellation::GeometryBuilder, math::*, path::Path, tessellation::FillVertex};
+use lyon::{
+    lyon_tessellation::GeometryBuilder, math::*, path::Path, path::VertexId,
+    tessellation::FillVertex,
+};
 
 use crate::*;
 
-pub fn get_side(a: &Point, b: &Point, c: &Point) -> i32 {
-    ((b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x)).signum() as i32
-}
+/// The number of triangles of a round cap or join.
+const ROUND_SEGMENTS: usize = 4;
 
-pub fn tesselate_line2(path: &Path, builder: &mut MeshBuilder, z: u32) {
-    GeometryBuilder::<FillVertex>::begin_geometry(builder);
-    // Fill
-    let points = path.points();
+/// Miter joins longer than this many half line widths are left out, which leaves a bevel join.
+const MITER_LIMIT: f32 = 2.0;
 
+/// Tessellates a line into a ribbon with the geometry of every cap and join.
+///
+/// The normals are scaled by the line width in the vertex shader,
+/// which also collapses the caps and joins the style of the line does not use.
+/// Every vertex carries its distance from the start of the line to evaluate dashes.
+pub fn tesselate_line2(path: &Path, builder: &mut MeshBuilder, z: u32) {
     let width_factor = 2f32.powi(z as i32 - 14);
 
-    let first = points[0];
-    let second = points[1];
-    let mut last_line = second.to_vector() - first.to_vector();
-    let normal = vector(last_line.y, -last_line.x);
-    let mut last_normal = if points.len() > 2 {
-        let third = points[2];
-        let next_line = third.to_vector() - second.to_vector();
-        let dot = normal.dot(last_line.normalize() + next_line.normalize());
-        if dot <= 1.0 && dot >= 0.0 {
-            normal
-        } else {
-            -normal
+    // Repeated points have no direction.
+    let mut points: Vec<Point> = vec![];
+    for point in path.points() {
+        if points.last() != Some(point) {
+            points.push(*point);
         }
-    } else {
-        normal
     }
-    .normalize()
-        * width_factor;
-
-    let (vl, vr) = {
-        let v1 = (first, last_normal);
-        let v2 = (first, -last_normal);
-        if get_side(&second, &first, &(first + last_normal)) == 1 {
-            (v1, v2)
-        } else {
-            (v2, v1)
-        }
-    };
+    if points.len() < 2 {
+        return;
+    }
+    let directions = points
+        .windows(2)
+        .map(|segment| (segment[1] - segment[0]).normalize())
+        .collect::<Vec<_>>();
+    let normal_of = |direction: Vector| vector(-direction.y, direction.x) * width_factor;
 
-    let mut last_vertex_left = builder
-        .add_vertex(FillVertex {
-            position: vl.0,
-            normal: vl.1,
-        })
-        .unwrap();
+    GeometryBuilder::<FillVertex>::begin_geometry(builder);
 
-    let mut last_vertex_right = builder
-        .add_vertex(FillVertex {
-            position: vr.0,
-            normal: vr.1,
-        })
-        .unwrap();
+    builder.set_current_line_distance(0.0);
+    add_cap(
+        builder,
+        points[0],
+        normal_of(directions[0]),
+        -directions[0] * width_factor,
+    );
 
-    if points.len() > 2 {
-        for i in 0..points.len() - 2 {
-            let previous = points[i];
-            let current = points[i + 1];
-            let next = points[i + 2];
-            let current_line = current.to_vector() - previous.to_vector();
-            let next_line = current.to_vector() - next.to_vector();
-
-            let normal = current_line.normalize() + next_line.normalize();
-            let local_normal = vector(last_line.y, -last_line.x);
-            let dot = local_normal.dot(last_normal);
-            let local_normal = if dot <= 1.0 && dot >= 0.0 {
-                local_normal
-            } else {
-                -local_normal
-            }
-            .normalize();
-
-            let dot = local_normal.dot(normal);
-            let normal = if dot == 0.0 { local_normal } else { normal }.normalize() * width_factor;
-
-            let factor = (1.0 / normal.dot(local_normal).abs()).min(3.0);
-
-            let (vl, vr) = {
-                let v1 = (current, normal * factor);
-                let v2 = (current, -normal * factor);
-                if get_side(&current, &previous, &(current + normal)) == 1 {
-                    (v1, v2)
-                } else {
-                    (v2, v1)
-                }
-            };
-
-            let vertex_left = builder
-                .add_vertex(FillVertex {
-                    position: vl.0,
-                    normal: vl.1,
-                })
-                .unwrap();
-
-            let vertex_right = builder
-                .add_vertex(FillVertex {
-                    position: vr.0,
-                    normal: vr.1,
-                })
-                .unwrap();
-
-            GeometryBuilder::<FillVertex>::add_triangle(
-                builder,
-                last_vertex_left,
-                last_vertex_right,
-                vertex_left,
-            );
-            GeometryBuilder::<FillVertex>::add_triangle(
-                builder,
-                last_vertex_right,
-                vertex_right,
-                vertex_left,
-            );
-
-            last_vertex_left = vertex_left;
-            last_vertex_right = vertex_right;
-            last_normal = normal;
-            last_line = next_line;
+    let mut distance = 0.0;
+    for (i, direction) in directions.iter().enumerate() {
+        let (start, end) = (points[i], points[i + 1]);
+        let normal = normal_of(*direction);
+
+        builder.set_current_line_distance(distance);
+        let start_left = add_vertex(builder, start, normal, LinePart::Body);
+        let start_right = add_vertex(builder, start, -normal, LinePart::Body);
+        distance += (end - start).length();
+        builder.set_current_line_distance(distance);
+        let end_left = add_vertex(builder, end, normal, LinePart::Body);
+        let end_right = add_vertex(builder, end, -normal, LinePart::Body);
+        add_triangle(builder, start_left, start_right, end_left);
+        add_triangle(builder, start_right, end_right, end_left);
+
+        if let Some(next) = directions.get(i + 1) {
+            add_join(builder, end, normal, normal_of(*next));
         }
     }
 
-    let last = points[points.len() - 1];
-    let second_last = points[points.len() - 2];
-    let line = last.to_vector() - second_last.to_vector();
-    let normal: Vector = vector(line.y, -line.x).normalize() * width_factor;
+    let last = points.len() - 1;
+    add_cap(
+        builder,
+        points[last],
+        normal_of(directions[last - 1]),
+        directions[last - 1] * width_factor,
+    );
 
-    let dot = normal.dot(last_line.normalize() + line.normalize());
+    builder.set_current_line_part(LinePart::Body);
+    builder.set_current_line_distance(0.0);
+    GeometryBuilder::<FillVertex>::end_geometry(builder);
+}
 
-    let normal = if dot <= 1.0 && dot >= 0.0 {
-        normal
+/// Adds the square and the round cap at an end of a line.
+///
+/// `outwards` points away from the line and is as long as `normal`.
+fn add_cap(builder: &mut MeshBuilder, center: Point, normal: Vector, outwards: Vector) {
+    let left = add_vertex(builder, center, normal, LinePart::Body);
+    let right = add_vertex(builder, center, -normal, LinePart::Body);
+    let square_left = add_vertex(builder, center, normal + outwards, LinePart::SquareCap);
+    let square_right = add_vertex(builder, center, -normal + outwards, LinePart::SquareCap);
+    add_triangle(builder, left, right, square_right);
+    add_triangle(builder, left, square_right, square_left);
+
+    let angle = if normal.cross(outwards) > 0.0 {
+        std::f32::consts::PI
     } else {
-        -normal
+        -std::f32::consts::PI
     };
+    add_fan(builder, center, normal, angle, LinePart::RoundCap);
+}
+
+/// Adds the bevel, the miter and the round join between two segments with the normals `before` and `after`.
+fn add_join(builder: &mut MeshBuilder, center: Point, before: Vector, after: Vector) {
+    let angle = before.cross(after).atan2(before.dot(after));
+    if angle.abs() < 0.001 {
+        return;
+    }
+    // The join fills the gap on the outer side of the turn.
+    let side = if angle > 0.0 { -1.0 } else { 1.0 };
+    let (from, to) = (before * side, after * side);
+
+    let center_vertex = add_vertex(builder, center, vector(0.0, 0.0), LinePart::Body);
+    let from_vertex = add_vertex(builder, center, from, LinePart::Body);
+    let to_vertex = add_vertex(builder, center, to, LinePart::Body);
+    add_triangle(builder, center_vertex, from_vertex, to_vertex);
+
+    let miter_length = 1.0 / (angle / 2.0).cos();
+    if miter_length <= MITER_LIMIT {
+        let miter = (from + to).normalize() * from.length() * miter_length;
+        let miter_vertex = add_vertex(builder, center, miter, LinePart::MiterJoin);
+        add_triangle(builder, from_vertex, miter_vertex, to_vertex);
+    }
 
-    let (vl, vr) = {
-        let v1 = (last, normal);
-        let v2 = (last, -normal);
-        if get_side(&last, &second_last, &(last + normal * width_factor)) == 1 {
-            (v1, v2)
+    add_fan(builder, center, from, angle, LinePart::RoundJoin);
+}
+
+/// Adds a fan of triangles around `center` which rotates `from` by `angle` radians.
+///
+/// The vertices between the first and the last one belong to `part`.
+fn add_fan(builder: &mut MeshBuilder, center: Point, from: Vector, angle: f32, part: LinePart) {
+    let center_vertex = add_vertex(builder, center, vector(0.0, 0.0), LinePart::Body);
+    let mut previous = add_vertex(builder, center, from, LinePart::Body);
+    for i in 1..=ROUND_SEGMENTS {
+        let (sin, cos) = (angle * i as f32 / ROUND_SEGMENTS as f32).sin_cos();
+        let normal = vector(from.x * cos - from.y * sin, from.x * sin + from.y * cos);
+        let part = if i == ROUND_SEGMENTS {
+            LinePart::Body
         } else {
-            (v2, v1)
-        }
-    };
+            part
+        };
+        let vertex = add_vertex(builder, center, normal, part);
+        add_triangle(builder, center_vertex, previous, vertex);
+        previous = vertex;
+    }
+}
 
-    let vertex_left = builder
-        .add_vertex(FillVertex {
-            position: vl.0,
-            normal: vl.1,
-        })
-        .unwrap();
+fn add_vertex(
+    builder: &mut MeshBuilder,
+    position: Point,
+    normal: Vector,
+    part: LinePart,
+) -> VertexId {
+    builder.set_current_line_part(part);
+    builder.add_vertex(FillVertex { position, normal }).unwrap()
+}
 
-    let vertex_right = builder
-        .add_vertex(FillVertex {
-            position: vr.0,
-            normal: vr.1,
-        })
-        .unwrap();
+fn add_triangle(builder: &mut MeshBuilder, a: VertexId, b: VertexId, c: VertexId) {
+    GeometryBuilder::<FillVertex>::add_triangle(builder, a, b, c);
+}
 
-    GeometryBuilder::<FillVertex>::add_triangle(
-        builder,
-        last_vertex_left,
-        last_vertex_right,
-        vertex_left,
-    );
-    GeometryBuilder::<FillVertex>::add_triangle(
-        builder,
-        last_vertex_right,
-        vertex_right,
-        vertex_left,
+#[cfg(test)]
+fn tesselate_test_line(points: &[(f32, f32)]) -> Vec<Vertex> {
+    let mut path = Path::builder();
+    path.move_to(point(points[0].0, points[0].1));
+    for (x, y) in &points[1..] {
+        path.line_to(point(*x, *y));
+    }
+    let path = path.build();
+
+    let mut buffers = lyon::tessellation::VertexBuffers::new();
+    let mut builder = MeshBuilder::new(
+        &mut buffers,
+        LayerVertexCtor::new(&TileId::new(14, 0, 0), 1.0),
     );
+    builder.set_current_vertex_type(VertexType::Line);
+    tesselate_line2(&path, &mut builder, 14);
+    assert!(buffers
+        .indices
+        .iter()
+        .all(|index| (*index as usize) < buffers.vertices.len()));
+    buffers.vertices
+}
 
-    GeometryBuilder::<FillVertex>::end_geometry(builder);
+#[test]
+fn tesselate_line_caps_and_joins() {
+    let vertices = tesselate_test_line(&[(0.0, 0.0), (100.0, 0.0), (100.0, 100.0)]);
+    let part = |vertex: &Vertex| (vertex.feature_id >> 18) & 0x7;
+    let count = |line_part: LinePart| {
+        vertices
+            .iter()
+            .filter(|vertex| part(vertex) == line_part as u32)
+            .count()
+    };
+    assert_eq!(count(LinePart::SquareCap), 4);
+    assert_eq!(count(LinePart::RoundCap), 2 * (ROUND_SEGMENTS - 1));
+    assert_eq!(count(LinePart::MiterJoin), 1);
+    assert_eq!(count(LinePart::RoundJoin), ROUND_SEGMENTS - 1);
+
+    // The miter points to the outer corner of the turn.
+    let miter = vertices
+        .iter()
+        .find(|vertex| part(vertex) == LinePart::MiterJoin as u32)
+        .unwrap();
+    let (position, normal) = (miter.position, miter.normal);
+    assert_eq!(position, [100, 0]);
+    assert_eq!(normal, [1, -1]);
+
+    // Turns sharper than the miter limit are only beveled.
+    let vertices = tesselate_test_line(&[(0.0, 0.0), (100.0, 0.0), (0.0, 10.0)]);
+    assert!(vertices
+        .iter()
+        .all(|vertex| part(vertex) != LinePart::MiterJoin as u32));
+}
+
+#[test]
+fn tesselate_line_distances() {
+    let vertices = tesselate_test_line(&[(0.0, 0.0), (30.0, 40.0), (30.0, 40.0), (30.0, 100.0)]);
+    let distances = vertices
+        .iter()
+        .map(|vertex| (vertex.position, vertex.distance))
+        .collect::<Vec<_>>();
+    for (position, distance) in distances {
+        let expected = match position {
+            [0, 0] => 0.0,
+            [30, 40] => 50.0,
+            [30, 100] => 110.0,
+            position => panic!("Unexpected vertex at {:?}", position),
+        };
+        assert_eq!(distance, expected);
+    }
 }
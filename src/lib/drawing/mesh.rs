@@ -1,5 +1,5 @@
-use crate::drawing::vertex::VertexType;
 use crate::drawing::vertex::{LayerVertexCtor, Vertex};
+use crate::drawing::vertex::{LinePart, VertexType};
 use lyon::{
     path::{Index, VertexId},
     tessellation::{
@@ -45,6 +45,14 @@ impl<'l> MeshBuilder<'l> {
     pub fn set_current_vertex_type(&mut self, vertex_type: VertexType) {
         self.vertex_constructor.vertex_type = vertex_type;
     }
+
+    pub fn set_current_line_part(&mut self, line_part: LinePart) {
+        self.vertex_constructor.line_part = line_part;
+    }
+
+    pub fn set_current_line_distance(&mut self, distance: f32) {
+        self.vertex_constructor.line_distance = distance;
+    }
 }
 
 impl<'l> GeometryBuilder<FillVertex> for MeshBuilder<'l> {
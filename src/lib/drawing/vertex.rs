@@ -7,12 +7,27 @@ pub enum VertexType {
     Line = 1,
 }
 
+/// The part of a line a vertex belongs to.
+///
+/// The vertex shader collapses the vertices of caps and joins the style of the line does not use.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LinePart {
+    Body = 0,
+    SquareCap = 1,
+    RoundCap = 2,
+    MiterJoin = 3,
+    RoundJoin = 4,
+}
+
 #[derive(Copy, Clone, Debug)]
 #[repr(C, packed)]
 pub struct Vertex {
     pub position: [i16; 2],
     pub normal: [i16; 2],
+    /// The feature id in the lower 16 bits, followed by the `VertexType` in 2 bits and the `LinePart` in 3 bits.
     pub feature_id: u32,
+    /// The distance from the start of the line in tile units.
+    pub distance: f32,
 }
 
 // A very simple vertex constructor that only outputs the vertex position
@@ -21,6 +36,8 @@ pub struct LayerVertexCtor {
     pub feature_id: u32,
     pub extent: f32,
     pub vertex_type: VertexType,
+    pub line_part: LinePart,
+    pub line_distance: f32,
 }
 
 impl LayerVertexCtor {
@@ -30,6 +47,8 @@ impl LayerVertexCtor {
             feature_id: 0,
             extent,
             vertex_type: VertexType::Polygon,
+            line_part: LinePart::Body,
+            line_distance: 0.0,
         }
     }
 }
@@ -45,12 +64,13 @@ impl VertexConstructor<tessellation::FillVertex, Vertex> for LayerVertexCtor {
             vertex.normal
         } * self.extent;
 
-        let meta: u16 = self.vertex_type as u16;
+        let meta: u16 = self.vertex_type as u16 | (self.line_part as u16) << 2;
 
         Vertex {
             position: [vertex.position.x as i16, vertex.position.y as i16],
             normal: [normal.x.round() as i16, normal.y.round() as i16],
             feature_id: ((meta as u32) << 16) | self.feature_id,
+            distance: self.line_distance,
         }
     }
 }
@@ -69,6 +89,7 @@ impl VertexConstructor<tessellation::StrokeVertex, Vertex> for LayerVertexCtor {
             position: [vertex.position.x as i16, vertex.position.y as i16],
             normal: [normal.x.round() as i16, normal.y.round() as i16],
             feature_id: self.feature_id,
+            distance: self.line_distance,
         }
     }
 }
@@ -5,6 +5,7 @@ pub use collection::*;
 use crate::*;
 
 #[derive(Debug, Copy, Clone, Default)]
+#[repr(C)]
 pub struct DrawableColor {
     pub r: f32,
     pub g: f32,
@@ -23,14 +24,64 @@ impl From<Color> for DrawableColor {
     }
 }
 
+/// How the ends of a line are drawn.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum LineCap {
+    Butt = 0,
+    Round = 1,
+    Square = 2,
+}
+
+impl LineCap {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "butt" => Some(LineCap::Butt),
+            "round" => Some(LineCap::Round),
+            "square" => Some(LineCap::Square),
+            _ => None,
+        }
+    }
+}
+
+/// How the segments of a line are joined.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum LineJoin {
+    Miter = 0,
+    Bevel = 1,
+    Round = 2,
+}
+
+impl LineJoin {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "miter" => Some(LineJoin::Miter),
+            "bevel" => Some(LineJoin::Bevel),
+            "round" => Some(LineJoin::Round),
+            _ => None,
+        }
+    }
+}
+
+/// The style of a feature as it is uploaded to the GPU.
+///
+/// The layout has to match `LayerData` in the shader.
 #[derive(Debug, Copy, Clone, Default)]
+#[repr(C)]
 pub struct FeatureStyle {
     pub background_color: DrawableColor,
     pub border_color: DrawableColor,
     pub border_width: f32,
-    pub line_width: u32,
+    /// The width of a line in pixels, or in world units if `world_scale_line` is set.
+    pub line_width: f32,
     pub display: bool,
+    /// A `LineCap`.
+    pub line_cap: u8,
+    /// A `LineJoin`.
+    pub line_join: u8,
+    pub world_scale_line: bool,
     pub z_index: f32,
+    /// The lengths of two dashes and the gaps after them in line widths, all zero for a solid line.
+    pub line_dasharray: [f32; 4],
 }
 
 #[derive(Debug, Clone, Default)]
@@ -53,33 +104,20 @@ impl Feature {
     }
 
     pub fn load_style(&mut self, zoom: f32, css_cache: &mut RulesCache) {
-        let rules = css_cache.get_matching_rules(
+        let style = css_cache.computed_style(
             &self
                 .selector
                 .clone()
-                .with_any("zoom".to_string(), (zoom.floor() as u32).to_string()),
+                .with_any("zoom".to_string(), zoom.to_string()),
         );
+        let value = |key: &str| style.get(key).map(|computed| computed.value.at_zoom(zoom));
 
-        let background_color = rules
-            .iter()
-            .filter_map(|r| r.kvs.get("background-color"))
-            .last();
+        let background_color = value("background-color");
 
         if let Some(color) = background_color {
             match color {
                 CSSValue::Color(bg) => {
-                    self.style.background_color = bg.clone().into();
-                }
-                CSSValue::String(string) => {
-                    match &string[..] {
-                        "red" => self.style.background_color = Color::RED.into(),
-                        "green" => self.style.background_color = Color::GREEN.into(),
-                        "blue" => self.style.background_color = Color::BLUE.into(),
-                        "black" => self.style.background_color = Color::BLACK.into(),
-                        "white" => self.style.background_color = Color::WHITE.into(),
-                        // Other CSS colors to come later.
-                        color => log::info!("The color '{}' is currently not supported.", color),
-                    }
+                    self.style.background_color = bg.into();
                 }
                 value => {
                     log::info!(
@@ -90,27 +128,12 @@ impl Feature {
             }
         }
 
-        let border_color = rules
-            .iter()
-            .filter_map(|r| r.kvs.get("border-color"))
-            .last();
+        let border_color = value("border-color");
 
         if let Some(color) = border_color {
             match color {
                 CSSValue::Color(bg) => {
-                    self.style.border_color = bg.clone().into();
-                    self.style.border_color; // TODO this is probably no intended to be this way
-                }
-                CSSValue::String(string) => {
-                    match &string[..] {
-                        "red" => self.style.border_color = Color::RED.into(),
-                        "blue" => self.style.border_color = Color::GREEN.into(),
-                        "green" => self.style.border_color = Color::BLUE.into(),
-                        "black" => self.style.background_color = Color::BLACK.into(),
-                        "white" => self.style.background_color = Color::WHITE.into(),
-                        // Other CSS colors to come later.
-                        color => log::info!("The color '{}' is currently not supported.", color),
-                    }
+                    self.style.border_color = bg.into();
                 }
                 value => {
                     log::info!(
@@ -121,15 +144,12 @@ impl Feature {
             }
         }
 
-        let border_width = rules
-            .iter()
-            .filter_map(|r| r.kvs.get("border-width"))
-            .last();
+        let border_width = value("border-width");
 
         if let Some(border_width) = border_width {
             match border_width {
                 CSSValue::Number(number) => match number {
-                    Number::Px(px) => self.style.border_width = *px,
+                    Number::Px(px) => self.style.border_width = px,
                     value => log::info!(
                         "The value '{:?}' is currently not supported for 'border-width'.",
                         value
@@ -142,7 +162,7 @@ impl Feature {
             }
         }
 
-        let display = rules.iter().filter_map(|r| r.kvs.get("display")).last();
+        let display = value("display");
 
         if let Some(display) = display {
             match display {
@@ -159,13 +179,19 @@ impl Feature {
             self.style.display = true;
         }
 
-        let line_width = rules.iter().filter_map(|r| r.kvs.get("line-width")).last();
+        let line_width = value("line-width");
 
         if let Some(line_width) = line_width {
             match line_width {
                 CSSValue::Number(number) => match number {
-                    Number::Px(px) => self.style.line_width = (*px as u32) << 1 | 0b01,
-                    Number::World(world) => self.style.line_width = ((*world as u32) << 1) | 0b00,
+                    Number::Px(px) => {
+                        self.style.line_width = px;
+                        self.style.world_scale_line = false;
+                    }
+                    Number::World(world) => {
+                        self.style.line_width = world;
+                        self.style.world_scale_line = true;
+                    }
                     value => log::info!(
                         "The value '{:?}' is currently not supported for 'line-width'.",
                         value
@@ -177,15 +203,71 @@ impl Feature {
                 ),
             }
         } else {
-            self.style.line_width = 0;
+            self.style.line_width = 0.0;
+            self.style.world_scale_line = false;
         }
 
-        let z_index = rules.iter().filter_map(|r| r.kvs.get("z-index")).last();
+        let line_cap = value("line-cap");
+
+        let line_cap = match line_cap {
+            Some(CSSValue::String(name)) => LineCap::from_name(&name).unwrap_or_else(|| {
+                log::info!(
+                    "The value '{}' is currently not supported for 'line-cap'.",
+                    name
+                );
+                LineCap::Butt
+            }),
+            Some(value) => {
+                log::info!(
+                    "The value '{:?}' is currently not supported for 'line-cap'.",
+                    value
+                );
+                LineCap::Butt
+            }
+            None => LineCap::Butt,
+        };
+        self.style.line_cap = line_cap as u8;
+
+        let line_join = value("line-join");
+
+        let line_join = match line_join {
+            Some(CSSValue::String(name)) => LineJoin::from_name(&name).unwrap_or_else(|| {
+                log::info!(
+                    "The value '{}' is currently not supported for 'line-join'.",
+                    name
+                );
+                LineJoin::Miter
+            }),
+            Some(value) => {
+                log::info!(
+                    "The value '{:?}' is currently not supported for 'line-join'.",
+                    value
+                );
+                LineJoin::Miter
+            }
+            None => LineJoin::Miter,
+        };
+        self.style.line_join = line_join as u8;
+
+        let line_dasharray = value("line-dasharray");
+
+        self.style.line_dasharray = match line_dasharray {
+            Some(value) => dasharray(&value).unwrap_or_else(|| {
+                log::info!(
+                    "The value '{:?}' is currently not supported for 'line-dasharray'.",
+                    value
+                );
+                [0.0; 4]
+            }),
+            None => [0.0; 4],
+        };
+
+        let z_index = value("z-index");
 
         if let Some(z_index) = z_index {
             match z_index {
                 CSSValue::Number(number) => match number {
-                    Number::Unitless(unitless) => self.style.z_index = *unitless,
+                    Number::Unitless(unitless) => self.style.z_index = unitless,
                     value => log::info!(
                         "The value '{:?}' is currently not supported for 'z-index'.",
                         value
@@ -201,3 +283,97 @@ impl Feature {
         }
     }
 }
+
+/// Converts a `line-dasharray` value into the lengths of two dashes and the gaps after them.
+///
+/// A single length is used for all dashes and gaps and two lengths are repeated.
+/// `none` is a solid line.
+fn dasharray(value: &CSSValue) -> Option<[f32; 4]> {
+    let numbers = match value {
+        CSSValue::String(name) if name == "none" => return Some([0.0; 4]),
+        CSSValue::Number(number) => vec![*number],
+        CSSValue::Numbers(numbers) => numbers.clone(),
+        _ => return None,
+    };
+    let lengths = numbers
+        .iter()
+        .map(|number| match number {
+            Number::Unitless(length) if *length >= 0.0 => Some(*length),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    match lengths[..] {
+        [length] => Some([length; 4]),
+        [dash, gap] => Some([dash, gap, dash, gap]),
+        [dash, gap, second_dash, second_gap] => Some([dash, gap, second_dash, second_gap]),
+        _ => None,
+    }
+}
+
+#[test]
+fn load_line_styles() {
+    let rules = try_parse_styles(
+        "layer[name=boundary] { line-dasharray: 4 2; line-cap: round; }
+         layer[name=boundary][admin_level=2] { line-dasharray: 3 1 1 1; line-join: bevel; }
+         layer[name=boundary][admin_level=4] { line-dasharray: none; line-cap: rounded; }
+         layer[name=boundary][admin_level=6] { line-width: 2w; line-dasharray: 2 1; }",
+    )
+    .unwrap();
+    let mut css_cache = RulesCache::from_rules(rules);
+    let mut style = |admin_level: &str| {
+        let selector = Selector::new()
+            .with_type("layer")
+            .with_any("name", "boundary")
+            .with_any("admin_level", admin_level);
+        let mut feature = Feature::new(selector, 0);
+        feature.load_style(10.0, &mut css_cache);
+        feature.style
+    };
+
+    let style_8 = style("8");
+    assert_eq!(style_8.line_dasharray, [4.0, 2.0, 4.0, 2.0]);
+    assert_eq!(style_8.line_cap, LineCap::Round as u8);
+    assert_eq!(style_8.line_join, LineJoin::Miter as u8);
+
+    let style_2 = style("2");
+    assert_eq!(style_2.line_dasharray, [3.0, 1.0, 1.0, 1.0]);
+    assert_eq!(style_2.line_join, LineJoin::Bevel as u8);
+
+    // Unknown keywords fall back to the default.
+    let style_4 = style("4");
+    assert_eq!(style_4.line_dasharray, [0.0; 4]);
+    assert_eq!(style_4.line_cap, LineCap::Butt as u8);
+
+    // Dashes of world scale lines are measured in the widths of the line too.
+    let style_6 = style("6");
+    assert_eq!(style_6.line_dasharray, [2.0, 1.0, 2.0, 1.0]);
+    assert_eq!(style_6.line_width, 2.0);
+    assert!(style_6.world_scale_line);
+
+    assert_eq!(std::mem::size_of::<FeatureStyle>(), 64);
+}
+
+#[test]
+fn interpolate_line_widths() {
+    let rules = try_parse_styles(
+        "layer[name=water] { line-width: interpolate(linear, zoom, 8: 1px, 14: 4px); }
+         layer[name=waterway] { line-width: 2.5w; }",
+    )
+    .unwrap();
+    let mut css_cache = RulesCache::from_rules(rules);
+    let mut style = |name: &str, zoom: f32| {
+        let selector = Selector::new().with_type("layer").with_any("name", name);
+        let mut feature = Feature::new(selector, 0);
+        feature.load_style(zoom, &mut css_cache);
+        feature.style
+    };
+
+    // A width between two stops is not rounded to whole pixels.
+    let water = style("water", 9.0);
+    assert!((water.line_width - 1.5).abs() < 1e-5);
+    assert!(!water.world_scale_line);
+
+    let waterway = style("waterway", 9.0);
+    assert_eq!(waterway.line_width, 2.5);
+    assert!(waterway.world_scale_line);
+}
@@ -1,63 +1,142 @@
-use std::{fs::File, io::Read, path::Path};
+use std::{
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+};
 
 use super::*;
 
-pub fn fetch_tile_data(cache_location: impl AsRef<Path>, tile_id: &TileId) -> Option<Vec<u8>> {
-    let zxy: String = format!("{}", tile_id);
-    let pbf = format!("cache/{}.pbf", zxy);
-    if !is_in_cache(pbf.clone()) {
-        if let Some(data) = fetch_tile_from_server(tile_id) {
-            ensure_cache_structure(cache_location, tile_id);
-            match File::create(&pbf) {
-                Ok(mut file) => {
-                    use std::io::Write;
-                    match file.write_all(&data[..]) {
-                        Ok(_) => Some(data),
-                        Err(e) => {
-                            log::error!("Unable to write pbf {}. Reason:\r\n{}", pbf, e);
-                            None
-                        }
-                    }
+/// Fetches the data of a tile from `url` and keeps a copy in the `disk_cache`.
+///
+/// If the tile is already present in the disk cache, no request is made unless the cached copy expired.
+/// Expired tiles are revalidated with the server and still used if the server can not be reached.
+pub fn fetch_tile_data(disk_cache: &DiskCache, url: &str, tile_id: &TileId) -> Option<Vec<u8>> {
+    if let Some(entry) = disk_cache.entry(tile_id) {
+        let data = if disk_cache.is_expired(&entry) {
+            match fetch_tile_conditional(
+                url,
+                tile_id,
+                entry.etag.as_deref(),
+                entry.last_modified.as_deref(),
+            ) {
+                Some(FetchResponse::NotModified) => {
+                    disk_cache.refresh(tile_id);
+                    disk_cache.read(tile_id)
                 }
-                Err(e) => {
-                    log::error!("Could not create pbf {}. Reason:\r\n{}", pbf, e);
-                    None
+                Some(FetchResponse::Modified {
+                    data,
+                    etag,
+                    last_modified,
+                }) => {
+                    disk_cache.store(tile_id, &data, etag.as_deref(), last_modified.as_deref());
+                    Some(data)
                 }
+                // We are most likely offline, so a stale tile is better than none.
+                None => disk_cache.read(tile_id),
             }
         } else {
-            None
+            disk_cache.read(tile_id)
+        };
+
+        if data.is_some() {
+            return data;
         }
-    } else {
-        match File::open(&pbf) {
-            Ok(mut f) => {
-                let mut buffer = Vec::new();
-                match f.read_to_end(&mut buffer) {
-                    Ok(_) => Some(buffer),
-                    Err(e) => {
-                        log::error!("Unable to read {}. Reason:\r\n{}", pbf, e);
-                        None
-                    }
+    }
+
+    match fetch_tile_conditional(url, tile_id, None, None)? {
+        FetchResponse::Modified {
+            data,
+            etag,
+            last_modified,
+        } => {
+            disk_cache.store(tile_id, &data, etag.as_deref(), last_modified.as_deref());
+            Some(data)
+        }
+        FetchResponse::NotModified => None,
+    }
+}
+
+/// Returns the path of a tile in the disk cache below `root`.
+pub fn tile_cache_path(root: impl AsRef<Path>, tile_id: &TileId) -> PathBuf {
+    root.as_ref().join(format!(
+        "cache/{}/{}/{}.pbf",
+        tile_id.z, tile_id.x, tile_id.y
+    ))
+}
+
+/// Reads an entire tile file into memory.
+pub fn read_tile_file(path: impl AsRef<Path>) -> Option<Vec<u8>> {
+    let path = path.as_ref();
+    match File::open(path) {
+        Ok(mut f) => {
+            let mut buffer = Vec::new();
+            match f.read_to_end(&mut buffer) {
+                Ok(_) => Some(buffer),
+                Err(e) => {
+                    log::error!("Unable to read {:?}. Reason:\r\n{}", path, e);
+                    None
                 }
             }
-            Err(e) => {
-                log::error!("Unable to open {}. Reason:\r\n{}", pbf, e);
-                None
-            }
+        }
+        Err(e) => {
+            log::error!("Unable to open {:?}. Reason:\r\n{}", path, e);
+            None
         }
     }
 }
 
-fn fetch_tile_from_server(tile_id: &TileId) -> Option<Vec<u8>> {
-    let request_url = format!(
-        "https://api.maptiler.com/tiles/v3/{}.pbf?key=t2mP0OQnprAXkW20R6Wd",
-        tile_id
-    );
-    let response = ureq::get(&request_url).call();
+/// The answer of a tile server to a tile request.
+pub enum FetchResponse {
+    /// The tile changed or was requested unconditionally.
+    Modified {
+        data: Vec<u8>,
+        etag: Option<String>,
+        last_modified: Option<String>,
+    },
+    /// The tile did not change since the cached copy was fetched.
+    NotModified,
+}
+
+/// Downloads the data of a tile from `url` without touching the disk cache.
+pub fn fetch_tile_from_server(url: &str, tile_id: &TileId) -> Option<Vec<u8>> {
+    match fetch_tile_conditional(url, tile_id, None, None)? {
+        FetchResponse::Modified { data, .. } => Some(data),
+        FetchResponse::NotModified => None,
+    }
+}
+
+/// Requests a tile from `url`, only transferring it if it differs from the copy
+/// with the given `etag` or `last_modified` header.
+pub fn fetch_tile_conditional(
+    url: &str,
+    tile_id: &TileId,
+    etag: Option<&str>,
+    last_modified: Option<&str>,
+) -> Option<FetchResponse> {
+    let mut request = ureq::get(url);
+    if let Some(etag) = etag {
+        request.set("If-None-Match", etag);
+    }
+    if let Some(last_modified) = last_modified {
+        request.set("If-Modified-Since", last_modified);
+    }
+
+    let response = request.call();
+    if response.status() == 304 {
+        return Some(FetchResponse::NotModified);
+    }
+
     if response.ok() {
+        let etag = response.header("ETag").map(|v| v.to_string());
+        let last_modified = response.header("Last-Modified").map(|v| v.to_string());
         let mut reader = response.into_reader();
         let mut data = vec![];
         match reader.read_to_end(&mut data) {
-            Ok(_) => Some(data),
+            Ok(_) => Some(FetchResponse::Modified {
+                data,
+                etag,
+                last_modified,
+            }),
             Err(e) => {
                 log::warn!(
                     "Could not read http response for {} to buffer. Reason:\r\n{}",
@@ -77,11 +156,7 @@ fn fetch_tile_from_server(tile_id: &TileId) -> Option<Vec<u8>> {
     }
 }
 
-fn is_in_cache(path: impl Into<String>) -> bool {
-    Path::new(&path.into()).exists()
-}
-
-fn ensure_cache_structure(root: impl AsRef<Path>, tile_id: &TileId) {
+pub(crate) fn ensure_cache_structure(root: impl AsRef<Path>, tile_id: &TileId) {
     let dir_path = root
         .as_ref()
         .join(&format!("cache/{}/{}/", tile_id.z, tile_id.x));
@@ -95,3 +170,11 @@ fn test_ensure_cache_structure() {
     assert!(md.is_ok());
     assert!(md.unwrap().is_dir());
 }
+
+#[test]
+fn test_tile_cache_path() {
+    assert_eq!(
+        tile_cache_path("/tmp/sailor-test", &crate::TileId::new(8, 142, 93)),
+        PathBuf::from("/tmp/sailor-test/cache/8/142/93.pbf")
+    );
+}
@@ -1,9 +1,10 @@
 pub use crate::*;
 use ncollide2d::{
     math::{Isometry, Point, Vector},
+    na,
     pipeline::object::{CollisionGroups, GeometricQueryType},
     query::Ray,
-    shape::{Polyline, Segment, ShapeHandle},
+    shape::{Ball, Polyline, Segment, ShapeHandle},
     world::CollisionWorld,
 };
 use std::{
@@ -11,6 +12,9 @@ use std::{
     thread::spawn,
 };
 
+/// The distance in tile units within which a point feature is hovered.
+const POINT_RADIUS: f32 = 64.0;
+
 pub struct TileCollider {
     world: CollisionWorld<f32, usize>,
 }
@@ -22,15 +26,49 @@ impl TileCollider {
         }
     }
 
+    /// Adds all parts of an object to the collision world.
+    ///
+    /// All rings of a polygon form a single shape, so the even-odd hit test respects holes.
     pub fn add_object(&mut self, id: usize, object: &Object) {
-        let polygon = Polyline::new(
-            object
-                .points()
+        let closed = object.object_type() == ObjectType::Polygon;
+        let mut points = vec![];
+        let mut edges = vec![];
+        let mut add_part = |part: &[lyon::math::Point]| {
+            let start = points.len();
+            points.extend(part.iter().map(|p| Point::new(p.x, p.y)));
+            for i in start + 1..points.len() {
+                edges.push(na::Point2::new(i - 1, i));
+            }
+            if closed && part.len() > 2 {
+                edges.push(na::Point2::new(points.len() - 1, start));
+            }
+        };
+
+        match object.geometry() {
+            // Points have no area, so they are hit within a small circle around them.
+            Geometry::Points(positions) => {
+                for position in positions {
+                    self.world.add(
+                        Isometry::translation(position.x, position.y),
+                        ShapeHandle::new(Ball::new(POINT_RADIUS)),
+                        CollisionGroups::new(),
+                        GeometricQueryType::Contacts(0.02, 0.02),
+                        id,
+                    );
+                }
+            }
+            Geometry::Lines(lines) => lines.iter().for_each(|line| add_part(line)),
+            Geometry::Polygons(polygons) => polygons
                 .iter()
-                .map(|p| Point::new(p.x, p.y))
-                .collect::<Vec<Point<f32>>>(),
-            None,
-        );
+                .flat_map(|polygon| polygon.rings())
+                .for_each(|ring| add_part(ring)),
+        }
+
+        if edges.is_empty() {
+            return;
+        }
+
+        let polygon = Polyline::new(points, Some(edges));
 
         self.world.add(
             Isometry::identity(),
@@ -55,7 +93,15 @@ impl TileCollider {
         let ray = Ray::new(*point, Vector::x());
         for handle in interferences {
             if let Some(co) = self.world.collision_object(*handle) {
-                if let Some(polyline) = co.shape().downcast_ref::<Polyline<f32>>() {
+                if let Some(ball) = co.shape().downcast_ref::<Ball<f32>>() {
+                    let center = co.position().translation.vector;
+                    // A multipoint is hovered once, even if several of its points are close.
+                    if (point.coords - center).norm() <= ball.radius
+                        && !object_ids.contains(co.data())
+                    {
+                        object_ids.push(*co.data());
+                    }
+                } else if let Some(polyline) = co.shape().downcast_ref::<Polyline<f32>>() {
                     let mut winding_number = 0;
                     let points = polyline.points();
                     for edge in polyline.edges() {
@@ -91,10 +137,8 @@ impl TileColliderLoader for Arc<RwLock<TileCollider>> {
                 if let Ok(objects) = tile.objects().read() {
                     match collider_clone.write() {
                         Ok(mut collider) => {
-                            for object_id in 0..objects.len() {
-                                if objects[object_id].points().len() >= 2 {
-                                    collider.add_object(object_id, &objects[object_id]);
-                                }
+                            for (object_id, object) in objects.iter().enumerate() {
+                                collider.add_object(object_id, object);
                             }
                             collider.update();
                         }
@@ -107,3 +151,36 @@ impl TileColliderLoader for Arc<RwLock<TileCollider>> {
         });
     }
 }
+
+#[test]
+fn hover_points_and_polygons() {
+    use lyon::math::point;
+
+    let mut collider = TileCollider::new();
+    let buoys = Geometry::Points(vec![point(100.0, 100.0), point(150.0, 100.0)]);
+    collider.add_object(0, &Object::new(Selector::new(), buoys));
+    let square = vec![
+        point(1000.0, 1000.0),
+        point(2000.0, 1000.0),
+        point(2000.0, 2000.0),
+        point(1000.0, 2000.0),
+    ];
+    let island = Geometry::Polygons(vec![Polygon {
+        exterior: square,
+        interiors: vec![],
+    }]);
+    collider.add_object(1, &Object::new(Selector::new(), island));
+    collider.update();
+
+    assert_eq!(
+        collider.get_hovered_objects(&Point::new(120.0, 110.0)),
+        vec![0]
+    );
+    assert_eq!(
+        collider.get_hovered_objects(&Point::new(1500.0, 1500.0)),
+        vec![1]
+    );
+    assert!(collider
+        .get_hovered_objects(&Point::new(100.0, 300.0))
+        .is_empty());
+}
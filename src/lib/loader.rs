@@ -0,0 +1,222 @@
+use crate::*;
+use lyon::math::Point;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+
+/// A pending request to load a single tile.
+pub(crate) struct LoadRequest {
+    pub tile_id: TileId,
+    /// The squared distance of the tile center to the focus point in global space.
+    /// Smaller values are loaded first.
+    pub priority: f32,
+    pub feature_collection: Arc<RwLock<FeatureCollection>>,
+    pub selection_tags: Vec<String>,
+}
+
+impl PartialEq for LoadRequest {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for LoadRequest {}
+
+impl PartialOrd for LoadRequest {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for LoadRequest {
+    /// Orders requests such that the `BinaryHeap` pops the closest tile first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .priority
+            .partial_cmp(&self.priority)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| other.tile_id.cmp(&self.tile_id))
+    }
+}
+
+/// The area the user is currently looking at.
+///
+/// Tiles closest to the `center` are loaded first and requests outside the `tile_field` are cancelled.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct LoadFocus {
+    pub center: Point,
+    pub tile_field: TileField,
+}
+
+impl LoadFocus {
+    /// Computes the load priority of a tile. Smaller is more urgent.
+    pub fn priority(&self, tile_id: &TileId) -> f32 {
+        let tile_center = num_to_global_space(&TileCoordinate::new(
+            tile_id.z,
+            tile_id.x as f32 + 0.5,
+            tile_id.y as f32 + 0.5,
+        ));
+        (tile_center - self.center).square_length()
+    }
+
+    /// Checks if a tile is still wanted.
+    pub fn wants(&self, tile_id: &TileId) -> bool {
+        tile_id.z == self.tile_field.topleft.z
+            && tile_id.x >= self.tile_field.topleft.x
+            && tile_id.x <= self.tile_field.bottomright.x
+            && tile_id.y >= self.tile_field.topleft.y
+            && tile_id.y <= self.tile_field.bottomright.y
+    }
+}
+
+/// The queue of tiles waiting for a free loader thread.
+#[derive(Default)]
+pub(crate) struct LoadQueue {
+    requests: BinaryHeap<LoadRequest>,
+    shutdown: bool,
+}
+
+impl LoadQueue {
+    pub fn push(&mut self, request: LoadRequest) {
+        self.requests.push(request);
+    }
+
+    pub fn pop(&mut self) -> Option<LoadRequest> {
+        self.requests.pop()
+    }
+
+    pub fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    /// Removes all queued requests.
+    ///
+    /// Returns the ids of the cancelled tiles.
+    pub fn clear(&mut self) -> Vec<TileId> {
+        self.requests
+            .drain()
+            .map(|request| request.tile_id)
+            .collect()
+    }
+
+    /// Reprioritizes all queued requests for a new focus.
+    ///
+    /// Returns the ids of the cancelled tiles which are not wanted anymore.
+    pub fn refocus(&mut self, focus: &LoadFocus) -> Vec<TileId> {
+        let mut cancelled = vec![];
+        let requests = std::mem::take(&mut self.requests);
+        self.requests = requests
+            .into_iter()
+            .filter_map(|mut request| {
+                if focus.wants(&request.tile_id) {
+                    request.priority = focus.priority(&request.tile_id);
+                    Some(request)
+                } else {
+                    cancelled.push(request.tile_id);
+                    None
+                }
+            })
+            .collect();
+        cancelled
+    }
+}
+
+/// The result of a single request.
+pub(crate) type LoadResult = (TileId, Result<Tile, TileError>);
+
+/// The queue shared between a `TileCache` and its loader threads.
+pub(crate) type SharedLoadQueue = Arc<(Mutex<LoadQueue>, Condvar)>;
+
+/// Signals all loader threads waiting on `queue` to exit.
+pub(crate) fn shutdown_loaders(queue: &SharedLoadQueue) {
+    let (lock, condvar) = &**queue;
+    lock.lock().unwrap().shutdown = true;
+    condvar.notify_all();
+}
+
+/// Spawns a loader thread which loads the requests from `queue` until it is shut down.
+///
+/// Every finished request is reported over `tx`, with the reason if the tile could not be loaded.
+pub(crate) fn spawn_loader(
+    queue: SharedLoadQueue,
+    source: Arc<dyn TileSource>,
+    tx: Sender<LoadResult>,
+) {
+    std::thread::spawn(move || loop {
+        let request = {
+            let (lock, condvar) = &*queue;
+            let mut queue = lock.lock().unwrap();
+            loop {
+                if queue.shutdown {
+                    return;
+                }
+                if let Some(request) = queue.pop() {
+                    break request;
+                }
+                queue = condvar.wait(queue).unwrap();
+            }
+        };
+
+        let tile_id = request.tile_id;
+        let source = source.clone();
+        // Keep the loader alive even if a single tile brings it down.
+        let tile = std::panic::catch_unwind(std::panic::AssertUnwindSafe(move || {
+            let data = source
+                .fetch(&request.tile_id)
+                .ok_or(TileError::Unavailable)?;
+            Tile::from_mbvt(
+                &request.tile_id,
+                &data,
+                request.feature_collection,
+                request.selection_tags,
+            )
+        }))
+        .unwrap_or_else(|e| {
+            let reason = e
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| e.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            Err(TileError::Panic(reason))
+        });
+
+        if let Err(e) = &tile {
+            log::error!("Failed to load tile {}. Reason:\r\n{}", tile_id, e);
+        }
+
+        if tx.send((tile_id, tile)).is_err() {
+            log::debug!("Could not send the tile load message. This most likely happened because the app was terminated.");
+            return;
+        }
+    });
+}
+
+#[test]
+fn closest_requests_first_and_cancel_outside() {
+    let feature_collection = Arc::new(RwLock::new(FeatureCollection::new(500)));
+    let request = |x, y| LoadRequest {
+        tile_id: TileId::new(4, x, y),
+        priority: 0.0,
+        feature_collection: feature_collection.clone(),
+        selection_tags: vec![],
+    };
+
+    let mut queue = LoadQueue::default();
+    for (x, y) in &[(0, 0), (1, 1), (2, 2), (3, 3)] {
+        queue.push(request(*x, *y));
+    }
+
+    // Focus on the center of tile 4/2/2 and move tile 4/0/0 out of view.
+    let focus = LoadFocus {
+        center: num_to_global_space(&TileCoordinate::new(4, 2.5, 2.5)),
+        tile_field: TileField::new(TileId::new(4, 1, 1), TileId::new(4, 3, 3)),
+    };
+    assert_eq!(queue.refocus(&focus), vec![TileId::new(4, 0, 0)]);
+    assert_eq!(queue.len(), 3);
+
+    assert_eq!(queue.pop().unwrap().tile_id, TileId::new(4, 2, 2));
+    let next = [queue.pop().unwrap().tile_id, queue.pop().unwrap().tile_id];
+    assert!(next.contains(&TileId::new(4, 1, 1)) && next.contains(&TileId::new(4, 3, 3)));
+    assert!(queue.pop().is_none());
+}
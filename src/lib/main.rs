@@ -2,21 +2,32 @@ use nalgebra_glm as glm;
 use parity_util_mem as malloc_size_of;
 
 mod cache;
+mod compression;
 mod css;
+mod disk_cache;
 mod drawing;
 mod feature;
 mod fetch;
 mod interaction;
+mod loader;
 mod math;
+mod nmea;
 mod object;
+mod route;
+mod source;
 mod vector_tile;
 
 pub use cache::*;
+pub use compression::*;
 pub use css::*;
+pub use disk_cache::*;
 pub use drawing::*;
 pub use feature::*;
 pub use fetch::*;
 pub use interaction::*;
 pub use math::*;
+pub use nmea::*;
 pub use object::*;
+pub use route::*;
+pub use source::*;
 pub use vector_tile::*;
@@ -0,0 +1,176 @@
+use super::*;
+
+/// The position in degrees, the course over ground in degrees and the speed in knots of a vessel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Motion {
+    pub position: (f32, f32),
+    pub course: f32,
+    pub speed: f32,
+}
+
+/// The closest point of approach of two vessels which keep their course and speed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Approach {
+    /// The distance at the closest point of approach in nautical miles.
+    pub cpa: f32,
+    /// The minutes until the closest point of approach, negative if it has been passed already.
+    pub tcpa: f32,
+    /// The current distance in nautical miles.
+    pub distance: f32,
+}
+
+/// Returns the offset of `target` from `own` in nautical miles towards east and north.
+///
+/// The positions are projected onto a plane around `own`, which is exact enough within the range of AIS.
+fn offset(own: (f32, f32), target: (f32, f32)) -> Vector {
+    let (lat, lon) = own;
+    // Take the short way across the antimeridian.
+    let mut delta_lon = target.1 - lon;
+    if delta_lon > 180.0 {
+        delta_lon -= 360.0;
+    } else if delta_lon < -180.0 {
+        delta_lon += 360.0;
+    }
+    // A minute of latitude is a nautical mile.
+    vector(
+        delta_lon * 60.0 * deg2rad(lat).cos(),
+        (target.0 - lat) * 60.0,
+    )
+}
+
+/// Returns the distance between two positions in degrees in nautical miles.
+pub fn distance(own: (f32, f32), target: (f32, f32)) -> f32 {
+    offset(own, target).length()
+}
+
+/// Computes the closest point of approach of a target to the own vessel.
+pub fn closest_approach(own: &Motion, target: &Motion) -> Approach {
+    let offset = offset(own.position, target.position);
+    let velocity = |motion: &Motion| {
+        let (sin, cos) = deg2rad(motion.course).sin_cos();
+        vector(sin, cos) * motion.speed
+    };
+    let relative_velocity = velocity(target) - velocity(own);
+
+    let speed_squared = relative_velocity.square_length();
+    // Vessels which move alike keep their distance.
+    let hours = if speed_squared < 1e-6 {
+        0.0
+    } else {
+        -offset.dot(relative_velocity) / speed_squared
+    };
+    Approach {
+        cpa: (offset + relative_velocity * hours.max(0.0)).length(),
+        tcpa: hours * 60.0,
+        distance: offset.length(),
+    }
+}
+
+#[cfg(test)]
+fn assert_approach(approach: Approach, cpa: f32, tcpa: f32) {
+    assert!(
+        (approach.cpa - cpa).abs() < 1e-3 && (approach.tcpa - tcpa).abs() < 1e-2,
+        "Expected a CPA of {} nm in {} min, got {:?}",
+        cpa,
+        tcpa,
+        approach
+    );
+}
+
+#[test]
+fn crossing_approach() {
+    let own = Motion {
+        position: (0.0, 0.0),
+        course: 0.0,
+        speed: 10.0,
+    };
+    // Both reach the point 5 nm north of the own vessel in 30 minutes.
+    let target = Motion {
+        position: (5.0 / 60.0, 5.0 / 60.0),
+        course: 270.0,
+        speed: 10.0,
+    };
+    assert_approach(closest_approach(&own, &target), 0.0, 30.0);
+
+    // A target which crosses later passes astern.
+    let target = Motion {
+        position: (5.0 / 60.0, 7.0 / 60.0),
+        ..target
+    };
+    let approach = closest_approach(&own, &target);
+    assert!(approach.cpa > 1.0 && approach.tcpa > 30.0);
+}
+
+#[test]
+fn head_on_approach() {
+    let own = Motion {
+        position: (45.0, 10.0),
+        course: 0.0,
+        speed: 6.0,
+    };
+    let target = Motion {
+        position: (45.1, 10.0),
+        course: 180.0,
+        speed: 6.0,
+    };
+    assert_approach(closest_approach(&own, &target), 0.0, 30.0);
+
+    // A target a nautical mile to the east passes at that distance.
+    let target = Motion {
+        position: (45.1, 10.0 + 1.0 / 60.0 / deg2rad(45.0).cos()),
+        ..target
+    };
+    assert_approach(closest_approach(&own, &target), 1.0, 30.0);
+
+    // Once passed, the closest point of approach is behind.
+    let own = Motion {
+        position: (45.2, 10.0),
+        ..own
+    };
+    let approach = closest_approach(&own, &target);
+    assert!(approach.tcpa < 0.0);
+    assert!((approach.cpa - approach.distance).abs() < 1e-4);
+}
+
+#[test]
+fn overtaking_approach() {
+    let own = Motion {
+        position: (0.0, 0.0),
+        course: 0.0,
+        speed: 12.0,
+    };
+    // A slower target 2 nm ahead and half a nautical mile to the side is passed in 30 minutes.
+    let target = Motion {
+        position: (2.0 / 60.0, 0.5 / 60.0),
+        course: 0.0,
+        speed: 8.0,
+    };
+    assert_approach(closest_approach(&own, &target), 0.5, 30.0);
+
+    // A target with the same course and speed keeps its distance.
+    let target = Motion {
+        speed: 12.0,
+        ..target
+    };
+    let approach = closest_approach(&own, &target);
+    assert_approach(approach, approach.distance, 0.0);
+}
+
+#[test]
+fn approach_across_antimeridian() {
+    // The target is a nautical mile west of the own vessel, on the other side of the antimeridian.
+    let own = Motion {
+        position: (0.0, -179.99),
+        course: 270.0,
+        speed: 6.0,
+    };
+    let target = Motion {
+        position: (0.0, 360.0 - 179.99 - 1.0 / 60.0),
+        course: 90.0,
+        speed: 6.0,
+    };
+    let approach = closest_approach(&own, &target);
+    assert!((approach.distance - 1.0).abs() < 1e-2);
+    assert_approach(approach, 0.0, 5.0);
+    assert!((distance(target.position, own.position) - 1.0).abs() < 1e-2);
+}
@@ -1,14 +1,21 @@
+mod cpa;
 mod screen;
+mod sun;
 mod tile_field;
 mod tile_id;
 
-use lyon::math::{point, vector, Point};
+use lyon::math::{point, vector, Point, Vector};
 use std::f32::consts::PI;
 
+pub use cpa::*;
 pub use screen::*;
+pub use sun::*;
 pub use tile_field::*;
 pub use tile_id::*;
 
+/// The circumference of the earth at the equator in meters.
+const EARTH_CIRCUMFERENCE: f32 = 40_075_016.686;
+
 fn deg2rad(deg: f32) -> f32 {
     2.0 * PI * deg / 360.0
 }
@@ -22,6 +29,58 @@ pub fn deg2num(lat_deg: f32, lon_deg: f32, zoom: u32) -> TileCoordinate {
     TileCoordinate::new(zoom, xtile, ytile)
 }
 
+/// Returns the initial bearing in degrees and the distance in nautical miles
+/// of the shortest route between two positions in degrees.
+pub fn great_circle(from: (f32, f32), to: (f32, f32)) -> (f32, f32) {
+    let (lat1, lon1) = (
+        f64::from(from.0).to_radians(),
+        f64::from(from.1).to_radians(),
+    );
+    let (lat2, lon2) = (f64::from(to.0).to_radians(), f64::from(to.1).to_radians());
+    let delta_lon = lon2 - lon1;
+
+    // The haversine formula is accurate for short distances as well.
+    let a = ((lat2 - lat1) / 2.0).sin().powi(2)
+        + lat1.cos() * lat2.cos() * (delta_lon / 2.0).sin().powi(2);
+    let angle = 2.0 * a.sqrt().atan2((1.0 - a).sqrt());
+
+    let bearing = (delta_lon.sin() * lat2.cos())
+        .atan2(lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * delta_lon.cos());
+    (
+        bearing.to_degrees().rem_euclid(360.0) as f32,
+        (angle.to_degrees() * 60.0) as f32,
+    )
+}
+
+/// Returns the constant bearing in degrees and the distance in nautical miles
+/// of the rhumb line between two positions in degrees.
+pub fn rhumb_line(from: (f32, f32), to: (f32, f32)) -> (f32, f32) {
+    let (lat1, lat2) = (f64::from(from.0).to_radians(), f64::from(to.0).to_radians());
+    let mut delta_lon = f64::from(to.1 - from.1).to_radians();
+    // Crossing the antimeridian is shorter than going around.
+    if delta_lon.abs() > std::f64::consts::PI {
+        delta_lon -= delta_lon.signum() * 2.0 * std::f64::consts::PI;
+    }
+    let delta_lat = lat2 - lat1;
+
+    // The difference of the latitudes stretched like in the Mercator projection.
+    let delta_psi = ((lat2 / 2.0 + std::f64::consts::FRAC_PI_4).tan()
+        / (lat1 / 2.0 + std::f64::consts::FRAC_PI_4).tan())
+    .ln();
+    let q = if delta_psi.abs() > 1e-12 {
+        delta_lat / delta_psi
+    } else {
+        lat1.cos()
+    };
+
+    let angle = (delta_lat * delta_lat + q * q * delta_lon * delta_lon).sqrt();
+    let bearing = delta_lon.atan2(delta_psi);
+    (
+        bearing.to_degrees().rem_euclid(360.0) as f32,
+        (angle.to_degrees() * 60.0) as f32,
+    )
+}
+
 pub fn num_to_global_space(coordinate: &TileCoordinate) -> Point {
     point(0.0, 0.0) + vector(coordinate.x, coordinate.y) * 1.0 / 2f32.powi(coordinate.z as i32)
 }
@@ -30,3 +89,79 @@ pub fn global_to_num_space(point: &Point, z: u32) -> TileCoordinate {
     let p = *point / 2f32.powi(-(z as i32));
     TileCoordinate::new(z, p.x, p.y)
 }
+
+/// Converts a point in global space back to latitude and longitude in degrees.
+pub fn global_space_to_deg(point: &Point) -> (f32, f32) {
+    let lon_deg = point.x * 360.0 - 180.0;
+    let lat_rad = (PI * (1.0 - 2.0 * point.y)).sinh().atan();
+    (lat_rad * 180.0 / PI, lon_deg)
+}
+
+/// Converts a length in meters at the latitude `lat_deg` into a length in global space.
+pub fn meters_to_global(meters: f32, lat_deg: f32) -> f32 {
+    meters / (EARTH_CIRCUMFERENCE * deg2rad(lat_deg).cos())
+}
+
+/// Moves a point in global space by `meters` towards `bearing` in degrees relative to true north.
+///
+/// The projection keeps bearings, so this is exact for the short distances shown on the chart.
+pub fn project_global(point: &Point, bearing: f32, meters: f32) -> Point {
+    let (lat, _) = global_space_to_deg(point);
+    let (sin, cos) = deg2rad(bearing).sin_cos();
+    *point + vector(sin, -cos) * meters_to_global(meters, lat)
+}
+
+#[test]
+fn project_global_positions() {
+    let start = num_to_global_space(&deg2num(60.0, 10.0, 0));
+    // A minute of latitude is a nautical mile.
+    let north = project_global(&start, 0.0, 6.0 * 1852.0);
+    let (lat, lon) = global_space_to_deg(&north);
+    assert!((lat - 60.1).abs() < 1e-3 && (lon - 10.0).abs() < 1e-4);
+
+    // A minute of longitude at 60° is half as long as at the equator.
+    let east = project_global(&start, 90.0, 3.0 * 1852.0);
+    let (lat, lon) = global_space_to_deg(&east);
+    assert!((lat - 60.0).abs() < 1e-4 && (lon - 10.1).abs() < 1e-3);
+}
+
+#[test]
+fn great_circle_and_rhumb_lines() {
+    let assert_close = |(bearing, distance): (f32, f32), expected: (f32, f32)| {
+        assert!(
+            (bearing - expected.0).abs() < 0.01 && (distance - expected.1).abs() < 0.01,
+            "Expected {:?}, got {:?}",
+            expected,
+            (bearing, distance)
+        );
+    };
+    // A degree is 60 nautical miles along the equator and the meridians.
+    assert_close(great_circle((0.0, 0.0), (0.0, 1.0)), (90.0, 60.0));
+    assert_close(rhumb_line((0.0, 0.0), (0.0, -1.0)), (270.0, 60.0));
+    assert_close(great_circle((10.0, 5.0), (9.0, 5.0)), (180.0, 60.0));
+    assert_close(rhumb_line((10.0, 5.0), (11.0, 5.0)), (0.0, 60.0));
+
+    // Along a parallel the rhumb line keeps east, which is longer than the great circle.
+    let (bearing, rhumb_distance) = rhumb_line((60.0, 0.0), (60.0, 10.0));
+    assert_close((bearing, rhumb_distance), (90.0, 300.0));
+    let (bearing, great_circle_distance) = great_circle((60.0, 0.0), (60.0, 10.0));
+    assert!(bearing < 90.0 && great_circle_distance < rhumb_distance);
+
+    // The rhumb line crosses the antimeridian.
+    assert_close(rhumb_line((0.0, 179.5), (0.0, -179.5)), (90.0, 60.0));
+
+    // Both agree for short distances.
+    let (a, b) = ((47.3769, 8.5417), (47.2, 8.7));
+    let (great_circle_bearing, great_circle_distance) = great_circle(a, b);
+    let (rhumb_bearing, rhumb_distance) = rhumb_line(a, b);
+    assert!((great_circle_bearing - rhumb_bearing).abs() < 0.1);
+    assert!((great_circle_distance - rhumb_distance).abs() < 0.01);
+}
+
+#[test]
+fn global_space_round_trip() {
+    let zurich = num_to_global_space(&deg2num(47.3769, 8.5417, 8));
+    let (lat, lon) = global_space_to_deg(&zurich);
+    assert!((lat - 47.3769).abs() < 1e-3);
+    assert!((lon - 8.5417).abs() < 1e-3);
+}
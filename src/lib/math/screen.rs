@@ -5,6 +5,8 @@ pub struct Screen {
     pub center: Point,
     pub width: u32,
     pub height: u32,
+    /// The bearing in degrees which points up on the screen, 0 is north up.
+    pub rotation: f32,
     tile_size: u32,
 }
 
@@ -14,6 +16,7 @@ impl Screen {
             center,
             width,
             height,
+            rotation: 0.0,
             tile_size: (tile_size as f64 * hidpi_factor) as u32,
         }
     }
@@ -35,10 +38,16 @@ impl Screen {
             / 2f32.powi(z as i32)
             / scale as f32;
 
-        let top_left: TileId =
-            global_to_num_space(&(self.center - vector(px_to_world, py_to_world)), z as u32).into();
-        let bottom_right: TileId =
-            global_to_num_space(&(self.center + vector(px_to_world, py_to_world)), z as u32).into();
+        // A rotated screen covers the bounding box of its corners.
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        let (sin, cos) = (sin.abs(), cos.abs());
+        let extent = vector(
+            cos * px_to_world + sin * py_to_world,
+            sin * px_to_world + cos * py_to_world,
+        );
+
+        let top_left: TileId = global_to_num_space(&(self.center - extent), z as u32).into();
+        let bottom_right: TileId = global_to_num_space(&(self.center + extent), z as u32).into();
         TileField::new(top_left, bottom_right)
     }
 
@@ -50,10 +59,99 @@ impl Screen {
     }
 
     pub fn global_to_screen(&self, z: f32) -> glm::TMat4<f32> {
-        let zoom_x = 2.0f32.powf(z) / (self.width as f32 / 2.0) * self.get_tile_size() as f32;
-        let zoom_y = 2.0f32.powf(z) / (self.height as f32 / 2.0) * self.get_tile_size() as f32;
-        let zoom = glm::scaling(&glm::vec3(zoom_x, zoom_y, 1.0));
+        let to_screen = glm::scaling(&glm::vec3(
+            2.0 / self.width as f32,
+            2.0 / self.height as f32,
+            1.0,
+        ));
+        // Turns the bearing of the rotation to the top of the screen.
+        let rotation = glm::rotation(-self.rotation.to_radians(), &glm::vec3(0.0, 0.0, 1.0));
+        let zoom = 2.0f32.powf(z) * self.get_tile_size() as f32;
+        let zoom = glm::scaling(&glm::vec3(zoom, zoom, 1.0));
         let position = glm::translation(&glm::vec3(-self.center.x, -self.center.y, 0.0));
-        zoom * position
+        to_screen * rotation * zoom * position
+    }
+
+    /// Returns the pixel a point in global space is drawn at, with the origin at the top left.
+    pub fn global_to_pixel(&self, z: f32, point: &Point) -> Point {
+        let position = self.global_to_screen(z) * glm::vec4(point.x, point.y, 0.0, 1.0);
+        super::point(
+            (position.x + 1.0) * self.width as f32 / 2.0,
+            (position.y + 1.0) * self.height as f32 / 2.0,
+        )
+    }
+
+    /// Returns the point in global space drawn at a pixel, with the origin at the top left.
+    pub fn pixel_to_global(&self, z: f32, pixel: &Point) -> Point {
+        let offset = *pixel - super::point(self.width as f32 / 2.0, self.height as f32 / 2.0);
+        self.center + self.pixels_to_global(z, offset)
+    }
+
+    /// Converts a movement on the screen in pixels into a movement in global space.
+    pub fn pixels_to_global(&self, z: f32, delta: Vector) -> Vector {
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        let rotated = vector(cos * delta.x - sin * delta.y, sin * delta.x + cos * delta.y);
+        rotated / (2.0f32.powf(z) * self.get_tile_size() as f32)
+    }
+
+    /// Converts a length in meters at a point in global space into pixels.
+    pub fn meters_to_pixels(&self, z: f32, point: &Point, meters: f32) -> f32 {
+        let (lat, _) = global_space_to_deg(point);
+        meters_to_global(meters, lat) * 2.0f32.powf(z) * self.get_tile_size() as f32
     }
 }
+
+#[test]
+fn global_to_pixel() {
+    let mut screen = Screen::new(point(0.5, 0.5), 800, 600, 256, 1.0);
+    let z = 2.0;
+    let pixel = |screen: &Screen, x: f32, y: f32| {
+        let pixel = screen.global_to_pixel(z, &point(x, y));
+        (pixel.x.round(), pixel.y.round())
+    };
+    assert_eq!(pixel(&screen, 0.5, 0.5), (400.0, 300.0));
+    // A tile at zoom level 2 is 256 pixels wide.
+    assert_eq!(pixel(&screen, 0.75, 0.5), (656.0, 300.0));
+    assert_eq!(pixel(&screen, 0.5, 0.25), (400.0, 44.0));
+
+    // With the course east up, east is at the top and north on the left.
+    screen.rotation = 90.0;
+    assert_eq!(pixel(&screen, 0.5, 0.5), (400.0, 300.0));
+    assert_eq!(pixel(&screen, 0.75, 0.5), (400.0, 44.0));
+    assert_eq!(pixel(&screen, 0.5, 0.25), (144.0, 300.0));
+}
+
+#[test]
+fn pixel_to_global() {
+    let mut screen = Screen::new(point(0.3, 0.6), 800, 600, 256, 1.0);
+    screen.rotation = 30.0;
+    let global = point(0.31, 0.59);
+    let pixel = screen.global_to_pixel(10.0, &global);
+    assert!((screen.pixel_to_global(10.0, &pixel) - global).length() < 1e-6);
+}
+
+#[test]
+fn drag_rotated_screen() {
+    let mut screen = Screen::new(point(0.5, 0.5), 800, 600, 256, 1.0);
+    screen.rotation = 90.0;
+    let z = 2.0;
+    let delta = screen.pixels_to_global(z, vector(0.0, -256.0));
+    assert!((delta - vector(0.25, 0.0)).length() < 1e-6);
+
+    // Moving the center by the dragged distance keeps the point under the cursor.
+    let target = screen.center + delta;
+    let before = screen.global_to_pixel(z, &target);
+    screen.center -= screen.pixels_to_global(z, vector(10.0, 20.0));
+    let after = screen.global_to_pixel(z, &target);
+    assert!((after - before - vector(10.0, 20.0)).length() < 1e-3);
+}
+
+#[test]
+fn rotated_tile_boundaries() {
+    let mut screen = Screen::new(point(0.5, 0.5), 1024, 256, 256, 1.0);
+    let north_up = screen.get_tile_boundaries_for_zoom_level(4.0, 1);
+    screen.rotation = 45.0;
+    let rotated = screen.get_tile_boundaries_for_zoom_level(4.0, 1);
+    assert!(rotated.topleft.y < north_up.topleft.y);
+    assert!(rotated.bottomright.y > north_up.bottomright.y);
+}
@@ -0,0 +1,46 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Computes the elevation of the sun above the horizon in degrees.
+///
+/// Uses the low precision formulas of the Astronomical Almanac, which are accurate to about 0.01°.
+pub fn sun_elevation(lat_deg: f32, lon_deg: f32, time: SystemTime) -> f32 {
+    let seconds = match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs_f64(),
+        Err(err) => -err.duration().as_secs_f64(),
+    };
+    // Days since noon of the 1st January 2000.
+    let days = seconds / 86400.0 - 10957.5;
+
+    let mean_anomaly = (357.529 + 0.985_600_28 * days).to_radians();
+    let mean_longitude = 280.459 + 0.985_647_36 * days;
+    let ecliptic_longitude =
+        (mean_longitude + 1.915 * mean_anomaly.sin() + 0.020 * (2.0 * mean_anomaly).sin())
+            .to_radians();
+    let obliquity = (23.439 - 0.000_000_36 * days).to_radians();
+
+    let right_ascension =
+        (obliquity.cos() * ecliptic_longitude.sin()).atan2(ecliptic_longitude.cos());
+    let declination = (obliquity.sin() * ecliptic_longitude.sin()).asin();
+
+    // The sidereal time at Greenwich in degrees.
+    let sidereal_time = 280.460_618_37 + 360.985_647_366_29 * days;
+    let hour_angle = (sidereal_time + lon_deg as f64).to_radians() - right_ascension;
+
+    let lat = (lat_deg as f64).to_radians();
+    let elevation =
+        (lat.sin() * declination.sin() + lat.cos() * declination.cos() * hour_angle.cos()).asin();
+    elevation.to_degrees() as f32
+}
+
+#[test]
+fn compute_sun_elevation() {
+    use std::time::Duration;
+    let at = |seconds| UNIX_EPOCH + Duration::from_secs(seconds);
+
+    // 2021-06-21 11:26 UTC is about noon in Zurich, 90° - 47.4° + 23.4°.
+    assert!((sun_elevation(47.4, 8.5, at(1_624_274_760)) - 66.0).abs() < 0.5);
+    // At midnight the sun is far below the horizon.
+    assert!(sun_elevation(47.4, 8.5, at(1_624_233_600)) < -15.0);
+    // 2021-03-20 12:07 UTC, the sun is straight above the equator and the prime meridian at the equinox.
+    assert!(sun_elevation(0.0, 0.0, at(1_616_242_020)) > 89.0);
+}
@@ -24,6 +24,25 @@ impl TileField {
         }
     }
 
+    /// Creates the field of all tiles at zoom level `z` which intersect the given lat/lon bounds.
+    pub fn from_bounds(min_lat: f32, min_lon: f32, max_lat: f32, max_lon: f32, z: u32) -> Self {
+        // Web mercator does not reach the poles.
+        const MAX_LAT: f32 = 85.051_13;
+        let max_index = 2u32.pow(z) - 1;
+        let clamp = |c: TileCoordinate| {
+            TileId::new(
+                z,
+                (c.x.max(0.0) as u32).min(max_index),
+                (c.y.max(0.0) as u32).min(max_index),
+            )
+        };
+
+        // Tile rows grow towards the south, so the north-west corner is the top left tile.
+        let topleft = clamp(deg2num(max_lat.min(MAX_LAT), min_lon, z));
+        let bottomright = clamp(deg2num(min_lat.max(-MAX_LAT), max_lon, z));
+        Self::new(topleft, bottomright)
+    }
+
     pub fn iter(&self) -> TileIterator {
         TileIterator {
             tile_field: self,
@@ -80,3 +99,14 @@ fn get_tile_boundaries_for_8_zoom() {
 
     assert_eq!(tile_field.iter().count(), 20);
 }
+
+#[test]
+fn tile_field_from_bounds() {
+    // Lake Zurich.
+    let tile_field = TileField::from_bounds(47.15, 8.5, 47.38, 8.85, 8);
+    assert_eq!(tile_field.topleft, TileId::new(8, 134, 89));
+    assert_eq!(tile_field.bottomright, TileId::new(8, 134, 89));
+
+    let world = TileField::from_bounds(-90.0, -180.0, 90.0, 180.0, 2);
+    assert_eq!(world.iter().count(), 16);
+}
@@ -0,0 +1,333 @@
+use super::*;
+use std::collections::HashMap;
+
+/// The kind of station which sent an AIS message.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AisClass {
+    /// A commercial vessel with a class A transponder.
+    A,
+    /// A leisure or small vessel with a class B transponder.
+    B,
+    /// A buoy, beacon or light.
+    AidToNavigation,
+}
+
+/// The data of a decoded AIS message.
+///
+/// Every message type carries only some of the fields, the others are `None`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AisReport {
+    pub message_type: u8,
+    pub mmsi: u32,
+    pub class: AisClass,
+    pub position: Option<LatLon>,
+    /// The course over ground in degrees relative to true north.
+    pub course: Option<f32>,
+    /// The speed over ground in knots.
+    pub speed: Option<f32>,
+    /// The true heading in degrees.
+    pub heading: Option<f32>,
+    /// The navigational status of class A vessels, e.g. 0 under way using engine or 5 moored.
+    pub status: Option<u8>,
+    pub name: Option<String>,
+    pub callsign: Option<String>,
+    /// The ship type of vessels or the type of aids to navigation.
+    pub ship_type: Option<u8>,
+    /// The length and the width in meters.
+    pub dimensions: Option<(u16, u16)>,
+    pub destination: Option<String>,
+}
+
+impl AisReport {
+    fn new(message_type: u8, mmsi: u32, class: AisClass) -> Self {
+        Self {
+            message_type,
+            mmsi,
+            class,
+            position: None,
+            course: None,
+            speed: None,
+            heading: None,
+            status: None,
+            name: None,
+            callsign: None,
+            ship_type: None,
+            dimensions: None,
+            destination: None,
+        }
+    }
+}
+
+/// Reassembles AIS messages from their fragments and decodes them.
+#[derive(Debug, Default)]
+pub struct AisDecoder {
+    /// The payloads of the fragments received so far by the source and the id of their message.
+    pending: HashMap<(bool, Option<u8>), Vec<String>>,
+}
+
+impl AisDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes a fragment and decodes its message once all fragments have been received.
+    ///
+    /// Returns `None` while fragments are missing. Fragments which arrive out of order drop the message.
+    pub fn decode(&mut self, vdm: &Vdm) -> Option<Result<AisReport, NmeaError>> {
+        if vdm.fragments <= 1 {
+            return Some(decode_payload(&vdm.payload, vdm.fill_bits));
+        }
+
+        let key = (vdm.own, vdm.message_id);
+        let fragments = self.pending.entry(key).or_default();
+        if vdm.fragment as usize != fragments.len() + 1 {
+            fragments.clear();
+            if vdm.fragment != 1 {
+                return None;
+            }
+        }
+        fragments.push(vdm.payload.clone());
+        if vdm.fragment < vdm.fragments {
+            return None;
+        }
+        let payload = self.pending.remove(&key)?.concat();
+        Some(decode_payload(&payload, vdm.fill_bits))
+    }
+}
+
+/// Decodes the payload of a complete AIS message.
+pub fn decode_payload(payload: &str, fill_bits: u8) -> Result<AisReport, NmeaError> {
+    let bits = Bits::unarmor(payload, fill_bits)?;
+    let message_type = bits.unsigned(0, 6)? as u8;
+    let mmsi = bits.unsigned(8, 30)?;
+    match message_type {
+        1..=3 => {
+            let mut report = AisReport::new(message_type, mmsi, AisClass::A);
+            report.status = Some(bits.unsigned(38, 4)? as u8).filter(|status| *status != 15);
+            report.speed = bits.speed(50)?;
+            report.position = bits.position(61)?;
+            report.course = bits.course(116)?;
+            report.heading = bits.heading(128)?;
+            Ok(report)
+        }
+        5 => {
+            let mut report = AisReport::new(message_type, mmsi, AisClass::A);
+            report.callsign = bits.text(70, 7);
+            report.name = bits.text(112, 20);
+            report.ship_type = bits.ship_type(232);
+            report.dimensions = bits.dimensions(240);
+            report.destination = bits.text(302, 20);
+            Ok(report)
+        }
+        18 | 19 => {
+            let mut report = AisReport::new(message_type, mmsi, AisClass::B);
+            report.speed = bits.speed(46)?;
+            report.position = bits.position(57)?;
+            report.course = bits.course(112)?;
+            report.heading = bits.heading(124)?;
+            if message_type == 19 {
+                report.name = bits.text(143, 20);
+                report.ship_type = bits.ship_type(263);
+                report.dimensions = bits.dimensions(271);
+            }
+            Ok(report)
+        }
+        21 => {
+            let mut report = AisReport::new(message_type, mmsi, AisClass::AidToNavigation);
+            report.ship_type = Some(bits.unsigned(38, 5)? as u8);
+            // Long names continue after the fixed fields.
+            report.name = match (
+                bits.text(43, 20),
+                bits.text(272, bits.len().saturating_sub(272) / 6),
+            ) {
+                (Some(name), Some(extension)) => Some(name + &extension),
+                (name, _) => name,
+            };
+            report.position = bits.position(164)?;
+            report.dimensions = bits.dimensions(219);
+            Ok(report)
+        }
+        24 => {
+            let mut report = AisReport::new(message_type, mmsi, AisClass::B);
+            if bits.unsigned(38, 2)? == 0 {
+                report.name = bits.text(40, 20);
+            } else {
+                report.ship_type = bits.ship_type(40);
+                report.callsign = bits.text(90, 7);
+                report.dimensions = bits.dimensions(132);
+            }
+            Ok(report)
+        }
+        message_type => Err(NmeaError::UnsupportedMessage(message_type)),
+    }
+}
+
+/// The bits of an AIS payload.
+struct Bits(Vec<bool>);
+
+impl Bits {
+    /// Unpacks the 6-bit characters of a payload.
+    fn unarmor(payload: &str, fill_bits: u8) -> Result<Self, NmeaError> {
+        let mut bits = Vec::with_capacity(payload.len() * 6);
+        for byte in payload.bytes() {
+            let value = match byte {
+                b'0'..=b'W' => byte - b'0',
+                b'`'..=b'w' => byte - b'0' - 8,
+                _ => return Err(NmeaError::BadPayload),
+            };
+            bits.extend((0..6).rev().map(|bit| value >> bit & 1 == 1));
+        }
+        bits.truncate(bits.len().saturating_sub(fill_bits as usize));
+        Ok(Self(bits))
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn unsigned(&self, start: usize, length: usize) -> Result<u32, NmeaError> {
+        let bits = self
+            .0
+            .get(start..start + length)
+            .ok_or(NmeaError::BadPayload)?;
+        Ok(bits
+            .iter()
+            .fold(0, |value, bit| value << 1 | u32::from(*bit)))
+    }
+
+    fn signed(&self, start: usize, length: usize) -> Result<i32, NmeaError> {
+        let value = self.unsigned(start, length)?;
+        Ok(((value << (32 - length)) as i32) >> (32 - length))
+    }
+
+    /// Reads the speed in tenths of knots.
+    fn speed(&self, start: usize) -> Result<Option<f32>, NmeaError> {
+        let speed = self.unsigned(start, 10)?;
+        Ok(Some(speed as f32 / 10.0).filter(|_| speed != 1023))
+    }
+
+    /// Reads a longitude and a latitude in ten thousandths of minutes.
+    fn position(&self, start: usize) -> Result<Option<LatLon>, NmeaError> {
+        let lon = self.signed(start, 28)? as f32 / 600_000.0;
+        let lat = self.signed(start + 28, 27)? as f32 / 600_000.0;
+        // 181° and 91° mark an unknown position.
+        Ok(Some((lat, lon)).filter(|_| lon.abs() <= 180.0 && lat.abs() <= 90.0))
+    }
+
+    /// Reads the course in tenths of degrees.
+    fn course(&self, start: usize) -> Result<Option<f32>, NmeaError> {
+        let course = self.unsigned(start, 12)?;
+        Ok(Some(course as f32 / 10.0).filter(|_| course < 3600))
+    }
+
+    fn heading(&self, start: usize) -> Result<Option<f32>, NmeaError> {
+        let heading = self.unsigned(start, 9)?;
+        Ok(Some(heading as f32).filter(|_| heading < 360))
+    }
+
+    fn ship_type(&self, start: usize) -> Option<u8> {
+        self.unsigned(start, 8)
+            .ok()
+            .map(|ship_type| ship_type as u8)
+            .filter(|ship_type| *ship_type != 0)
+    }
+
+    /// Reads the distances from the reference point to the bow, stern, port and starboard side.
+    fn dimensions(&self, start: usize) -> Option<(u16, u16)> {
+        let length = self.unsigned(start, 9).ok()? + self.unsigned(start + 9, 9).ok()?;
+        let width = self.unsigned(start + 18, 6).ok()? + self.unsigned(start + 24, 6).ok()?;
+        Some((length as u16, width as u16)).filter(|_| length > 0 && width > 0)
+    }
+
+    /// Reads up to `length` 6-bit characters, without the padding at the end.
+    ///
+    /// Returns `None` if the text is empty or the payload is too short.
+    fn text(&self, start: usize, length: usize) -> Option<String> {
+        let text = (0..length)
+            .map(|i| self.unsigned(start + i * 6, 6).ok())
+            .take_while(Option::is_some)
+            .flatten()
+            .map(|value| {
+                if value < 32 {
+                    (value as u8 + b'@') as char
+                } else {
+                    value as u8 as char
+                }
+            })
+            .take_while(|c| *c != '@')
+            .collect::<String>();
+        let text = text.trim_end();
+        Some(text.to_string()).filter(|_| !text.is_empty())
+    }
+}
+
+#[cfg(test)]
+fn decode_test_sentences(lines: &[&str]) -> Vec<AisReport> {
+    let mut decoder = AisDecoder::new();
+    lines
+        .iter()
+        .filter_map(|line| match parse_sentence(line).unwrap() {
+            Sentence::Vdm(vdm) => decoder.decode(&vdm),
+            sentence => panic!("Unexpected sentence {:?}", sentence),
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap()
+}
+
+#[test]
+fn decode_position_reports() {
+    let reports = decode_test_sentences(&["!AIVDM,1,1,,B,177KQJ5000G?tO`K>RA1wUbN0TKH,0*5C"]);
+    let report = &reports[0];
+    assert_eq!((report.message_type, report.mmsi), (1, 477_553_000));
+    assert_eq!(report.class, AisClass::A);
+    assert_eq!(report.status, Some(5));
+    assert_eq!(report.speed, Some(0.0));
+    let (lat, lon) = report.position.unwrap();
+    assert!((lat - 47.582_832).abs() < 1e-4 && (lon + 122.345_83).abs() < 1e-4);
+    assert_eq!((report.course, report.heading), (Some(51.0), Some(181.0)));
+}
+
+#[test]
+fn decode_static_data() {
+    let reports = decode_test_sentences(&[
+        "!AIVDM,2,1,1,A,55?MbV02;H;s<HtKR20EHE:0@T4@Dn2222222216L961O5Gf0NSQEp6ClRp8,0*1C",
+        "!AIVDM,2,2,1,A,88888888880,2*25",
+    ]);
+    assert_eq!(reports.len(), 1);
+    let report = &reports[0];
+    assert_eq!((report.message_type, report.mmsi), (5, 351_759_000));
+    assert_eq!(report.name.as_deref(), Some("EVER DIADEM"));
+    assert_eq!(report.callsign.as_deref(), Some("3FOF8"));
+    assert_eq!(report.destination.as_deref(), Some("NEW YORK"));
+    assert_eq!(report.ship_type, Some(70));
+    assert_eq!(report.dimensions, Some((295, 32)));
+    assert_eq!(report.position, None);
+}
+
+#[test]
+fn drop_incomplete_messages() {
+    let mut decoder = AisDecoder::new();
+    let fragment = |line: &str| match parse_sentence(line).unwrap() {
+        Sentence::Vdm(vdm) => vdm,
+        sentence => panic!("Unexpected sentence {:?}", sentence),
+    };
+    // The second fragment without the first one can not be decoded.
+    assert_eq!(
+        decoder.decode(&fragment("!AIVDM,2,2,1,A,88888888880,2*25")),
+        None
+    );
+    assert_eq!(
+        decode_payload("177KQJ5000G?tO`K>RA1wUbN0TKH!", 0),
+        Err(NmeaError::BadPayload)
+    );
+    assert_eq!(decode_payload("177KQJ", 0), Err(NmeaError::BadPayload));
+}
+
+#[test]
+fn decode_short_aid_to_navigation() {
+    // Too short for a position, but must not underflow the length of the name extension.
+    assert_eq!(
+        decode_payload("E>kb9O9aS@7PUh10dh19@;0Tah2c", 0),
+        Err(NmeaError::BadPayload)
+    );
+}
@@ -0,0 +1,242 @@
+use super::*;
+use crate::*;
+use lyon::math::Point;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// A vessel or aid to navigation with everything received about it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AisTarget {
+    pub mmsi: u32,
+    pub class: AisClass,
+    pub position: Option<LatLon>,
+    /// The course over ground in degrees relative to true north.
+    pub course: Option<f32>,
+    /// The speed over ground in knots.
+    pub speed: Option<f32>,
+    /// The true heading in degrees.
+    pub heading: Option<f32>,
+    pub status: Option<u8>,
+    pub name: Option<String>,
+    pub callsign: Option<String>,
+    pub ship_type: Option<u8>,
+    /// The length and the width in meters.
+    pub dimensions: Option<(u16, u16)>,
+    pub destination: Option<String>,
+    /// When the last message of the target was received.
+    pub last_seen: Instant,
+}
+
+impl AisTarget {
+    fn new(mmsi: u32, class: AisClass, now: Instant) -> Self {
+        Self {
+            mmsi,
+            class,
+            position: None,
+            course: None,
+            speed: None,
+            heading: None,
+            status: None,
+            name: None,
+            callsign: None,
+            ship_type: None,
+            dimensions: None,
+            destination: None,
+            last_seen: now,
+        }
+    }
+
+    /// Takes the fields of a report over, keeping the ones it does not carry.
+    ///
+    /// The navigation data of position reports is replaced as a whole, so unknown values do not linger.
+    fn update(&mut self, report: &AisReport, now: Instant) {
+        self.class = report.class;
+        if report.position.is_some() {
+            self.position = report.position;
+            self.course = report.course;
+            self.speed = report.speed;
+            self.heading = report.heading;
+            self.status = report.status.or(self.status);
+        }
+        self.name = report.name.clone().or_else(|| self.name.take());
+        self.callsign = report.callsign.clone().or_else(|| self.callsign.take());
+        self.ship_type = report.ship_type.or(self.ship_type);
+        self.dimensions = report.dimensions.or(self.dimensions);
+        self.destination = report
+            .destination
+            .clone()
+            .or_else(|| self.destination.take());
+        self.last_seen = now;
+    }
+
+    /// Returns the name of the target, or its MMSI if the name was not received yet.
+    pub fn display_name(&self) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| format!("MMSI {}", self.mmsi))
+    }
+
+    /// Returns the position in global space.
+    pub fn global_position(&self) -> Option<Point> {
+        let (lat, lon) = self.position?;
+        Some(num_to_global_space(&deg2num(lat, lon, 0)))
+    }
+}
+
+/// The AIS targets received recently, by their MMSI.
+pub struct AisTargetTable {
+    targets: BTreeMap<u32, AisTarget>,
+    max_age: Duration,
+}
+
+impl AisTargetTable {
+    /// Creates a table which forgets targets that were not received for `max_age`.
+    pub fn new(max_age: Duration) -> Self {
+        Self {
+            targets: BTreeMap::new(),
+            max_age,
+        }
+    }
+
+    /// Adds or updates the target of a report.
+    pub fn update(&mut self, report: &AisReport) {
+        self.update_at(report, Instant::now());
+    }
+
+    fn update_at(&mut self, report: &AisReport, now: Instant) {
+        self.targets
+            .entry(report.mmsi)
+            .or_insert_with(|| AisTarget::new(report.mmsi, report.class, now))
+            .update(report, now);
+    }
+
+    /// Removes the targets which were not received for longer than the maximum age.
+    pub fn expire(&mut self) {
+        self.expire_at(Instant::now());
+    }
+
+    fn expire_at(&mut self, now: Instant) {
+        let max_age = self.max_age;
+        self.targets
+            .retain(|_, target| now.saturating_duration_since(target.last_seen) <= max_age);
+    }
+
+    pub fn get(&self, mmsi: u32) -> Option<&AisTarget> {
+        self.targets.get(&mmsi)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &AisTarget> {
+        self.targets.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.targets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.targets.is_empty()
+    }
+
+    /// Returns the MMSI of the target closest to a pixel on the screen within `radius` pixels.
+    pub fn target_at(&self, screen: &Screen, zoom: f32, pixel: Point, radius: f32) -> Option<u32> {
+        self.iter()
+            .filter_map(|target| {
+                let position = screen.global_to_pixel(zoom, &target.global_position()?);
+                Some((target.mmsi, (position - pixel).length()))
+            })
+            .filter(|(_, distance)| *distance <= radius)
+            .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+            .map(|(mmsi, _)| mmsi)
+    }
+}
+
+#[test]
+fn collect_recorded_targets() {
+    let source = NmeaSource::File {
+        path: "data/ais.nmea".into(),
+        interval: Duration::from_millis(0),
+    };
+    let reader = NmeaReader::start(&source).unwrap();
+    let mut decoder = AisDecoder::new();
+    let mut table = AisTargetTable::new(Duration::from_secs(360));
+    while let Some(sentence) = reader.recv_timeout(Duration::from_secs(5)) {
+        if let Sentence::Vdm(vdm) = sentence {
+            if let Some(Ok(report)) = decoder.decode(&vdm) {
+                if !vdm.own {
+                    table.update(&report);
+                }
+            }
+        }
+    }
+
+    // The sentence with the broken checksum and the own vessel are left out.
+    let mmsis = table.iter().map(|target| target.mmsi).collect::<Vec<_>>();
+    assert_eq!(
+        mmsis,
+        vec![
+            271_041_815,
+            351_759_000,
+            367_059_850,
+            423_302_100,
+            477_553_000,
+            993_692_028
+        ]
+    );
+
+    // The static data and the position report of a vessel are merged.
+    let ever_diadem = table.get(351_759_000).unwrap();
+    assert_eq!(ever_diadem.display_name(), "EVER DIADEM");
+    assert_eq!(ever_diadem.destination.as_deref(), Some("NEW YORK"));
+    assert_eq!(
+        (ever_diadem.course, ever_diadem.speed, ever_diadem.heading),
+        (Some(90.5), Some(12.3), Some(92.0))
+    );
+    let (lat, lon) = ever_diadem.position.unwrap();
+    assert!((lat - 40.6012).abs() < 1e-4 && (lon + 74.0421).abs() < 1e-4);
+
+    // Both parts of a class B static data report are merged.
+    let proguy = table.get(271_041_815).unwrap();
+    assert_eq!(proguy.name.as_deref(), Some("PROGUY"));
+    assert_eq!(proguy.callsign.as_deref(), Some("TC6163"));
+    assert_eq!(proguy.dimensions, Some((15, 5)));
+
+    let aid = table.get(993_692_028).unwrap();
+    assert_eq!(aid.class, AisClass::AidToNavigation);
+    assert_eq!(aid.name.as_deref(), Some("SF OAK BAY BR VAIS E"));
+
+    let screen = Screen::new(ever_diadem.global_position().unwrap(), 800, 600, 256, 1.0);
+    assert_eq!(
+        table.target_at(&screen, 12.0, lyon::math::point(405.0, 297.0), 10.0),
+        Some(351_759_000)
+    );
+    assert_eq!(
+        table.target_at(&screen, 12.0, lyon::math::point(100.0, 100.0), 10.0),
+        None
+    );
+}
+
+#[test]
+fn expire_stale_targets() {
+    let report = |mmsi| {
+        let payload = "177KQJ5000G?tO`K>RA1wUbN0TKH";
+        AisReport {
+            mmsi,
+            ..decode_payload(payload, 0).unwrap()
+        }
+    };
+    let mut table = AisTargetTable::new(Duration::from_secs(60));
+    let start = Instant::now();
+    table.update_at(&report(1), start);
+    table.update_at(&report(2), start + Duration::from_secs(30));
+
+    table.expire_at(start + Duration::from_secs(60));
+    assert_eq!(table.len(), 2);
+    table.expire_at(start + Duration::from_secs(61));
+    assert_eq!(
+        table.iter().map(|target| target.mmsi).collect::<Vec<_>>(),
+        vec![2]
+    );
+    table.update_at(&report(2), start + Duration::from_secs(90));
+    table.expire_at(start + Duration::from_secs(150));
+    assert_eq!(table.len(), 1);
+}
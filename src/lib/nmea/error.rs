@@ -0,0 +1,45 @@
+/// The reasons a NMEA 0183 sentence can not be used.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NmeaError {
+    /// The line does not start with `$` or `!` followed by an address.
+    Malformed,
+    /// The checksum at the end of the sentence does not match the one of its contents.
+    Checksum { expected: u8, actual: u8 },
+    /// The sentence type is not supported.
+    Unsupported(String),
+    /// A field of the sentence can not be parsed, the first field after the address has the index 1.
+    BadField { sentence: String, index: usize },
+    /// The payload of an AIS message contains invalid characters or is too short for its type.
+    BadPayload,
+    /// The AIS message type is not supported.
+    UnsupportedMessage(u8),
+}
+
+impl std::fmt::Display for NmeaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            NmeaError::Malformed => write!(f, "The line is not a NMEA sentence."),
+            NmeaError::Checksum { expected, actual } => write!(
+                f,
+                "The sentence ends with the checksum {:02X}, but its checksum is {:02X}.",
+                expected, actual
+            ),
+            NmeaError::Unsupported(sentence) => {
+                write!(f, "The sentence type '{}' is not supported.", sentence)
+            }
+            NmeaError::BadField { sentence, index } => {
+                write!(
+                    f,
+                    "Field {} of the {} sentence is invalid.",
+                    index, sentence
+                )
+            }
+            NmeaError::BadPayload => write!(f, "The AIS payload is invalid."),
+            NmeaError::UnsupportedMessage(message_type) => {
+                write!(f, "The AIS message type {} is not supported.", message_type)
+            }
+        }
+    }
+}
+
+impl std::error::Error for NmeaError {}
@@ -0,0 +1,13 @@
+mod ais;
+mod ais_targets;
+mod error;
+mod own_ship;
+mod reader;
+mod sentence;
+
+pub use ais::*;
+pub use ais_targets::*;
+pub use error::*;
+pub use own_ship::*;
+pub use reader::*;
+pub use sentence::*;
@@ -0,0 +1,124 @@
+use super::*;
+use crate::*;
+use lyon::math::Point;
+use std::time::{Duration, Instant};
+
+/// A fix older than this is considered lost.
+const FIX_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The navigation data of the own ship collected from NMEA sentences.
+#[derive(Debug, Clone, Default)]
+pub struct OwnShip {
+    pub position: Option<LatLon>,
+    /// The course over ground in degrees relative to true north.
+    pub course: Option<f32>,
+    /// The speed over ground in knots.
+    pub speed: Option<f32>,
+    /// The true heading in degrees.
+    pub heading: Option<f32>,
+    /// The horizontal dilution of precision of the last fix.
+    pub hdop: Option<f32>,
+    pub satellites: Option<u8>,
+    /// The magnetic variation in degrees, positive to the east.
+    pub magnetic_variation: Option<f32>,
+    /// When the last valid position was received.
+    pub last_fix: Option<Instant>,
+}
+
+impl OwnShip {
+    /// Takes the data of a sentence over.
+    ///
+    /// Positions of sentences which are marked invalid are ignored.
+    pub fn update(&mut self, sentence: &Sentence) {
+        match sentence {
+            Sentence::Rmc(rmc) if rmc.valid => {
+                self.set_position(rmc.position);
+                self.speed = rmc.speed.or(self.speed);
+                self.course = rmc.course.or(self.course);
+                self.magnetic_variation = rmc.magnetic_variation.or(self.magnetic_variation);
+            }
+            Sentence::Gga(gga) if gga.quality > 0 => {
+                self.set_position(gga.position);
+                self.hdop = gga.hdop;
+                self.satellites = gga.satellites;
+            }
+            Sentence::Gll(gll) if gll.valid => self.set_position(gll.position),
+            Sentence::Vtg(vtg) => {
+                self.course = vtg.course.or(self.course);
+                self.speed = vtg.speed.or(self.speed);
+            }
+            Sentence::Hdg(hdg) => {
+                self.heading = hdg.true_heading(self.magnetic_variation).or(self.heading)
+            }
+            Sentence::Hdt(hdt) => self.heading = hdt.heading.or(self.heading),
+            _ => {}
+        }
+    }
+
+    fn set_position(&mut self, position: Option<LatLon>) {
+        if let Some(position) = position {
+            self.position = Some(position);
+            self.last_fix = Some(Instant::now());
+        }
+    }
+
+    /// Returns whether a position was received recently.
+    pub fn has_fix(&self) -> bool {
+        self.fix_age().map(|age| age < FIX_TIMEOUT).unwrap_or(false)
+    }
+
+    /// Returns the time since the last valid position was received.
+    pub fn fix_age(&self) -> Option<Duration> {
+        self.last_fix.map(|last_fix| last_fix.elapsed())
+    }
+
+    /// Returns the position in global space.
+    pub fn global_position(&self) -> Option<Point> {
+        let (lat, lon) = self.position?;
+        Some(num_to_global_space(&deg2num(lat, lon, 0)))
+    }
+}
+
+/// Formats a position in degrees and decimal minutes, e.g. `47°22.614'N 008°32.502'E`.
+pub fn format_position((lat, lon): LatLon) -> String {
+    let format = |angle: f32, width: usize, positive: char, negative: char| {
+        let direction = if angle < 0.0 { negative } else { positive };
+        let minutes = (angle.abs() * 60.0 * 1000.0).round() as u32;
+        format!(
+            "{:0width$}°{:06.3}'{}",
+            minutes / 60000,
+            (minutes % 60000) as f32 / 1000.0,
+            direction,
+            width = width
+        )
+    };
+    format!("{} {}", format(lat, 2, 'N', 'S'), format(lon, 3, 'E', 'W'))
+}
+
+#[test]
+fn update_own_ship() {
+    let mut own_ship = OwnShip::default();
+    for line in &[
+        "$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6A",
+        "$GPVTG,054.7,T,034.4,M,005.5,N,010.2,K*48",
+        "$HCHDG,101.1,,,,*43",
+        "$GPRMC,,V,,,,,,,,,*31",
+    ] {
+        own_ship.update(&parse_sentence(line).unwrap());
+    }
+
+    assert!(own_ship.has_fix());
+    assert_eq!(own_ship.position, Some((48.1173, 11.516666)));
+    assert_eq!((own_ship.course, own_ship.speed), (Some(54.7), Some(5.5)));
+    // The compass heading is corrected by the variation of the RMC sentence.
+    assert!((own_ship.heading.unwrap() - 98.0).abs() < 1e-4);
+
+    let global = own_ship.global_position().unwrap();
+    let (lat, lon) = global_space_to_deg(&global);
+    assert!((lat - 48.1173).abs() < 1e-4 && (lon - 11.516666).abs() < 1e-4);
+
+    assert_eq!(
+        format_position((47.3769, -8.5417)),
+        "47°22.614'N 008°32.502'W"
+    );
+}
@@ -0,0 +1,225 @@
+use super::*;
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use std::io::{BufRead, BufReader};
+use std::net::{SocketAddr, TcpStream, UdpSocket};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Where NMEA 0183 sentences are read from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NmeaSource {
+    /// A serial device like `/dev/ttyUSB0`.
+    ///
+    /// The baud rate has to be set beforehand, e.g. with `stty -F /dev/ttyUSB0 4800`.
+    Serial(PathBuf),
+    /// A TCP server, like a NMEA multiplexer or a chart plotter.
+    Tcp(String),
+    /// A local address to receive UDP broadcasts on.
+    Udp(String),
+    /// A recorded log which is replayed with `interval` between its RMC sentences.
+    File { path: PathBuf, interval: Duration },
+}
+
+impl NmeaSource {
+    /// Parses a source like `serial:///dev/ttyUSB0`, `tcp://192.168.1.10:10110`,
+    /// `udp://0.0.0.0:10110` or `file://logs/passage.nmea`.
+    pub fn from_url(url: &str, replay_interval: Duration) -> Option<Self> {
+        let (scheme, rest) = url.split_at(url.find("://")?);
+        let rest = &rest[3..];
+        match scheme {
+            "serial" => Some(NmeaSource::Serial(rest.into())),
+            "tcp" => Some(NmeaSource::Tcp(rest.into())),
+            "udp" => Some(NmeaSource::Udp(rest.into())),
+            "file" => Some(NmeaSource::File {
+                path: rest.into(),
+                interval: replay_interval,
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for NmeaSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            NmeaSource::Serial(path) => write!(f, "serial://{}", path.display()),
+            NmeaSource::Tcp(address) => write!(f, "tcp://{}", address),
+            NmeaSource::Udp(address) => write!(f, "udp://{}", address),
+            NmeaSource::File { path, .. } => write!(f, "file://{}", path.display()),
+        }
+    }
+}
+
+/// Reads sentences from a `NmeaSource` on a background thread.
+pub struct NmeaReader {
+    rx: Receiver<Sentence>,
+    /// The address a UDP source is bound to.
+    local_addr: Option<SocketAddr>,
+}
+
+impl NmeaReader {
+    /// Opens the source and starts reading it.
+    ///
+    /// Opening the source fails right away, problems while reading are logged and end the thread.
+    pub fn start(source: &NmeaSource) -> std::io::Result<Self> {
+        let (tx, rx) = unbounded();
+        let name = source.to_string();
+        let mut local_addr = None;
+        match source {
+            NmeaSource::Serial(path) => {
+                let device = std::fs::File::open(path)?;
+                spawn(name, move || read_lines(BufReader::new(device), &tx, None));
+            }
+            NmeaSource::Tcp(address) => {
+                let stream = TcpStream::connect(address)?;
+                spawn(name, move || read_lines(BufReader::new(stream), &tx, None));
+            }
+            NmeaSource::Udp(address) => {
+                let socket = UdpSocket::bind(address)?;
+                local_addr = Some(socket.local_addr()?);
+                spawn(name, move || read_datagrams(&socket, &tx));
+            }
+            NmeaSource::File { path, interval } => {
+                let file = std::fs::File::open(path)?;
+                let interval = *interval;
+                spawn(name, move || {
+                    read_lines(BufReader::new(file), &tx, Some(interval))
+                });
+            }
+        }
+        Ok(Self { rx, local_addr })
+    }
+
+    /// Returns the address a UDP source is bound to.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+
+    /// Returns all sentences which were received since the last call.
+    pub fn try_iter(&self) -> impl Iterator<Item = Sentence> + '_ {
+        self.rx.try_iter()
+    }
+
+    /// Waits for the next sentence.
+    ///
+    /// Returns `None` if there was none within `timeout` or the source has ended.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<Sentence> {
+        self.rx.recv_timeout(timeout).ok()
+    }
+}
+
+/// Runs `read` on a new thread and logs how it ended.
+fn spawn(name: String, read: impl FnOnce() -> std::io::Result<()> + Send + 'static) {
+    std::thread::spawn(move || match read() {
+        Ok(()) => log::info!("The NMEA source {} has ended.", name),
+        Err(err) => log::error!("Failed to read the NMEA source {}: {}", name, err),
+    });
+}
+
+/// Reads sentences line by line until the reader or the receiver is closed.
+///
+/// With an `interval`, the reader waits that long after each RMC sentence to replay a recording.
+fn read_lines(
+    mut reader: impl BufRead,
+    tx: &Sender<Sentence>,
+    interval: Option<Duration>,
+) -> std::io::Result<()> {
+    let mut line = vec![];
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Ok(());
+        }
+        // Serial lines can contain garbage, so the line is not required to be valid UTF-8.
+        let sentence = match parse(&String::from_utf8_lossy(&line)) {
+            Some(sentence) => sentence,
+            None => continue,
+        };
+        let is_fix = matches!(sentence, Sentence::Rmc(_));
+        if tx.send(sentence).is_err() {
+            return Ok(());
+        }
+        if let (true, Some(interval)) = (is_fix, interval) {
+            std::thread::sleep(interval);
+        }
+    }
+}
+
+/// Receives datagrams which contain one or more lines until the receiver is closed.
+fn read_datagrams(socket: &UdpSocket, tx: &Sender<Sentence>) -> std::io::Result<()> {
+    let mut buffer = [0; 4096];
+    loop {
+        let length = socket.recv(&mut buffer)?;
+        for sentence in String::from_utf8_lossy(&buffer[..length])
+            .lines()
+            .filter_map(parse)
+        {
+            if tx.send(sentence).is_err() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Parses a line and logs why it can not be used, unless the sentence is just not supported.
+fn parse(line: &str) -> Option<Sentence> {
+    match parse_sentence(line) {
+        Ok(sentence) => Some(sentence),
+        Err(NmeaError::Unsupported(_)) => None,
+        Err(_) if line.trim().is_empty() => None,
+        Err(err) => {
+            log::info!("Ignoring the NMEA sentence '{}': {}", line.trim(), err);
+            None
+        }
+    }
+}
+
+#[test]
+fn read_udp_datagrams() {
+    let reader = NmeaReader::start(&NmeaSource::Udp("127.0.0.1:0".into())).unwrap();
+    let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+    sender
+        .send_to(
+            b"$HEHDT,274.07,T*19\r\n$GPZDA,201530.00,04,07,2002,00,00*60\r\n$GPGLL,4916.45,N,12311.12,W,225444,A*31\r\n",
+            reader.local_addr().unwrap(),
+        )
+        .unwrap();
+
+    let timeout = Duration::from_secs(5);
+    assert!(matches!(
+        reader.recv_timeout(timeout),
+        Some(Sentence::Hdt(_))
+    ));
+    assert!(matches!(
+        reader.recv_timeout(timeout),
+        Some(Sentence::Gll(_))
+    ));
+}
+
+#[test]
+fn replay_log_file() {
+    let path = std::env::temp_dir().join(format!("sailor-nmea-{}.nmea", std::process::id()));
+    std::fs::write(
+        &path,
+        "$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6A\n\
+         $GPVTG,054.7,T,034.4,M,005.5,N,010.2,K*00\n\
+         garbage\n\
+         $HEHDT,274.07,T*19\n",
+    )
+    .unwrap();
+    let source = NmeaSource::from_url(
+        &format!("file://{}", path.display()),
+        Duration::from_millis(1),
+    )
+    .unwrap();
+    let reader = NmeaReader::start(&source).unwrap();
+
+    // The sentence with the wrong checksum and the garbage are left out.
+    let mut sentences = vec![];
+    while let Some(sentence) = reader.recv_timeout(Duration::from_secs(5)) {
+        sentences.push(sentence);
+    }
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(sentences.len(), 2);
+    assert!(matches!(sentences[1], Sentence::Hdt(_)));
+}
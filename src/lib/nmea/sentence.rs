@@ -0,0 +1,401 @@
+use super::*;
+
+/// A position as latitude and longitude in degrees.
+pub type LatLon = (f32, f32);
+
+/// A NMEA 0183 sentence with the data used for navigation.
+///
+/// Speeds are in knots, courses and headings in degrees relative to true north
+/// and times in seconds since midnight UTC.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sentence {
+    Rmc(Rmc),
+    Gga(Gga),
+    Gll(Gll),
+    Vtg(Vtg),
+    Hdg(Hdg),
+    Hdt(Hdt),
+    Vdm(Vdm),
+}
+
+/// The recommended minimum data of a fix.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rmc {
+    pub time: Option<f32>,
+    /// Whether the receiver considers the fix valid.
+    pub valid: bool,
+    pub position: Option<LatLon>,
+    pub speed: Option<f32>,
+    pub course: Option<f32>,
+    /// The magnetic variation in degrees, positive to the east.
+    pub magnetic_variation: Option<f32>,
+}
+
+/// The fix of a GPS receiver.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gga {
+    pub time: Option<f32>,
+    pub position: Option<LatLon>,
+    /// The kind of fix, 0 if there is none.
+    pub quality: u8,
+    pub satellites: Option<u8>,
+    /// The horizontal dilution of precision.
+    pub hdop: Option<f32>,
+    /// The altitude above the mean sea level in meters.
+    pub altitude: Option<f32>,
+}
+
+/// A geographic position.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gll {
+    pub position: Option<LatLon>,
+    pub time: Option<f32>,
+    pub valid: bool,
+}
+
+/// The course and speed over ground.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vtg {
+    pub course: Option<f32>,
+    pub speed: Option<f32>,
+}
+
+/// The heading of a magnetic compass.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hdg {
+    /// The magnetic heading in degrees.
+    pub heading: Option<f32>,
+    /// The deviation of the compass in degrees, positive to the east.
+    pub deviation: Option<f32>,
+    /// The magnetic variation in degrees, positive to the east.
+    pub variation: Option<f32>,
+}
+
+impl Hdg {
+    /// Returns the heading relative to true north using the variation of the sentence or `variation`.
+    pub fn true_heading(&self, variation: Option<f32>) -> Option<f32> {
+        let heading =
+            self.heading? + self.deviation.unwrap_or(0.0) + self.variation.or(variation)?;
+        Some(heading.rem_euclid(360.0))
+    }
+}
+
+/// The true heading.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hdt {
+    pub heading: Option<f32>,
+}
+
+/// A fragment of an AIS message, received from another vessel (VDM) or sent by the own one (VDO).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vdm {
+    /// Whether the message was sent by the own vessel.
+    pub own: bool,
+    /// The number of fragments of the message.
+    pub fragments: u8,
+    /// The number of this fragment, starting at 1.
+    pub fragment: u8,
+    /// Identifies the fragments of a message which is split over multiple sentences.
+    pub message_id: Option<u8>,
+    /// The radio channel, `A` or `B`.
+    pub channel: Option<String>,
+    /// The payload armored in 6-bit characters.
+    pub payload: String,
+    /// The number of bits to ignore at the end of the payload.
+    pub fill_bits: u8,
+}
+
+/// Parses a single line with a NMEA 0183 sentence.
+///
+/// Sentences without a checksum are accepted, but a wrong checksum is an error.
+pub fn parse_sentence(line: &str) -> Result<Sentence, NmeaError> {
+    let fields = Fields::parse(line)?;
+    match fields.sentence {
+        "RMC" => Ok(Sentence::Rmc(Rmc {
+            time: fields.time(1)?,
+            valid: fields.get(2) == Some("A"),
+            position: fields.position(3)?,
+            speed: fields.number(7)?,
+            course: fields.number(8)?,
+            magnetic_variation: fields.signed(10, "E", "W")?,
+        })),
+        "GGA" => Ok(Sentence::Gga(Gga {
+            time: fields.time(1)?,
+            position: fields.position(2)?,
+            quality: fields.number(6)?.unwrap_or(0),
+            satellites: fields.number(7)?,
+            hdop: fields.number(8)?,
+            altitude: fields.number(9)?,
+        })),
+        "GLL" => Ok(Sentence::Gll(Gll {
+            position: fields.position(1)?,
+            time: fields.time(5)?,
+            valid: fields.get(6) == Some("A"),
+        })),
+        // Old receivers leave out the unit fields.
+        "VTG" if fields.get(2) == Some("T") || fields.get(2).is_none() => Ok(Sentence::Vtg(Vtg {
+            course: fields.number(1)?,
+            speed: fields.number(5)?,
+        })),
+        "VTG" => Ok(Sentence::Vtg(Vtg {
+            course: fields.number(1)?,
+            speed: fields.number(3)?,
+        })),
+        "HDG" => Ok(Sentence::Hdg(Hdg {
+            heading: fields.number(1)?,
+            deviation: fields.signed(2, "E", "W")?,
+            variation: fields.signed(4, "E", "W")?,
+        })),
+        "HDT" => Ok(Sentence::Hdt(Hdt {
+            heading: fields.number(1)?,
+        })),
+        "VDM" | "VDO" => Ok(Sentence::Vdm(Vdm {
+            own: fields.sentence == "VDO",
+            fragments: fields.number(1)?.ok_or_else(|| fields.error(1))?,
+            fragment: fields.number(2)?.ok_or_else(|| fields.error(2))?,
+            message_id: fields.number(3)?,
+            channel: fields.get(4).map(String::from),
+            payload: fields.get(5).unwrap_or_default().into(),
+            fill_bits: fields.number(6)?.unwrap_or(0),
+        })),
+        sentence => Err(NmeaError::Unsupported(sentence.into())),
+    }
+}
+
+/// The fields of a sentence with a valid checksum.
+struct Fields<'a> {
+    /// The sentence type without the talker, e.g. `RMC`.
+    sentence: &'a str,
+    /// All fields, starting with the address.
+    fields: Vec<&'a str>,
+}
+
+impl<'a> Fields<'a> {
+    fn parse(line: &'a str) -> Result<Self, NmeaError> {
+        let line = line.trim();
+        if !line.starts_with('$') && !line.starts_with('!') {
+            return Err(NmeaError::Malformed);
+        }
+        let (contents, checksum) = match line[1..].rfind('*') {
+            Some(star) => (&line[1..star + 1], Some(&line[star + 2..])),
+            None => (&line[1..], None),
+        };
+        if let Some(checksum) = checksum {
+            let expected = u8::from_str_radix(checksum, 16).map_err(|_| NmeaError::Malformed)?;
+            let actual = contents.bytes().fold(0, |checksum, byte| checksum ^ byte);
+            if expected != actual {
+                return Err(NmeaError::Checksum { expected, actual });
+            }
+        }
+
+        let fields = contents.split(',').collect::<Vec<_>>();
+        let address = fields[0];
+        if address.len() != 5 || !address.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(NmeaError::Malformed);
+        }
+        Ok(Self {
+            sentence: &address[2..],
+            fields,
+        })
+    }
+
+    fn error(&self, index: usize) -> NmeaError {
+        NmeaError::BadField {
+            sentence: self.sentence.into(),
+            index,
+        }
+    }
+
+    /// Returns a field, or `None` if it is missing or empty.
+    fn get(&self, index: usize) -> Option<&'a str> {
+        self.fields
+            .get(index)
+            .copied()
+            .filter(|field| !field.is_empty())
+    }
+
+    fn number<T: std::str::FromStr>(&self, index: usize) -> Result<Option<T>, NmeaError> {
+        self.get(index)
+            .map(|field| field.parse().map_err(|_| self.error(index)))
+            .transpose()
+    }
+
+    /// Parses a number followed by a field with its direction, which is negative for `negative`.
+    fn signed(
+        &self,
+        index: usize,
+        positive: &str,
+        negative: &str,
+    ) -> Result<Option<f32>, NmeaError> {
+        let value = match self.number::<f32>(index)? {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        match self.get(index + 1) {
+            Some(direction) if direction == positive => Ok(Some(value)),
+            Some(direction) if direction == negative => Ok(Some(-value)),
+            _ => Err(self.error(index + 1)),
+        }
+    }
+
+    /// Parses a time like `123519.00` into seconds since midnight.
+    fn time(&self, index: usize) -> Result<Option<f32>, NmeaError> {
+        let time = match self.number::<f32>(index)? {
+            Some(time) => time,
+            None => return Ok(None),
+        };
+        let (hours, minutes, seconds) = (
+            (time / 10000.0).floor(),
+            (time / 100.0).floor() % 100.0,
+            time % 100.0,
+        );
+        if hours >= 24.0 || minutes >= 60.0 || seconds >= 61.0 {
+            return Err(self.error(index));
+        }
+        Ok(Some(hours * 3600.0 + minutes * 60.0 + seconds))
+    }
+
+    /// Parses a latitude like `4807.038,N` and a longitude like `01131.000,E` in four fields.
+    fn position(&self, index: usize) -> Result<Option<LatLon>, NmeaError> {
+        let lat = self.angle(index, "N", "S", 90.0)?;
+        let lon = self.angle(index + 2, "E", "W", 180.0)?;
+        match (lat, lon) {
+            (Some(lat), Some(lon)) => Ok(Some((lat, lon))),
+            _ => Ok(None),
+        }
+    }
+
+    /// Parses degrees and minutes like `4807.038` into degrees.
+    fn angle(
+        &self,
+        index: usize,
+        positive: &str,
+        negative: &str,
+        max: f64,
+    ) -> Result<Option<f32>, NmeaError> {
+        let value = match self.number::<f64>(index)? {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        let degrees = (value / 100.0).floor();
+        let minutes = value - degrees * 100.0;
+        let angle = degrees + minutes / 60.0;
+        if minutes >= 60.0 || angle > max {
+            return Err(self.error(index));
+        }
+        match self.get(index + 1) {
+            Some(direction) if direction == positive => Ok(Some(angle as f32)),
+            Some(direction) if direction == negative => Ok(Some(-angle as f32)),
+            _ => Err(self.error(index + 1)),
+        }
+    }
+}
+
+#[test]
+fn parse_navigation_sentences() {
+    let rmc =
+        parse_sentence("$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6A\r\n")
+            .unwrap();
+    assert_eq!(
+        rmc,
+        Sentence::Rmc(Rmc {
+            time: Some(12.0 * 3600.0 + 35.0 * 60.0 + 19.0),
+            valid: true,
+            position: Some((48.1173, 11.516666)),
+            speed: Some(22.4),
+            course: Some(84.4),
+            magnetic_variation: Some(-3.1),
+        })
+    );
+
+    let gga = parse_sentence("$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47")
+        .unwrap();
+    assert!(matches!(
+        gga,
+        Sentence::Gga(Gga { quality: 1, satellites: Some(8), hdop: Some(h), .. }) if h == 0.9
+    ));
+
+    let gll = parse_sentence("$GPGLL,4916.45,N,12311.12,W,225444,A*31").unwrap();
+    assert_eq!(
+        gll,
+        Sentence::Gll(Gll {
+            position: Some((49.274166, -123.18533)),
+            time: Some(22.0 * 3600.0 + 54.0 * 60.0 + 44.0),
+            valid: true,
+        })
+    );
+
+    let vtg = Sentence::Vtg(Vtg {
+        course: Some(54.7),
+        speed: Some(5.5),
+    });
+    assert_eq!(
+        parse_sentence("$GPVTG,054.7,T,034.4,M,005.5,N,010.2,K*48").unwrap(),
+        vtg
+    );
+    assert_eq!(
+        parse_sentence("$GPVTG,054.7,034.4,005.5,010.2*54").unwrap(),
+        vtg
+    );
+
+    match parse_sentence("$HCHDG,101.1,,,7.1,W*3C").unwrap() {
+        Sentence::Hdg(hdg) => {
+            assert!((hdg.true_heading(None).unwrap() - 94.0).abs() < 1e-4);
+            assert_eq!(hdg.variation, Some(-7.1));
+        }
+        sentence => panic!("Unexpected sentence {:?}", sentence),
+    }
+    assert_eq!(
+        parse_sentence("$HEHDT,274.07,T*19").unwrap(),
+        Sentence::Hdt(Hdt {
+            heading: Some(274.07)
+        })
+    );
+
+    assert_eq!(
+        parse_sentence("!AIVDM,2,2,1,A,88888888880,2*25").unwrap(),
+        Sentence::Vdm(Vdm {
+            own: false,
+            fragments: 2,
+            fragment: 2,
+            message_id: Some(1),
+            channel: Some("A".into()),
+            payload: "88888888880".into(),
+            fill_bits: 2,
+        })
+    );
+
+    // A receiver without a fix sends empty fields.
+    assert!(matches!(
+        parse_sentence("$GPRMC,,V,,,,,,,,,*31").unwrap(),
+        Sentence::Rmc(Rmc {
+            valid: false,
+            position: None,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn reject_broken_sentences() {
+    assert_eq!(
+        parse_sentence("$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6B"),
+        Err(NmeaError::Checksum {
+            expected: 0x6B,
+            actual: 0x6A
+        })
+    );
+    assert_eq!(
+        parse_sentence("$GPZDA,201530.00,04,07,2002,00,00*60"),
+        Err(NmeaError::Unsupported("ZDA".into()))
+    );
+    assert_eq!(
+        parse_sentence("$GPGLL,4916.45,X,12311.12,W,225444,A"),
+        Err(NmeaError::BadField {
+            sentence: "GLL".into(),
+            index: 2
+        })
+    );
+    assert_eq!(parse_sentence("GPGLL,4916.45"), Err(NmeaError::Malformed));
+    assert_eq!(parse_sentence("$GP"), Err(NmeaError::Malformed));
+}
@@ -1,60 +1,199 @@
 use super::*;
 use lyon::math::Point;
+use lyon::path::Path;
 use std::collections::HashMap;
 
 /// Classifies an object as one of three possible types.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ObjectType {
     Polygon,
     Line,
     Point,
 }
 
+/// A single polygon made of an exterior ring and any number of holes.
+///
+/// The rings are not closed explicitly, the last point connects back to the first one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polygon {
+    pub exterior: Vec<Point>,
+    pub interiors: Vec<Vec<Point>>,
+}
+
+impl Polygon {
+    /// Returns all rings of the polygon, starting with the exterior ring.
+    pub fn rings(&self) -> impl Iterator<Item = &Vec<Point>> {
+        std::iter::once(&self.exterior).chain(self.interiors.iter())
+    }
+}
+
+/// The full geometry of an object in tile space.
+///
+/// Every geometry can hold multiple parts, e.g. the points of a multipoint
+/// or the polygons of a multipolygon.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Geometry {
+    Points(Vec<Point>),
+    Lines(Vec<Vec<Point>>),
+    Polygons(Vec<Polygon>),
+}
+
+impl Geometry {
+    /// Returns the type of object the geometry describes.
+    pub fn object_type(&self) -> ObjectType {
+        match self {
+            Geometry::Points(_) => ObjectType::Point,
+            Geometry::Lines(_) => ObjectType::Line,
+            Geometry::Polygons(_) => ObjectType::Polygon,
+        }
+    }
+
+    /// Returns the number of parts of the geometry.
+    pub fn len(&self) -> usize {
+        match self {
+            Geometry::Points(points) => points.len(),
+            Geometry::Lines(lines) => lines.len(),
+            Geometry::Polygons(polygons) => polygons.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns all points of all parts of the geometry.
+    pub fn points(&self) -> Box<dyn Iterator<Item = &Point> + '_> {
+        match self {
+            Geometry::Points(points) => Box::new(points.iter()),
+            Geometry::Lines(lines) => Box::new(lines.iter().flatten()),
+            Geometry::Polygons(polygons) => {
+                Box::new(polygons.iter().flat_map(|p| p.rings()).flatten())
+            }
+        }
+    }
+
+    /// Returns the position of a label for every part of the geometry.
+    pub fn label_positions(&self) -> Vec<Point> {
+        match self {
+            Geometry::Points(points) => points.clone(),
+            Geometry::Lines(lines) => lines.iter().filter_map(|l| l.first()).copied().collect(),
+            Geometry::Polygons(polygons) => polygons
+                .iter()
+                .filter_map(|p| p.exterior.first())
+                .copied()
+                .collect(),
+        }
+    }
+
+    /// Converts the geometry into paths which can be tesselated.
+    ///
+    /// Every polygon becomes one path containing all of its rings, so holes are cut out when filling.
+    pub fn to_paths(&self) -> Vec<Path> {
+        let build = |rings: &mut dyn Iterator<Item = &Vec<Point>>, close: bool| {
+            let mut builder = Path::builder();
+            for ring in rings {
+                if let Some((first, rest)) = ring.split_first() {
+                    builder.move_to(*first);
+                    for point in rest {
+                        builder.line_to(*point);
+                    }
+                    if close {
+                        builder.close();
+                    }
+                }
+            }
+            builder.build()
+        };
+
+        match self {
+            Geometry::Points(points) => points
+                .iter()
+                .map(|point| {
+                    let mut builder = Path::builder();
+                    builder.move_to(*point);
+                    builder.build()
+                })
+                .collect(),
+            Geometry::Lines(lines) => lines
+                .iter()
+                .map(|line| build(&mut std::iter::once(line), false))
+                .collect(),
+            Geometry::Polygons(polygons) => polygons
+                .iter()
+                .map(|polygon| build(&mut polygon.rings(), true))
+                .collect(),
+        }
+    }
+
+    /// Returns the estimated heap memory used by the geometry.
+    fn size(&self) -> usize {
+        let point_size = std::mem::size_of::<Point>();
+        let ring_size = std::mem::size_of::<Vec<Point>>();
+        match self {
+            Geometry::Points(points) => points.capacity() * point_size,
+            Geometry::Lines(lines) => {
+                lines.capacity() * ring_size
+                    + lines
+                        .iter()
+                        .map(|l| l.capacity() * point_size)
+                        .sum::<usize>()
+            }
+            Geometry::Polygons(polygons) => {
+                polygons.capacity() * std::mem::size_of::<Polygon>()
+                    + polygons
+                        .iter()
+                        .map(|p| {
+                            p.interiors.capacity() * ring_size
+                                + p.rings().map(|r| r.capacity() * point_size).sum::<usize>()
+                        })
+                        .sum::<usize>()
+            }
+        }
+    }
+}
+
 /// Represents any object on the map.
 #[derive(Debug, Clone)]
 pub struct Object {
     /// The CSS selector that fully describes the object.
     selector: Selector,
-    /// All the points that belong to the object.
-    /// If this is a polygon, the points describe the outline in order.
-    /// If this is a line, the points describe the line in order.
-    /// For a point there is only one point contained.
-    points: Vec<Point>,
+    /// The full geometry of the object with all its parts.
+    geometry: Geometry,
     /// All the OSM tags that are attached to this object.
     tags: HashMap<String, String>,
-    /// The object type.
-    object_type: ObjectType,
 }
 
 impl Object {
     /// Creates a new object with no tags.
-    pub fn new(selector: Selector, points: Vec<Point>, object_type: ObjectType) -> Self {
+    pub fn new(selector: Selector, geometry: Geometry) -> Self {
         Self {
             selector,
-            points,
+            geometry,
             tags: HashMap::new(),
-            object_type,
         }
     }
 
     /// Creates a new object with an initial set of tags.
     pub fn new_with_tags(
         selector: Selector,
-        points: Vec<Point>,
+        geometry: Geometry,
         tags: HashMap<String, String>,
-        object_type: ObjectType,
     ) -> Self {
         Self {
             selector,
-            points,
+            geometry,
             tags,
-            object_type,
         }
     }
 
-    /// Returns the set of points contained in the object.
-    pub fn points(&self) -> &Vec<Point> {
-        &self.points
+    /// Returns the full geometry of the object.
+    pub fn geometry(&self) -> &Geometry {
+        &self.geometry
+    }
+
+    /// Returns the type of the object.
+    pub fn object_type(&self) -> ObjectType {
+        self.geometry.object_type()
     }
 
     /// Returns the set of tags contained in the object.
@@ -78,8 +217,7 @@ impl Object {
                 .iter()
                 .map(|(k, v)| k.len() + v.len())
                 .sum::<usize>()
-            + self.points.capacity() * std::mem::size_of::<Point>()
-            + 8
-            + std::mem::size_of::<ObjectType>()
+            + self.geometry.size()
+            + std::mem::size_of::<Geometry>()
     }
 }
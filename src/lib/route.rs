@@ -0,0 +1,268 @@
+use crate::*;
+use lyon::math::Point;
+use serde_derive::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// A named sequence of waypoints in degrees.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Route {
+    pub name: String,
+    pub waypoints: Vec<LatLon>,
+}
+
+/// The rhumb line from a waypoint to the next one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Leg {
+    /// The index of the waypoint the leg starts at.
+    pub start: usize,
+    /// The constant bearing in degrees relative to true north.
+    pub bearing: f32,
+    /// The distance in nautical miles.
+    pub distance: f32,
+}
+
+impl Route {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            waypoints: vec![],
+        }
+    }
+
+    /// Returns the legs between the waypoints, which are sailed on a constant bearing.
+    pub fn legs(&self) -> Vec<Leg> {
+        self.waypoints
+            .windows(2)
+            .enumerate()
+            .map(|(start, leg)| {
+                let (bearing, distance) = rhumb_line(leg[0], leg[1]);
+                Leg {
+                    start,
+                    bearing,
+                    distance,
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the distance of all legs in nautical miles.
+    pub fn total_distance(&self) -> f32 {
+        self.legs().iter().map(|leg| leg.distance).sum()
+    }
+
+    /// Inserts a waypoint in the middle of the leg starting at waypoint `start`.
+    pub fn split_leg(&mut self, start: usize) {
+        if let (Some(from), Some(to)) = (self.waypoints.get(start), self.waypoints.get(start + 1)) {
+            // Crossing the antimeridian is shorter than going around, like on the rhumb line.
+            let mut delta_lon = to.1 - from.1;
+            if delta_lon.abs() > 180.0 {
+                delta_lon -= delta_lon.signum() * 360.0;
+            }
+            let mut lon = from.1 + delta_lon / 2.0;
+            if lon.abs() > 180.0 {
+                lon -= lon.signum() * 360.0;
+            }
+            let middle = ((from.0 + to.0) / 2.0, lon);
+            self.waypoints.insert(start + 1, middle);
+        }
+    }
+
+    /// Returns the waypoints in global space.
+    pub fn global_waypoints(&self) -> Vec<Point> {
+        self.waypoints
+            .iter()
+            .map(|(lat, lon)| num_to_global_space(&deg2num(*lat, *lon, 0)))
+            .collect()
+    }
+
+    /// Returns the index of the waypoint closest to a pixel on the screen within `radius` pixels.
+    pub fn waypoint_at(
+        &self,
+        screen: &Screen,
+        zoom: f32,
+        pixel: Point,
+        radius: f32,
+    ) -> Option<usize> {
+        self.global_waypoints()
+            .iter()
+            .map(|waypoint| (screen.global_to_pixel(zoom, waypoint) - pixel).length())
+            .enumerate()
+            .filter(|(_, distance)| *distance <= radius)
+            .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+            .map(|(index, _)| index)
+    }
+
+    /// Returns the index of the waypoint starting the leg closest to a pixel on the screen within `radius` pixels.
+    pub fn leg_at(&self, screen: &Screen, zoom: f32, pixel: Point, radius: f32) -> Option<usize> {
+        let pixels = self
+            .global_waypoints()
+            .iter()
+            .map(|waypoint| screen.global_to_pixel(zoom, waypoint))
+            .collect::<Vec<_>>();
+        pixels
+            .windows(2)
+            .map(|leg| {
+                let (start, end) = (leg[0], leg[1]);
+                let length = (end - start).square_length();
+                let along = if length > 0.0 {
+                    ((pixel - start).dot(end - start) / length).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                (pixel - start.lerp(end, along)).length()
+            })
+            .enumerate()
+            .filter(|(_, distance)| *distance <= radius)
+            .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+            .map(|(index, _)| index)
+    }
+}
+
+/// All routes, which are stored in a JSON file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RouteBook {
+    pub routes: Vec<Route>,
+}
+
+impl RouteBook {
+    /// Loads the routes from a file, an empty book if the file does not exist yet.
+    ///
+    /// A file which can not be parsed is moved to [`RouteBook::backup_path`], so saving does not overwrite it.
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let path = path.as_ref();
+        match std::fs::read(path) {
+            Ok(data) => serde_json::from_slice(&data).or_else(|err| {
+                let backup = Self::backup_path(path);
+                std::fs::rename(path, &backup)?;
+                log::warn!("Moved the unreadable routes {:?} to {:?}.", path, backup);
+                Err(err.into())
+            }),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Returns where a file which could not be loaded is kept, e.g. `routes.json.bak`.
+    pub fn backup_path(path: impl AsRef<Path>) -> PathBuf {
+        let mut backup = path.as_ref().as_os_str().to_owned();
+        backup.push(".bak");
+        backup.into()
+    }
+
+    /// Writes the routes to a file.
+    ///
+    /// The file is replaced at once, so it is never left half written.
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let path = path.as_ref();
+        let temporary = path.with_extension("tmp");
+        std::fs::write(&temporary, serde_json::to_vec_pretty(self)?)?;
+        std::fs::rename(&temporary, path)
+    }
+}
+
+#[cfg(test)]
+fn test_route() -> Route {
+    Route {
+        name: "Lake Zurich".into(),
+        waypoints: vec![(47.35, 8.55), (47.3, 8.6), (47.3, 8.7)],
+    }
+}
+
+#[test]
+fn route_legs() {
+    let route = test_route();
+    let legs = route.legs();
+    assert_eq!(legs.len(), 2);
+    assert_eq!(legs[1].start, 1);
+    assert!(legs[0].bearing > 90.0 && legs[0].bearing < 180.0);
+    assert!((legs[1].bearing - 90.0).abs() < 0.1);
+    // A tenth of a degree of longitude at 47.3° is 6 cos(47.3°) nautical miles.
+    assert!((legs[1].distance - 6.0 * 47.3f32.to_radians().cos()).abs() < 0.01);
+    assert!((route.total_distance() - legs[0].distance - legs[1].distance).abs() < 1e-4);
+    assert_eq!(Route::new("Empty").total_distance(), 0.0);
+}
+
+#[test]
+fn find_waypoints_and_legs_on_screen() {
+    let route = test_route();
+    let waypoints = route.global_waypoints();
+    let screen = Screen::new(waypoints[1], 800, 600, 256, 1.0);
+    let zoom = 10.0;
+    let pixel = |point: &Point| screen.global_to_pixel(zoom, point);
+
+    let near_second = pixel(&waypoints[1]) + lyon::math::vector(3.0, -4.0);
+    assert_eq!(route.waypoint_at(&screen, zoom, near_second, 10.0), Some(1));
+    assert_eq!(route.waypoint_at(&screen, zoom, near_second, 4.0), None);
+
+    let middle_of_second_leg = pixel(&waypoints[1]).lerp(pixel(&waypoints[2]), 0.5);
+    assert_eq!(
+        route.waypoint_at(&screen, zoom, middle_of_second_leg, 10.0),
+        None
+    );
+    assert_eq!(
+        route.leg_at(&screen, zoom, middle_of_second_leg, 10.0),
+        Some(1)
+    );
+    assert_eq!(
+        route.leg_at(
+            &screen,
+            zoom,
+            middle_of_second_leg + lyon::math::vector(0.0, 50.0),
+            10.0
+        ),
+        None
+    );
+}
+
+#[test]
+fn save_and_load_routes() {
+    let path = std::env::temp_dir().join(format!("sailor-routes-{}.json", std::process::id()));
+    assert_eq!(RouteBook::load(&path).unwrap(), RouteBook::default());
+
+    let book = RouteBook {
+        routes: vec![test_route(), Route::new("Empty")],
+    };
+    book.save(&path).unwrap();
+    assert_eq!(RouteBook::load(&path).unwrap(), book);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn keep_unreadable_routes() {
+    let path =
+        std::env::temp_dir().join(format!("sailor-broken-routes-{}.json", std::process::id()));
+    std::fs::write(&path, "{").unwrap();
+    assert!(RouteBook::load(&path).is_err());
+
+    // The broken file is moved aside, so the next save does not overwrite it.
+    let backup = RouteBook::backup_path(&path);
+    assert!(!path.exists());
+    assert_eq!(std::fs::read_to_string(&backup).unwrap(), "{");
+    RouteBook::default().save(&path).unwrap();
+    assert_eq!(std::fs::read_to_string(&backup).unwrap(), "{");
+
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(&backup).unwrap();
+}
+
+#[test]
+fn split_legs() {
+    let mut route = test_route();
+    route.split_leg(1);
+    assert_eq!(route.waypoints[2], (47.3, 8.65));
+    assert_eq!(route.waypoints.len(), 4);
+
+    // The middle of a leg across the antimeridian is on the antimeridian.
+    let mut route = Route::new("Pacific");
+    route.waypoints = vec![(-17.0, 179.0), (-17.0, -179.0)];
+    route.split_leg(0);
+    assert_eq!(route.waypoints[1].1.abs(), 180.0);
+    route.waypoints = vec![(-17.0, -179.5), (-17.0, 178.5)];
+    route.split_leg(0);
+    assert_eq!(route.waypoints[1], (-17.0, 179.5));
+
+    // The last waypoint does not start a leg.
+    route.split_leg(2);
+    assert_eq!(route.waypoints.len(), 3);
+}
@@ -0,0 +1,57 @@
+use std::path::PathBuf;
+
+use super::*;
+
+/// A `TileSource` which reads tiles from a `{z}/{x}/{y}.pbf` directory tree.
+pub struct DirectoryTileSource {
+    root: PathBuf,
+    min_zoom: u32,
+    max_zoom: u32,
+}
+
+impl DirectoryTileSource {
+    /// Creates a new `DirectoryTileSource` reading from `root`.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            min_zoom: 0,
+            max_zoom: 14,
+        }
+    }
+
+    /// Limits the source to the zoom levels `min_zoom` to `max_zoom`.
+    pub fn with_zoom_range(mut self, min_zoom: u32, max_zoom: u32) -> Self {
+        self.min_zoom = min_zoom;
+        self.max_zoom = max_zoom;
+        self
+    }
+
+    /// Returns the path a tile is read from.
+    pub fn path(&self, tile_id: &TileId) -> PathBuf {
+        self.root
+            .join(format!("{}/{}/{}.pbf", tile_id.z, tile_id.x, tile_id.y))
+    }
+}
+
+impl TileSource for DirectoryTileSource {
+    fn fetch(&self, tile_id: &TileId) -> Option<Vec<u8>> {
+        let path = self.path(tile_id);
+        if path.exists() {
+            read_tile_file(path)
+        } else {
+            None
+        }
+    }
+
+    fn min_zoom(&self) -> u32 {
+        self.min_zoom
+    }
+
+    fn max_zoom(&self) -> u32 {
+        self.max_zoom
+    }
+
+    fn attribution(&self) -> Option<&str> {
+        None
+    }
+}
@@ -0,0 +1,103 @@
+use std::path::PathBuf;
+
+use super::*;
+
+/// A `TileSource` which downloads tiles from a HTTP tile server.
+///
+/// Downloaded tiles are kept in the `DiskCache` below `cache_location`.
+pub struct HttpTileSource {
+    url_template: String,
+    key: String,
+    disk_cache: DiskCache,
+    min_zoom: u32,
+    max_zoom: u32,
+    attribution: Option<String>,
+}
+
+impl HttpTileSource {
+    /// Creates a new `HttpTileSource`.
+    ///
+    /// The `{z}`, `{x}`, `{y}` and `{key}` placeholders in `url_template`
+    /// are replaced with the tile coordinates and the API key respectively.
+    pub fn new(
+        url_template: impl Into<String>,
+        key: impl Into<String>,
+        cache_location: impl Into<PathBuf>,
+    ) -> Self {
+        Self {
+            url_template: url_template.into(),
+            key: key.into(),
+            disk_cache: DiskCache::open(cache_location),
+            min_zoom: 0,
+            max_zoom: 14,
+            attribution: None,
+        }
+    }
+
+    /// Limits the source to the zoom levels `min_zoom` to `max_zoom`.
+    pub fn with_zoom_range(mut self, min_zoom: u32, max_zoom: u32) -> Self {
+        self.min_zoom = min_zoom;
+        self.max_zoom = max_zoom;
+        self
+    }
+
+    /// Sets the expiry and size limits of the disk cache.
+    pub fn with_cache_policy(mut self, policy: CachePolicy) -> Self {
+        self.disk_cache = self.disk_cache.with_policy(policy);
+        self
+    }
+
+    /// Returns the disk cache the downloaded tiles are kept in.
+    pub fn disk_cache(&self) -> &DiskCache {
+        &self.disk_cache
+    }
+
+    /// Sets the attribution text of the source.
+    pub fn with_attribution(mut self, attribution: impl Into<String>) -> Self {
+        self.attribution = Some(attribution.into());
+        self
+    }
+
+    /// Returns the URL a tile is requested from.
+    pub fn url(&self, tile_id: &TileId) -> String {
+        self.url_template
+            .replace("{z}", &tile_id.z.to_string())
+            .replace("{x}", &tile_id.x.to_string())
+            .replace("{y}", &tile_id.y.to_string())
+            .replace("{key}", &self.key)
+    }
+}
+
+impl TileSource for HttpTileSource {
+    fn fetch(&self, tile_id: &TileId) -> Option<Vec<u8>> {
+        fetch_tile_data(&self.disk_cache, &self.url(tile_id), tile_id)
+    }
+
+    fn min_zoom(&self) -> u32 {
+        self.min_zoom
+    }
+
+    fn max_zoom(&self) -> u32 {
+        self.max_zoom
+    }
+
+    fn attribution(&self) -> Option<&str> {
+        self.attribution.as_deref()
+    }
+}
+
+#[test]
+fn url_from_template() {
+    let root = std::env::temp_dir().join(format!("sailor-http-{}", std::process::id()));
+    let source = HttpTileSource::new(
+        "https://tiles.example.com/{z}/{x}/{y}.pbf?key={key}",
+        "secret",
+        &root,
+    );
+    assert_eq!(
+        source.url(&TileId::new(8, 142, 93)),
+        "https://tiles.example.com/8/142/93.pbf?key=secret"
+    );
+    drop(source);
+    std::fs::remove_dir_all(&root).unwrap();
+}
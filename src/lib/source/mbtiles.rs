@@ -0,0 +1,354 @@
+use std::path::Path;
+use std::sync::Mutex;
+
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension};
+
+use super::*;
+
+/// The contents of the `metadata` table of a MBTiles archive.
+#[derive(Debug, Clone, Default)]
+pub struct MbTilesMetadata {
+    pub name: Option<String>,
+    pub format: Option<String>,
+    /// The area covered by the archive as `[west, south, east, north]` in degrees.
+    pub bounds: Option<[f32; 4]>,
+    pub min_zoom: Option<u32>,
+    pub max_zoom: Option<u32>,
+    pub attribution: Option<String>,
+    /// The ids of all the vector layers contained in the tiles.
+    pub layers: Vec<String>,
+}
+
+impl MbTilesMetadata {
+    /// Reads the metadata from the `metadata` table of an open MBTiles archive.
+    fn read(connection: &Connection) -> rusqlite::Result<Self> {
+        let mut metadata = Self::default();
+
+        let mut statement = connection.prepare("SELECT name, value FROM metadata")?;
+        let rows = statement.query_map(params![], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+
+        for row in rows {
+            let (name, value) = row?;
+            match &name[..] {
+                "name" => metadata.name = Some(value),
+                "format" => metadata.format = Some(value),
+                "bounds" => {
+                    let bounds = value
+                        .split(',')
+                        .map(|v| v.trim().parse::<f32>())
+                        .collect::<Result<Vec<_>, _>>();
+                    match bounds {
+                        Ok(bounds) if bounds.len() == 4 => {
+                            metadata.bounds = Some([bounds[0], bounds[1], bounds[2], bounds[3]])
+                        }
+                        _ => log::warn!("Invalid MBTiles bounds '{}'.", value),
+                    }
+                }
+                "minzoom" => metadata.min_zoom = value.trim().parse().ok(),
+                "maxzoom" => metadata.max_zoom = value.trim().parse().ok(),
+                "attribution" => metadata.attribution = Some(value),
+                "json" => match serde_json::from_str::<serde_json::Value>(&value) {
+                    Ok(json) => {
+                        if let Some(layers) = json["vector_layers"].as_array() {
+                            metadata.layers = layers
+                                .iter()
+                                .filter_map(|layer| layer["id"].as_str())
+                                .map(|id| id.to_string())
+                                .collect();
+                        }
+                    }
+                    Err(e) => log::warn!("Invalid MBTiles json metadata. Reason:\r\n{}", e),
+                },
+                _ => {}
+            }
+        }
+
+        Ok(metadata)
+    }
+}
+
+/// A `TileSource` which reads tiles from a MBTiles (SQLite) archive.
+pub struct MbTilesSource {
+    connection: Mutex<Connection>,
+    metadata: MbTilesMetadata,
+}
+
+impl MbTilesSource {
+    /// Tries to open the MBTiles archive at `path` for reading.
+    pub fn try_open(path: impl AsRef<Path>) -> Option<Self> {
+        let path = path.as_ref();
+        let connection = match Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY) {
+            Ok(connection) => connection,
+            Err(e) => {
+                log::error!("Unable to open MBTiles {:?}. Reason:\r\n{}", path, e);
+                return None;
+            }
+        };
+
+        let metadata = match MbTilesMetadata::read(&connection) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                log::error!(
+                    "Unable to read the metadata of MBTiles {:?}. Reason:\r\n{}",
+                    path,
+                    e
+                );
+                return None;
+            }
+        };
+
+        Some(Self {
+            connection: Mutex::new(connection),
+            metadata,
+        })
+    }
+
+    /// Returns the metadata of the archive.
+    pub fn metadata(&self) -> &MbTilesMetadata {
+        &self.metadata
+    }
+
+    /// Checks if the archive holds a tile without reading it.
+    pub fn contains(&self, tile_id: &TileId) -> bool {
+        let row = match tms_row(tile_id) {
+            Some(row) => row,
+            None => return false,
+        };
+        self.connection
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT 1 FROM tiles WHERE zoom_level = ?1 AND tile_column = ?2 AND tile_row = ?3",
+                params![tile_id.z, tile_id.x, row],
+                |_| Ok(()),
+            )
+            .optional()
+            .ok()
+            .flatten()
+            .is_some()
+    }
+}
+
+impl TileSource for MbTilesSource {
+    fn fetch(&self, tile_id: &TileId) -> Option<Vec<u8>> {
+        let row = match tms_row(tile_id) {
+            Some(row) => row,
+            None => {
+                log::warn!("Tile {} does not exist.", tile_id);
+                return None;
+            }
+        };
+        let connection = self.connection.lock().unwrap();
+        let data = connection
+            .query_row(
+                "SELECT tile_data FROM tiles WHERE zoom_level = ?1 AND tile_column = ?2 AND tile_row = ?3",
+                params![tile_id.z, tile_id.x, row],
+                |row| row.get::<_, Vec<u8>>(0),
+            )
+            .optional();
+
+        match data {
+            Ok(Some(data)) => match decompress_tile_data(&data) {
+                Ok(decompressed) => Some(decompressed.into_owned()),
+                Err(e) => {
+                    log::warn!("Could not decompress {}. Reason:\r\n{}", tile_id, e);
+                    None
+                }
+            },
+            Ok(None) => None,
+            Err(e) => {
+                log::warn!("Could not read {} from MBTiles. Reason:\r\n{}", tile_id, e);
+                None
+            }
+        }
+    }
+
+    fn min_zoom(&self) -> u32 {
+        self.metadata.min_zoom.unwrap_or(0)
+    }
+
+    fn max_zoom(&self) -> u32 {
+        self.metadata.max_zoom.unwrap_or(14)
+    }
+
+    fn attribution(&self) -> Option<&str> {
+        self.metadata.attribution.as_deref()
+    }
+}
+
+/// A writer which stores tiles in a MBTiles archive.
+///
+/// Opening an existing archive keeps its tiles, so interrupted downloads can be resumed.
+pub struct MbTilesWriter {
+    connection: Connection,
+}
+
+impl MbTilesWriter {
+    /// Tries to open or create the MBTiles archive at `path` for writing.
+    ///
+    /// The `metadata` entries are written to the archive, replacing existing entries with the same name.
+    pub fn try_create(path: impl AsRef<Path>, metadata: &[(&str, String)]) -> Option<Self> {
+        let path = path.as_ref();
+        let result = Connection::open(path).and_then(|connection| {
+            connection.execute_batch(
+                "CREATE TABLE IF NOT EXISTS metadata (name TEXT, value TEXT);
+                 CREATE UNIQUE INDEX IF NOT EXISTS name ON metadata (name);
+                 CREATE TABLE IF NOT EXISTS tiles (zoom_level INTEGER, tile_column INTEGER, tile_row INTEGER, tile_data BLOB);
+                 CREATE UNIQUE INDEX IF NOT EXISTS tile_index ON tiles (zoom_level, tile_column, tile_row);",
+            )?;
+            for (name, value) in metadata {
+                connection.execute(
+                    "INSERT OR REPLACE INTO metadata (name, value) VALUES (?1, ?2)",
+                    params![name, value],
+                )?;
+            }
+            Ok(connection)
+        });
+
+        match result {
+            Ok(connection) => Some(Self { connection }),
+            Err(e) => {
+                log::error!("Unable to create MBTiles {:?}. Reason:\r\n{}", path, e);
+                None
+            }
+        }
+    }
+
+    /// Checks if the archive already holds a tile.
+    pub fn contains(&self, tile_id: &TileId) -> bool {
+        let row = match tms_row(tile_id) {
+            Some(row) => row,
+            None => return false,
+        };
+        self.connection
+            .query_row(
+                "SELECT 1 FROM tiles WHERE zoom_level = ?1 AND tile_column = ?2 AND tile_row = ?3",
+                params![tile_id.z, tile_id.x, row],
+                |_| Ok(()),
+            )
+            .optional()
+            .ok()
+            .flatten()
+            .is_some()
+    }
+
+    /// Stores the raw MVT `data` of a tile gzip compressed in the archive.
+    pub fn insert(&self, tile_id: &TileId, data: &[u8]) -> bool {
+        let row = match tms_row(tile_id) {
+            Some(row) => row,
+            None => {
+                log::error!(
+                    "Unable to store {} in MBTiles. The tile does not exist.",
+                    tile_id
+                );
+                return false;
+            }
+        };
+        let result = compress_tile_data(data).map_err(|e| e.to_string()).and_then(|compressed| {
+            self.connection
+                .execute(
+                    "INSERT OR REPLACE INTO tiles (zoom_level, tile_column, tile_row, tile_data) VALUES (?1, ?2, ?3, ?4)",
+                    params![tile_id.z, tile_id.x, row, &compressed[..]],
+                )
+                .map_err(|e| e.to_string())
+        });
+
+        match result {
+            Ok(_) => true,
+            Err(e) => {
+                log::error!("Unable to store {} in MBTiles. Reason:\r\n{}", tile_id, e);
+                false
+            }
+        }
+    }
+}
+
+/// Converts the XYZ row of a tile into the TMS row used by MBTiles, which counts rows from the bottom.
+///
+/// Returns `None` if the tile is outside of the world at its zoom level.
+fn tms_row(tile_id: &TileId) -> Option<u32> {
+    let size = 1u32.checked_shl(tile_id.z)?;
+    if tile_id.x >= size || tile_id.y >= size {
+        return None;
+    }
+    Some(size - 1 - tile_id.y)
+}
+
+#[test]
+fn convert_tms_rows() {
+    assert_eq!(tms_row(&TileId::new(8, 142, 93)), Some(162));
+    assert_eq!(tms_row(&TileId::new(0, 0, 0)), Some(0));
+    assert_eq!(tms_row(&TileId::new(31, 0, 0)), Some((1 << 31) - 1));
+    assert_eq!(tms_row(&TileId::new(32, 0, 0)), None);
+    assert_eq!(tms_row(&TileId::new(8, 0, 256)), None);
+    assert_eq!(tms_row(&TileId::new(8, 256, 0)), None);
+}
+
+#[test]
+fn read_tile_and_metadata() {
+    use std::io::Write;
+
+    let path = std::env::temp_dir().join(format!("sailor-test-{}.mbtiles", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let data = include_bytes!("../../../data/8_142_93.pbf");
+    let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+    encoder.write_all(data).unwrap();
+    let compressed = encoder.finish().unwrap();
+
+    {
+        let connection = Connection::open(&path).unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE metadata (name TEXT, value TEXT);
+                 CREATE TABLE tiles (zoom_level INTEGER, tile_column INTEGER, tile_row INTEGER, tile_data BLOB);
+                 INSERT INTO metadata VALUES ('bounds', '5.9,45.8,10.5,47.8');
+                 INSERT INTO metadata VALUES ('minzoom', '0');
+                 INSERT INTO metadata VALUES ('maxzoom', '8');
+                 INSERT INTO metadata VALUES ('json', '{\"vector_layers\": [{\"id\": \"water\"}, {\"id\": \"building\"}]}');",
+            )
+            .unwrap();
+        connection
+            .execute(
+                "INSERT INTO tiles VALUES (8, 142, 162, ?1)",
+                params![compressed],
+            )
+            .unwrap();
+    }
+
+    let source = MbTilesSource::try_open(&path).unwrap();
+    assert_eq!(source.metadata().bounds, Some([5.9, 45.8, 10.5, 47.8]));
+    assert_eq!(source.max_zoom(), 8);
+    assert_eq!(source.metadata().layers, vec!["water", "building"]);
+    assert_eq!(
+        source.fetch(&TileId::new(8, 142, 93)).as_deref(),
+        Some(&data[..])
+    );
+    assert!(source.fetch(&TileId::new(8, 142, 94)).is_none());
+}
+
+#[test]
+fn write_and_read_tiles() {
+    let path =
+        std::env::temp_dir().join(format!("sailor-test-writer-{}.mbtiles", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let tile_id = TileId::new(8, 142, 93);
+    let data = include_bytes!("../../../data/8_142_93.pbf");
+    {
+        let writer =
+            MbTilesWriter::try_create(&path, &[("minzoom", "8".into()), ("maxzoom", "8".into())])
+                .unwrap();
+        assert!(!writer.contains(&tile_id));
+        assert!(writer.insert(&tile_id, data));
+        assert!(writer.contains(&tile_id));
+    }
+
+    let source = MbTilesSource::try_open(&path).unwrap();
+    assert!(source.contains(&tile_id));
+    assert!(!source.contains(&TileId::new(8, 142, 94)));
+    assert_eq!(source.min_zoom(), 8);
+    assert_eq!(source.fetch(&tile_id).as_deref(), Some(&data[..]));
+}
@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+
+use super::*;
+
+/// A `TileSource` which serves tiles from memory.
+///
+/// Mostly useful to feed a `TileCache` with known data without touching the network or the disk.
+#[derive(Default)]
+pub struct MemoryTileSource {
+    tiles: HashMap<TileId, Vec<u8>>,
+}
+
+impl MemoryTileSource {
+    /// Creates a new empty `MemoryTileSource`.
+    pub fn new() -> Self {
+        Self {
+            tiles: HashMap::new(),
+        }
+    }
+
+    /// Adds the raw MVT `data` of a tile to the source.
+    pub fn with_tile(mut self, tile_id: TileId, data: impl Into<Vec<u8>>) -> Self {
+        self.tiles.insert(tile_id, data.into());
+        self
+    }
+}
+
+impl TileSource for MemoryTileSource {
+    fn fetch(&self, tile_id: &TileId) -> Option<Vec<u8>> {
+        self.tiles.get(tile_id).cloned()
+    }
+
+    fn min_zoom(&self) -> u32 {
+        self.tiles.keys().map(|t| t.z).min().unwrap_or(0)
+    }
+
+    fn max_zoom(&self) -> u32 {
+        self.tiles.keys().map(|t| t.z).max().unwrap_or(0)
+    }
+
+    fn attribution(&self) -> Option<&str> {
+        None
+    }
+}
@@ -0,0 +1,38 @@
+mod directory;
+mod http;
+mod mbtiles;
+mod memory;
+mod pmtiles;
+
+pub use directory::*;
+pub use http::*;
+pub use mbtiles::*;
+pub use memory::*;
+pub use pmtiles::*;
+
+use crate::*;
+
+/// A provider of raw MVT tile data.
+///
+/// A `TileSource` is shared between all tile loader threads of a `TileCache`,
+/// so implementations have to be safe to use from multiple threads at once.
+pub trait TileSource: Send + Sync {
+    /// Fetches the raw MVT data of a tile.
+    ///
+    /// Returns `None` if the tile is not available from this source.
+    fn fetch(&self, tile_id: &TileId) -> Option<Vec<u8>>;
+
+    /// The smallest zoom level the source holds tiles for.
+    fn min_zoom(&self) -> u32;
+
+    /// The biggest zoom level the source holds tiles for.
+    fn max_zoom(&self) -> u32;
+
+    /// The attribution text which has to be displayed alongside the tiles of this source.
+    fn attribution(&self) -> Option<&str>;
+
+    /// Checks if `tile_id` lies in the zoom range of the source.
+    fn covers(&self, tile_id: &TileId) -> bool {
+        tile_id.z >= self.min_zoom() && tile_id.z <= self.max_zoom()
+    }
+}
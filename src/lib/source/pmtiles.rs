@@ -0,0 +1,500 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::Mutex;
+
+use super::*;
+
+/// The size of a PMTiles v3 header in bytes.
+const HEADER_SIZE: usize = 127;
+
+/// The compression schemes a PMTiles archive can use for its directories and tiles.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PmTilesCompression {
+    Unknown,
+    None,
+    Gzip,
+    Brotli,
+    Zstd,
+}
+
+impl From<u8> for PmTilesCompression {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => Self::None,
+            2 => Self::Gzip,
+            3 => Self::Brotli,
+            4 => Self::Zstd,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+/// The fixed size header at the start of every PMTiles v3 archive.
+#[derive(Debug, Clone)]
+pub struct PmTilesHeader {
+    pub root_directory: (u64, u64),
+    pub metadata: (u64, u64),
+    pub leaf_directories: (u64, u64),
+    pub tile_data: (u64, u64),
+    pub internal_compression: PmTilesCompression,
+    pub tile_compression: PmTilesCompression,
+    /// The type of the contained tiles. `1` means MVT.
+    pub tile_type: u8,
+    pub min_zoom: u8,
+    pub max_zoom: u8,
+    /// The area covered by the archive as `[west, south, east, north]` in degrees.
+    pub bounds: [f32; 4],
+}
+
+impl PmTilesHeader {
+    /// Parses a header from the first 127 bytes of an archive.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < HEADER_SIZE || &data[0..7] != b"PMTiles" {
+            log::error!("Not a PMTiles archive.");
+            return None;
+        }
+        if data[7] != 3 {
+            log::error!("PMTiles version {} is not supported.", data[7]);
+            return None;
+        }
+
+        let u64_at = |i: usize| {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&data[i..i + 8]);
+            u64::from_le_bytes(bytes)
+        };
+        let degrees_at = |i: usize| {
+            let mut bytes = [0u8; 4];
+            bytes.copy_from_slice(&data[i..i + 4]);
+            i32::from_le_bytes(bytes) as f32 / 10_000_000.0
+        };
+
+        Some(Self {
+            root_directory: (u64_at(8), u64_at(16)),
+            metadata: (u64_at(24), u64_at(32)),
+            leaf_directories: (u64_at(40), u64_at(48)),
+            tile_data: (u64_at(56), u64_at(64)),
+            internal_compression: data[97].into(),
+            tile_compression: data[98].into(),
+            tile_type: data[99],
+            min_zoom: data[100],
+            max_zoom: data[101],
+            bounds: [
+                degrees_at(102),
+                degrees_at(106),
+                degrees_at(110),
+                degrees_at(114),
+            ],
+        })
+    }
+}
+
+/// A single entry of a PMTiles directory.
+///
+/// An entry with a `run_length` of `0` points to a leaf directory.
+/// Otherwise it points to tile data which is shared by `run_length` consecutive tile ids.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PmTilesEntry {
+    pub tile_id: u64,
+    pub offset: u64,
+    pub length: u32,
+    pub run_length: u32,
+}
+
+/// Converts a `TileId` into the position of the tile on the PMTiles Hilbert curve.
+///
+/// Returns `None` for tiles outside of the world and zoom levels beyond what the ids can hold.
+pub fn zxy_to_tile_id(tile_id: &TileId) -> Option<u64> {
+    let z = tile_id.z as u64;
+    let mut x = tile_id.x as u64;
+    let mut y = tile_id.y as u64;
+    if z > 31 || x >= 1 << z || y >= 1 << z {
+        return None;
+    }
+
+    // All tiles of the lower zoom levels come first.
+    let mut id = ((1u64 << (2 * z)) - 1) / 3;
+
+    let mut s = (1u64 << z) >> 1;
+    while s > 0 {
+        let rx = (x & s > 0) as u64;
+        let ry = (y & s > 0) as u64;
+        id += s * s * ((3 * rx) ^ ry);
+
+        // Rotate the quadrant. Only the bits below `s` matter from here on, so wrapping is fine.
+        if ry == 0 {
+            if rx == 1 {
+                x = (s - 1).wrapping_sub(x);
+                y = (s - 1).wrapping_sub(y);
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+
+        s >>= 1;
+    }
+
+    Some(id)
+}
+
+/// Decodes a decompressed PMTiles directory.
+pub fn decode_directory(data: &[u8]) -> Option<Vec<PmTilesEntry>> {
+    let mut cursor = 0;
+    let mut next = || read_varint(data, &mut cursor);
+
+    // Every entry takes at least one byte for each of its four fields.
+    let count = next()? as usize;
+    if count > data.len() / 4 {
+        return None;
+    }
+    let mut entries = vec![
+        PmTilesEntry {
+            tile_id: 0,
+            offset: 0,
+            length: 0,
+            run_length: 0,
+        };
+        count
+    ];
+
+    // The tile ids are delta encoded.
+    let mut last_id = 0u64;
+    for entry in entries.iter_mut() {
+        last_id = last_id.checked_add(next()?)?;
+        entry.tile_id = last_id;
+    }
+    for entry in entries.iter_mut() {
+        entry.run_length = u32::try_from(next()?).ok()?;
+    }
+    for entry in entries.iter_mut() {
+        entry.length = u32::try_from(next()?).ok()?;
+    }
+    // An offset of `0` means the data directly follows the data of the previous entry.
+    for i in 0..count {
+        let value = next()?;
+        entries[i].offset = if value == 0 && i > 0 {
+            entries[i - 1]
+                .offset
+                .checked_add(entries[i - 1].length as u64)?
+        } else {
+            value.checked_sub(1)?
+        };
+    }
+
+    Some(entries)
+}
+
+/// Finds the entry of a directory which holds `tile_id`.
+///
+/// This is either the entry of the tile itself or the leaf directory containing it.
+pub fn find_entry(entries: &[PmTilesEntry], tile_id: u64) -> Option<&PmTilesEntry> {
+    let index = match entries.binary_search_by_key(&tile_id, |e| e.tile_id) {
+        Ok(index) => index,
+        Err(0) => return None,
+        Err(index) => index - 1,
+    };
+
+    let entry = &entries[index];
+    if entry.run_length == 0 || tile_id < entry.tile_id + entry.run_length as u64 {
+        Some(entry)
+    } else {
+        None
+    }
+}
+
+/// Reads a single LEB128 encoded varint.
+fn read_varint(data: &[u8], cursor: &mut usize) -> Option<u64> {
+    let mut value = 0u64;
+    let mut shift = 0;
+    loop {
+        let byte = *data.get(*cursor)?;
+        *cursor += 1;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+        shift += 7;
+        if shift >= 64 {
+            return None;
+        }
+    }
+}
+
+/// Decompresses a block of data of a PMTiles archive.
+fn decompress(data: Vec<u8>, compression: PmTilesCompression) -> Option<Vec<u8>> {
+    match compression {
+        PmTilesCompression::None => Some(data),
+        PmTilesCompression::Gzip => match decompress_tile_data(&data) {
+            Ok(decompressed) => Some(decompressed.into_owned()),
+            Err(e) => {
+                log::warn!("Could not decompress PMTiles data. Reason:\r\n{}", e);
+                None
+            }
+        },
+        compression => {
+            log::warn!(
+                "The PMTiles compression {:?} is currently not supported.",
+                compression
+            );
+            None
+        }
+    }
+}
+
+/// A `TileSource` which reads tiles from a local PMTiles v3 archive.
+pub struct PmTilesSource {
+    file: Mutex<File>,
+    header: PmTilesHeader,
+    root: Vec<PmTilesEntry>,
+    leaves: Mutex<HashMap<u64, Vec<PmTilesEntry>>>,
+    attribution: Option<String>,
+}
+
+impl PmTilesSource {
+    /// Tries to open the PMTiles archive at `path` for reading.
+    pub fn try_open(path: impl AsRef<Path>) -> Option<Self> {
+        let path = path.as_ref();
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) => {
+                log::error!("Unable to open PMTiles {:?}. Reason:\r\n{}", path, e);
+                return None;
+            }
+        };
+
+        let header = PmTilesHeader::parse(&read_range(&mut file, (0, HEADER_SIZE as u64))?)?;
+        if header.tile_type != 1 {
+            log::error!("PMTiles {:?} does not contain vector tiles.", path);
+            return None;
+        }
+
+        let root = decode_directory(&decompress(
+            read_range(&mut file, header.root_directory)?,
+            header.internal_compression,
+        )?)?;
+
+        let attribution = decompress(
+            read_range(&mut file, header.metadata)?,
+            header.internal_compression,
+        )
+        .and_then(|metadata| serde_json::from_slice::<serde_json::Value>(&metadata).ok())
+        .and_then(|metadata| metadata["attribution"].as_str().map(|a| a.to_string()));
+
+        Some(Self {
+            file: Mutex::new(file),
+            header,
+            root,
+            leaves: Mutex::new(HashMap::new()),
+            attribution,
+        })
+    }
+
+    /// Returns the header of the archive.
+    pub fn header(&self) -> &PmTilesHeader {
+        &self.header
+    }
+
+    /// Returns the leaf directory at `offset` in the leaf directory section.
+    fn leaf(&self, entry: &PmTilesEntry) -> Option<Vec<PmTilesEntry>> {
+        if let Some(leaf) = self.leaves.lock().unwrap().get(&entry.offset) {
+            return Some(leaf.clone());
+        }
+
+        let data = {
+            let mut file = self.file.lock().unwrap();
+            read_range(
+                &mut file,
+                (
+                    self.header.leaf_directories.0.checked_add(entry.offset)?,
+                    entry.length as u64,
+                ),
+            )?
+        };
+        let leaf = decode_directory(&decompress(data, self.header.internal_compression)?)?;
+
+        self.leaves
+            .lock()
+            .unwrap()
+            .insert(entry.offset, leaf.clone());
+        Some(leaf)
+    }
+}
+
+impl TileSource for PmTilesSource {
+    fn fetch(&self, tile_id: &TileId) -> Option<Vec<u8>> {
+        let id = zxy_to_tile_id(tile_id)?;
+
+        let mut entry = *find_entry(&self.root, id)?;
+        // The spec limits the depth of leaf directories, so this terminates quickly.
+        for _ in 0..4 {
+            if entry.run_length > 0 {
+                let data = {
+                    let mut file = self.file.lock().unwrap();
+                    read_range(
+                        &mut file,
+                        (
+                            self.header.tile_data.0.checked_add(entry.offset)?,
+                            entry.length as u64,
+                        ),
+                    )?
+                };
+                return decompress(data, self.header.tile_compression);
+            }
+            entry = *find_entry(&self.leaf(&entry)?, id)?;
+        }
+
+        log::warn!("Leaf directories for {} are nested too deep.", tile_id);
+        None
+    }
+
+    fn min_zoom(&self) -> u32 {
+        self.header.min_zoom as u32
+    }
+
+    fn max_zoom(&self) -> u32 {
+        self.header.max_zoom as u32
+    }
+
+    fn attribution(&self) -> Option<&str> {
+        self.attribution.as_deref()
+    }
+}
+
+/// Reads the `(offset, length)` range of a file.
+///
+/// Ranges beyond the end of the file are rejected before anything is allocated.
+fn read_range(file: &mut File, range: (u64, u64)) -> Option<Vec<u8>> {
+    let file_length = match file.metadata() {
+        Ok(metadata) => metadata.len(),
+        Err(e) => {
+            log::error!("Unable to read the size of PMTiles. Reason:\r\n{}", e);
+            return None;
+        }
+    };
+    if range
+        .0
+        .checked_add(range.1)
+        .is_none_or(|end| end > file_length)
+    {
+        log::error!(
+            "The range of {} bytes at {} is outside of the PMTiles of {} bytes.",
+            range.1,
+            range.0,
+            file_length
+        );
+        return None;
+    }
+
+    let mut buffer = vec![0; range.1 as usize];
+    match file
+        .seek(SeekFrom::Start(range.0))
+        .and_then(|_| file.read_exact(&mut buffer))
+    {
+        Ok(_) => Some(buffer),
+        Err(e) => {
+            log::error!(
+                "Unable to read {} bytes at {} from PMTiles. Reason:\r\n{}",
+                range.1,
+                range.0,
+                e
+            );
+            None
+        }
+    }
+}
+
+#[test]
+fn hilbert_tile_ids() {
+    assert_eq!(zxy_to_tile_id(&TileId::new(0, 0, 0)), Some(0));
+    assert_eq!(zxy_to_tile_id(&TileId::new(1, 0, 0)), Some(1));
+    assert_eq!(zxy_to_tile_id(&TileId::new(1, 0, 1)), Some(2));
+    assert_eq!(zxy_to_tile_id(&TileId::new(1, 1, 1)), Some(3));
+    assert_eq!(zxy_to_tile_id(&TileId::new(1, 1, 0)), Some(4));
+    assert_eq!(zxy_to_tile_id(&TileId::new(2, 0, 0)), Some(5));
+    assert_eq!(
+        zxy_to_tile_id(&TileId::new(12, 3423, 1763)),
+        Some(19_078_479)
+    );
+    assert!(zxy_to_tile_id(&TileId::new(31, (1 << 31) - 1, 0)).is_some());
+
+    // Tiles outside of the world must not alias to a tile inside of it.
+    assert_eq!(zxy_to_tile_id(&TileId::new(1, 2, 0)), None);
+    assert_eq!(zxy_to_tile_id(&TileId::new(2, 0, 4)), None);
+    assert_eq!(zxy_to_tile_id(&TileId::new(32, 0, 0)), None);
+    assert_eq!(zxy_to_tile_id(&TileId::new(40, 0, 0)), None);
+}
+
+#[test]
+fn reject_corrupt_directories() {
+    // A huge entry count must not be allocated.
+    assert_eq!(decode_directory(&[0xff, 0xff, 0xff, 0xff, 0x0f]), None);
+    // Truncated after the tile ids.
+    assert_eq!(decode_directory(&[2, 1, 1, 0, 0, 0, 0]), None);
+    // Tile ids which overflow.
+    let mut overflowing = vec![2];
+    for _ in 0..2 {
+        overflowing.extend(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]);
+    }
+    overflowing.extend(&[1, 1, 1, 1, 1, 1]);
+    assert_eq!(decode_directory(&overflowing), None);
+
+    assert_eq!(
+        decode_directory(&[1, 5, 1, 10, 1]),
+        Some(vec![PmTilesEntry {
+            tile_id: 5,
+            offset: 0,
+            length: 10,
+            run_length: 1,
+        }])
+    );
+}
+
+#[test]
+fn read_fixture_directories() {
+    let source = PmTilesSource::try_open("data/fixture.pmtiles").unwrap();
+    assert_eq!(source.header().bounds, [-180.0, -85.0, 180.0, 85.0]);
+    assert_eq!(source.max_zoom(), 8);
+    assert_eq!(source.attribution(), Some("sailor test fixture"));
+
+    // The root directory contains a run length entry and a leaf pointer.
+    assert_eq!(source.root.len(), 3);
+    assert_eq!(source.root[1].run_length, 4);
+    assert_eq!(source.root[2].run_length, 0);
+
+    assert_eq!(
+        source.fetch(&TileId::new(0, 0, 0)).as_deref(),
+        Some(&b"tile-0/0/0"[..])
+    );
+    // All tiles of zoom level 1 share the same data.
+    for (x, y) in &[(0, 0), (0, 1), (1, 0), (1, 1)] {
+        assert_eq!(
+            source.fetch(&TileId::new(1, *x, *y)).as_deref(),
+            Some(&b"tile-1"[..])
+        );
+    }
+    // Tiles from the leaf directory.
+    assert_eq!(
+        source.fetch(&TileId::new(2, 1, 2)).as_deref(),
+        Some(&b"tile-2/1/2"[..])
+    );
+    assert!(source.fetch(&TileId::new(2, 0, 0)).is_none());
+
+    let tile_id = TileId::new(8, 142, 93);
+    let data = source.fetch(&tile_id).unwrap();
+    let feature_collection =
+        std::sync::Arc::new(std::sync::RwLock::new(FeatureCollection::new(500)));
+    let tile = Tile::from_mbvt(&tile_id, &data, feature_collection, vec![]).unwrap();
+    assert_eq!(tile.extent(), 4096);
+}
+
+#[test]
+fn reject_ranges_beyond_the_file() {
+    let mut file = File::open("data/fixture.pmtiles").unwrap();
+    let length = file.metadata().unwrap().len();
+    assert!(read_range(&mut file, (0, HEADER_SIZE as u64)).is_some());
+    assert!(read_range(&mut file, (length - 1, 2)).is_none());
+    assert!(read_range(&mut file, (0, u64::MAX)).is_none());
+    assert!(read_range(&mut file, (u64::MAX, 1)).is_none());
+}
@@ -0,0 +1,65 @@
+use super::vector_tile::mod_Tile::GeomType;
+
+/// The reasons a tile can fail to load.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TileError {
+    /// The source could not provide the data of the tile.
+    Unavailable,
+    /// The compressed tile data could not be decompressed.
+    Decompress(String),
+    /// The protobuf data of the tile could not be decoded.
+    Decode(String),
+    /// The tile does not contain any layers.
+    NoLayers,
+    /// A feature has a geometry type which can not be drawn.
+    UnknownGeometryType,
+    /// A geometry contains a command which is unknown or not allowed for its geometry type.
+    BadGeometryCommand {
+        command: u32,
+        geometry_type: GeomType,
+    },
+    /// A geometry ended in the middle of a command or path.
+    TruncatedGeometry { geometry_type: GeomType },
+    /// A feature tag refers to a key which is not in the key table of its layer.
+    KeyIndexOutOfRange { layer: String, index: u32 },
+    /// A feature tag refers to a value which is not in the value table of its layer.
+    ValueIndexOutOfRange { layer: String, index: u32 },
+    /// The loader crashed while working the tile.
+    Panic(String),
+}
+
+impl std::fmt::Display for TileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TileError::Unavailable => write!(f, "The tile is not available from the source."),
+            TileError::Decompress(e) => write!(f, "Cannot decompress the tile data: {}", e),
+            TileError::Decode(e) => write!(f, "Cannot read the tile data: {}", e),
+            TileError::NoLayers => write!(f, "The tile does not contain any layers."),
+            TileError::UnknownGeometryType => write!(f, "A feature has an unknown geometry type."),
+            TileError::BadGeometryCommand {
+                command,
+                geometry_type,
+            } => write!(
+                f,
+                "Invalid geometry command {} in a {:?} geometry.",
+                command, geometry_type
+            ),
+            TileError::TruncatedGeometry { geometry_type } => {
+                write!(f, "A {:?} geometry ended unexpectedly.", geometry_type)
+            }
+            TileError::KeyIndexOutOfRange { layer, index } => write!(
+                f,
+                "Tag key index {} is out of range in layer '{}'.",
+                index, layer
+            ),
+            TileError::ValueIndexOutOfRange { layer, index } => write!(
+                f,
+                "Tag value index {} is out of range in layer '{}'.",
+                index, layer
+            ),
+            TileError::Panic(e) => write!(f, "The tile loader crashed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for TileError {}
@@ -1,8 +1,10 @@
+mod error;
 mod tile;
 mod tile_id;
 mod vector_tile;
 mod visible_tile;
 
+pub use error::*;
 pub use tile::*;
 pub use tile_id::*;
 pub use vector_tile::*;
@@ -26,95 +28,123 @@ pub struct Layer {
     pub features: Vec<(u32, Range<u32>)>,
 }
 
-fn area(path: &Path) -> f32 {
-    let mut points = path.points().to_vec();
-    points.push(*points.first().expect("Path contains no points!"));
+/// Computes twice the signed area of a ring with the surveyor's formula.
+///
+/// In tile coordinates exterior rings have a positive and interior rings a negative area.
+fn area(ring: &[Point]) -> f32 {
     let mut area = 0f32;
-    for i in 0..points.len() - 1 {
-        area += points[i].x * points[i + 1].y;
+    for (i, point) in ring.iter().enumerate() {
+        let next = ring[(i + 1) % ring.len()];
+        area += point.x * next.y - next.x * point.y;
     }
-    for i in 0..points.len() - 1 {
-        area -= points[i + 1].x * points[i].y;
-    }
-    area + points[points.len() - 1].x * points[1].y - points[points.len() - 1].y * points[1].x
+    area
 }
 
-fn parse_one_to_path(
+/// Reads the next delta encoded point of a geometry and moves the cursor to it.
+fn next_point(
     geometry_type: GeomType,
     geometry: &[u32],
     cursor: &mut usize,
     gcursor: &mut Point,
-) -> Path {
-    let mut builder = Path::builder();
+) -> Result<Point, TileError> {
+    match geometry.get(*cursor..*cursor + 2) {
+        Some(delta) => {
+            *cursor += 2;
+            let dx = ZigZag::<i32>::zigzag(&delta[0]) as f32;
+            let dy = ZigZag::<i32>::zigzag(&delta[1]) as f32;
+            *gcursor += vector(dx, dy);
+            Ok(*gcursor)
+        }
+        None => Err(TileError::TruncatedGeometry { geometry_type }),
+    }
+}
 
-    while *cursor < geometry.len() {
-        let value = geometry[*cursor];
-        *cursor += 1;
+/// Groups the rings of a polygon geometry into polygons by their winding order.
+///
+/// Every exterior ring starts a new polygon which owns all interior rings following it.
+/// Rings without an area are dropped.
+fn group_rings(rings: Vec<Vec<Point>>) -> Vec<Polygon> {
+    let mut polygons: Vec<Polygon> = vec![];
+    for ring in rings {
+        let area = area(&ring);
+        if area == 0.0 {
+            continue;
+        }
+        match polygons.last_mut() {
+            Some(polygon) if area < 0.0 => polygon.interiors.push(ring),
+            // A leading interior ring is invalid, but rather draw it as exterior than lose it.
+            _ => polygons.push(Polygon {
+                exterior: ring,
+                interiors: vec![],
+            }),
+        }
+    }
+    polygons
+}
+
+/// Decodes the geometry commands of a feature as described by the MVT specification.
+pub fn decode_geometry(geometry_type: GeomType, geometry: &[u32]) -> Result<Geometry, TileError> {
+    let bad_command = |command| TileError::BadGeometryCommand {
+        command,
+        geometry_type,
+    };
+
+    let mut cursor = 0;
+    let mut gcursor = point(0f32, 0f32);
+    // The points of a multipoint, or the lines or rings of the other types.
+    let mut parts: Vec<Vec<Point>> = vec![];
+
+    while cursor < geometry.len() {
+        let value = geometry[cursor];
+        cursor += 1;
 
         let count = value >> 3;
-        match value & 0x07 {
-            1 => {
+        match (value & 0x07, geometry_type) {
+            (1, GeomType::POINT) => {
                 for _ in 0..count {
-                    let dx = ZigZag::<i32>::zigzag(&geometry[*cursor]) as f32;
-                    *cursor += 1;
-                    let dy = ZigZag::<i32>::zigzag(&geometry[*cursor]) as f32;
-                    *cursor += 1;
-                    *gcursor += vector(dx, dy);
-                    builder.move_to(*gcursor);
-                }
-
-                if let GeomType::POINT = geometry_type {
-                    return builder.build();
+                    parts.push(vec![next_point(
+                        geometry_type,
+                        geometry,
+                        &mut cursor,
+                        &mut gcursor,
+                    )?]);
                 }
             }
-            2 => {
-                for _ in 0..count {
-                    let dx = ZigZag::<i32>::zigzag(&geometry[*cursor]) as f32;
-                    *cursor += 1;
-                    let dy = ZigZag::<i32>::zigzag(&geometry[*cursor]) as f32;
-                    *cursor += 1;
-                    *gcursor += vector(dx, dy);
-                    builder.line_to(*gcursor);
-                }
-                match geometry_type {
-                    GeomType::POINT => panic!("This is a bug. Please report it."),
-                    GeomType::LINESTRING => return builder.build(),
-                    _ => {}
-                }
+            // Every line and ring starts with a MoveTo to a single point.
+            (1, GeomType::LINESTRING) | (1, GeomType::POLYGON) if count == 1 => {
+                parts.push(vec![next_point(
+                    geometry_type,
+                    geometry,
+                    &mut cursor,
+                    &mut gcursor,
+                )?]);
             }
-            7 => {
-                builder.close();
-                match geometry_type {
-                    GeomType::POINT => panic!("This is a bug. Please report it."),
-                    GeomType::LINESTRING => panic!("This is a bug. Please report it."),
-                    GeomType::POLYGON => {}
-                    _ => panic!("This is a bug. Please report it."),
+            (2, GeomType::LINESTRING) | (2, GeomType::POLYGON) => {
+                let part = parts.last_mut().ok_or_else(|| bad_command(2))?;
+                for _ in 0..count {
+                    part.push(next_point(
+                        geometry_type,
+                        geometry,
+                        &mut cursor,
+                        &mut gcursor,
+                    )?);
                 }
             }
-            _ => {
-                panic!("This is a bug. Please report it.");
-            }
+            // Rings are closed implicitly.
+            (7, GeomType::POLYGON) if !parts.is_empty() => {}
+            (command, _) => return Err(bad_command(command)),
         }
     }
-    match geometry_type {
-        GeomType::POINT => panic!("This is a bug. Please report it."),
-        GeomType::LINESTRING => panic!("This is a bug. Please report it."),
-        GeomType::POLYGON => builder.build(),
-        _ => panic!("This is a bug. Please report it."),
-    }
-}
-
-pub fn geometry_commands_to_paths(geometry_type: GeomType, geometry: &[u32]) -> Vec<Path> {
-    let mut cursor = 0;
-    let mut c = point(0f32, 0f32);
-    let mut paths = Vec::new();
 
-    while cursor < geometry.len() {
-        let path = parse_one_to_path(geometry_type, geometry, &mut cursor, &mut c);
-        paths.push(path);
+    match geometry_type {
+        GeomType::POINT => Ok(Geometry::Points(parts.into_iter().flatten().collect())),
+        GeomType::LINESTRING if parts.iter().any(|line| line.len() < 2) => {
+            Err(TileError::TruncatedGeometry { geometry_type })
+        }
+        GeomType::LINESTRING => Ok(Geometry::Lines(parts)),
+        GeomType::POLYGON => Ok(Geometry::Polygons(group_rings(parts))),
+        GeomType::UNKNOWN => Err(TileError::UnknownGeometryType),
     }
-
-    paths
 }
 
 pub fn paths_to_drawable(
@@ -147,3 +177,99 @@ pub fn paths_to_drawable(
         }
     }
 }
+
+/// Encodes parts of absolute points as MVT geometry commands.
+#[cfg(test)]
+fn encode_geometry(parts: &[&[(i32, i32)]], close: bool) -> Vec<u32> {
+    let zigzag = |n: i32| ((n << 1) ^ (n >> 31)) as u32;
+    let mut commands = vec![];
+    let mut cursor = (0, 0);
+    for part in parts {
+        for (i, (x, y)) in part.iter().enumerate() {
+            match i {
+                0 => commands.push(9),
+                1 => commands.push(2 | (part.len() as u32 - 1) << 3),
+                _ => {}
+            }
+            commands.extend(&[zigzag(x - cursor.0), zigzag(y - cursor.1)]);
+            cursor = (*x, *y);
+        }
+        if close {
+            commands.push(15);
+        }
+    }
+    commands
+}
+
+#[test]
+fn parse_geometry_commands() {
+    // MoveTo(2, 2) LineTo(4, 4)
+    let line = [9, 4, 4, 10, 4, 4];
+    assert_eq!(
+        decode_geometry(GeomType::LINESTRING, &line).unwrap(),
+        Geometry::Lines(vec![vec![point(2.0, 2.0), point(4.0, 4.0)]])
+    );
+
+    // A point geometry must not contain a LineTo.
+    assert_eq!(
+        decode_geometry(GeomType::POINT, &[9, 4, 4, 10, 4, 4]).err(),
+        Some(TileError::BadGeometryCommand {
+            command: 2,
+            geometry_type: GeomType::POINT
+        })
+    );
+    // Command 5 does not exist.
+    assert_eq!(
+        decode_geometry(GeomType::POLYGON, &[13, 4, 4]).err(),
+        Some(TileError::BadGeometryCommand {
+            command: 5,
+            geometry_type: GeomType::POLYGON
+        })
+    );
+    // The LineTo is missing its second coordinate.
+    assert_eq!(
+        decode_geometry(GeomType::LINESTRING, &[9, 4, 4, 10, 4]).err(),
+        Some(TileError::TruncatedGeometry {
+            geometry_type: GeomType::LINESTRING
+        })
+    );
+}
+
+#[test]
+fn decode_multi_geometries() {
+    // MoveTo(3) with three points.
+    assert_eq!(
+        decode_geometry(GeomType::POINT, &[25, 4, 4, 2, 2, 3, 3]).unwrap(),
+        Geometry::Points(vec![point(2.0, 2.0), point(3.0, 3.0), point(1.0, 1.0)])
+    );
+
+    let lines = encode_geometry(&[&[(0, 0), (5, 5)], &[(10, 0), (10, 5), (15, 5)]], false);
+    assert_eq!(
+        decode_geometry(GeomType::LINESTRING, &lines).unwrap().len(),
+        2
+    );
+
+    // Two squares, the first one with a hole. Exterior rings are clockwise in tile space.
+    let exterior = [(0, 0), (10, 0), (10, 10), (0, 10)];
+    let hole = [(2, 2), (2, 8), (8, 8), (8, 2)];
+    let second = [(20, 0), (30, 0), (30, 10), (20, 10)];
+    let polygons = encode_geometry(&[&exterior, &hole, &second], true);
+    let to_points = |ring: &[(i32, i32)]| {
+        ring.iter()
+            .map(|(x, y)| point(*x as f32, *y as f32))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        decode_geometry(GeomType::POLYGON, &polygons).unwrap(),
+        Geometry::Polygons(vec![
+            Polygon {
+                exterior: to_points(&exterior),
+                interiors: vec![to_points(&hole)],
+            },
+            Polygon {
+                exterior: to_points(&second),
+                interiors: vec![],
+            },
+        ])
+    );
+}
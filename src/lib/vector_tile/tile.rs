@@ -61,26 +61,32 @@ pub struct Tile {
     stats: TileStats,
 }
 
+/// The names of all layers which are drawn, in the order of their layer numbers.
+pub const KNOWN_LAYERS: &[&str] = &[
+    "landcover",
+    "water",
+    "waterway",
+    "landuse",
+    "mountain_peak",
+    "park",
+    "boundary",
+    "aeroway",
+    "transportation",
+    "building",
+    "water_name",
+    "transportation_name",
+    "place",
+    "housenumber",
+    "poi",
+    "aerodrome_label",
+];
+
 pub fn layer_num(name: &str) -> u32 {
-    match name {
-        "landcover" => 0,
-        "water" => 1,
-        "waterway" => 2,
-        "landuse" => 3,
-        "mountain_peak" => 4,
-        "park" => 5,
-        "boundary" => 6,
-        "aeroway" => 7,
-        "transportation" => 8,
-        "building" => 9,
-        "water_name" => 10,
-        "transportation_name" => 11,
-        "place" => 12,
-        "housenumber" => 13,
-        "poi" => 14,
-        "aerodrome_label" => 15,
-        _ => 19,
-    }
+    KNOWN_LAYERS
+        .iter()
+        .position(|layer| *layer == name)
+        .map(|i| i as u32)
+        .unwrap_or(19)
 }
 
 impl Tile {
@@ -88,21 +94,27 @@ impl Tile {
     ///
     /// Creates all the data necessecary to render the MBVT.
     /// This includes vertex and index buffers.
+    /// Gzip or zlib compressed data is decompressed first.
+    ///
+    /// Returns an error if the tile data is malformed.
     pub fn from_mbvt(
         tile_id: &TileId,
         pbf_data: &[u8],
         feature_collection: Arc<RwLock<FeatureCollection>>,
         selection_tags: Vec<String>,
-    ) -> Self {
+    ) -> Result<Self, TileError> {
+        let pbf_data =
+            decompress_tile_data(pbf_data).map_err(|e| TileError::Decompress(e.to_string()))?;
+
         // Read tile data from the pbf data.
         let mut reader = BytesReader::from_bytes(&pbf_data);
         let tile = super::vector_tile::Tile::from_reader(&mut reader, &pbf_data)
-            .expect("Cannot read Tile object.");
+            .map_err(|e| TileError::Decode(e.to_string()))?;
 
         let mut objects = Vec::new();
         let mut mesh: VertexBuffers<Vertex, u32> = VertexBuffers::with_capacity(10_000, 10_000);
         let mut builder = MeshBuilder::new(&mut mesh, LayerVertexCtor::new(tile_id, 1.0));
-        let extent = tile.layers[0].extent as u16;
+        let extent = tile.layers.first().ok_or(TileError::NoLayers)?.extent as u16;
         let mut features = vec![];
         let mut text = vec![];
 
@@ -119,35 +131,26 @@ impl Tile {
 
             // Preevaluate the selectors and group features by the selector they belong to.
             for feature in &layer.features {
-                let (selector, tags) = Self::classify(&layer, &feature, &selection_tags);
+                let (selector, tags) = Self::classify(&layer, &feature, &selection_tags)?;
 
-                let paths = geometry_commands_to_paths(feature.type_pb, &feature.geometry);
+                let geometry = decode_geometry(feature.type_pb, &feature.geometry)?;
+                if geometry.is_empty() {
+                    continue;
+                }
+                let paths = geometry.to_paths();
 
+                // Label every part of the object.
                 if let Some(tag) = tags.get("name:en") {
-                    let point = paths[0].points()[0];
-                    text.push((
-                        (point.x / extent as f32, point.y / extent as f32),
-                        tag.clone(),
-                    ));
+                    for point in geometry.label_positions() {
+                        text.push((
+                            (point.x / extent as f32, point.y / extent as f32),
+                            tag.clone(),
+                        ));
+                    }
                 }
 
-                // If we have a valid object at hand, insert it into the object list
-
-                let object_type = match feature.type_pb {
-                    GeomType::POLYGON => Some(ObjectType::Polygon),
-                    GeomType::LINESTRING => Some(ObjectType::Line),
-                    GeomType::POINT => Some(ObjectType::Point),
-                    _ => None,
-                };
-
-                if let Some(ot) = object_type {
-                    objects.push(Object::new_with_tags(
-                        selector.clone(),
-                        paths[0].points().to_vec(),
-                        tags,
-                        ot,
-                    ));
-                }
+                // Insert the object with its full geometry into the object list.
+                objects.push(Object::new_with_tags(selector.clone(), geometry, tags));
 
                 if let Some(value) = map.get_mut(&selector) {
                     value.push((feature.type_pb, paths));
@@ -229,7 +232,7 @@ impl Tile {
         //     }
         // });
 
-        Self {
+        Ok(Self {
             tile_id: *tile_id,
             mesh,
             extent,
@@ -238,7 +241,7 @@ impl Tile {
             collider,
             text,
             stats,
-        }
+        })
     }
 
     pub fn extent(&self) -> u16 {
@@ -313,7 +316,13 @@ impl Tile {
             )
             .expect("This is a bug. Please report it.");
 
-        let object = Object::new(selector, path.points().to_vec(), ObjectType::Polygon);
+        let object = Object::new(
+            selector,
+            Geometry::Polygons(vec![Polygon {
+                exterior: path.points().to_vec(),
+                interiors: vec![],
+            }]),
+        );
 
         (
             current_feature_id,
@@ -327,21 +336,31 @@ impl Tile {
         layer: &vector_tile::mod_Tile::Layer,
         feature: &vector_tile::mod_Tile::Feature,
         selection_tags: &[String],
-    ) -> (Selector, HashMap<String, String>) {
+    ) -> Result<(Selector, HashMap<String, String>), TileError> {
         let mut selector = Selector::new()
             .with_type("layer".to_string())
             .with_any("name".to_string(), layer.name.to_string());
 
         let mut tags = HashMap::new();
 
-        for tag in feature.tags.chunks(2) {
-            let key = layer.keys[tag[0] as usize].to_string();
-            let value = layer.values[tag[1] as usize].clone();
+        for tag in feature.tags.chunks_exact(2) {
+            let key = layer
+                .keys
+                .get(tag[0] as usize)
+                .ok_or_else(|| TileError::KeyIndexOutOfRange {
+                    layer: layer.name.to_string(),
+                    index: tag[0],
+                })?
+                .to_string();
+            let value = layer.values.get(tag[1] as usize).ok_or_else(|| {
+                TileError::ValueIndexOutOfRange {
+                    layer: layer.name.to_string(),
+                    index: tag[1],
+                }
+            })?;
             match &key[..] {
                 "class" => {
-                    selector
-                        .classes
-                        .push(value.string_value.clone().unwrap().to_string());
+                    selector.classes.push(value.to_string());
                 }
                 _ => {
                     if selection_tags.contains(&key) {
@@ -353,7 +372,7 @@ impl Tile {
             }
         }
 
-        (selector, tags)
+        Ok((selector, tags))
     }
 }
 
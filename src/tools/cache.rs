@@ -0,0 +1,145 @@
+use osm::*;
+use serde_derive::Deserialize;
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use structopt::StructOpt;
+
+/// Inspects and maintains the disk cache of downloaded tiles.
+#[derive(Debug, StructOpt)]
+#[structopt(name = "sailor-cache")]
+enum Command {
+    /// Prints the number and size of the cached tiles per zoom level.
+    Info,
+    /// Removes expired tiles and the least recently used tiles beyond the size limit, which keeps prefetched tiles.
+    Prune {
+        /// Removes tiles fetched more than this many days ago instead of the configured limit.
+        #[structopt(long)]
+        max_age_days: Option<u64>,
+        /// Prunes the cache to this size in MiB instead of the configured limit.
+        #[structopt(long)]
+        max_size: Option<u64>,
+    },
+    /// Checks that the cache index matches the tile files on disk.
+    Verify {
+        /// Fixes the index by dropping broken tiles and adopting unknown tile files.
+        #[structopt(long)]
+        repair: bool,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+struct General {
+    data_root: String,
+}
+
+/// The part of the sailor configuration the cache tool needs.
+#[derive(Debug, Deserialize)]
+struct Config {
+    general: General,
+    cache: CacheConfig,
+}
+
+impl Config {
+    fn new() -> Result<Self, config::ConfigError> {
+        let mut s = config::Config::new();
+        s.merge(config::File::with_name("config/default"))?;
+        s.merge(config::File::with_name("config/local").required(false))?;
+        s.try_into()
+    }
+}
+
+fn main() {
+    pretty_env_logger::init();
+
+    let command = Command::from_args();
+    let config = Config::new().expect("Config could not be loaded.");
+    let cache =
+        DiskCache::open(config.general.data_root.clone()).with_policy(config.cache.policy());
+
+    match command {
+        Command::Info => info(&cache),
+        Command::Prune {
+            max_age_days,
+            max_size,
+        } => {
+            let limits = CacheConfig {
+                max_age_days: max_age_days.unwrap_or(config.cache.max_age_days),
+                max_size: max_size.unwrap_or(config.cache.max_size),
+                ..config.cache
+            };
+            let cache = cache.with_policy(limits.policy());
+            let report = cache.prune();
+            println!(
+                "Removed {} tiles ({:.1} MiB), {:.1} MiB remain.",
+                report.removed_tiles,
+                mib(report.removed_bytes),
+                mib(cache.total_bytes())
+            );
+        }
+        Command::Verify { repair } => {
+            let report = cache.verify(repair);
+            let problems = [
+                ("missing", &report.missing),
+                ("with the wrong size", &report.size_mismatch),
+                ("not in the index", &report.orphaned),
+            ];
+            for (problem, tiles) in problems.iter() {
+                if !tiles.is_empty() {
+                    println!("{} tiles {}:", tiles.len(), problem);
+                    for tile_id in tiles.iter() {
+                        println!("    {}", tile_id);
+                    }
+                }
+            }
+
+            if report.is_ok() {
+                println!("The cache is consistent.");
+            } else if repair {
+                println!("The cache index was repaired.");
+            } else {
+                println!("Run the command again with --repair to fix the index.");
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+/// Prints an overview of the cache contents.
+fn info(cache: &DiskCache) {
+    let entries = cache.entries();
+    let mut per_zoom = BTreeMap::new();
+    for entry in &entries {
+        let (count, bytes) = per_zoom.entry(entry.tile_id.z).or_insert((0, 0));
+        *count += 1;
+        *bytes += entry.size;
+    }
+    for (z, (count, bytes)) in per_zoom {
+        println!("Zoom {:>2}: {:>8} tiles {:>10.1} MiB", z, count, mib(bytes));
+    }
+
+    let expired = entries
+        .iter()
+        .filter(|entry| cache.is_expired(entry))
+        .count();
+    println!(
+        "{} tiles in total, {:.1} MiB in {:?}.",
+        entries.len(),
+        mib(cache.total_bytes()),
+        cache.root().join("cache")
+    );
+    println!("{} tiles are due for revalidation.", expired);
+
+    if let Some(oldest) = entries.iter().map(|entry| entry.fetched_at).min() {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        println!(
+            "The oldest tile was fetched {} days ago.",
+            now.saturating_sub(oldest) / (24 * 60 * 60)
+        );
+    }
+}
+
+fn mib(bytes: u64) -> f64 {
+    bytes as f64 / 1024.0 / 1024.0
+}
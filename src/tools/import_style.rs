@@ -0,0 +1,50 @@
+use osm::*;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Converts a Mapbox GL or MapLibre style JSON into a sailor stylesheet.
+#[derive(Debug, StructOpt)]
+#[structopt(name = "sailor-import-style")]
+struct Options {
+    /// The style JSON to convert.
+    #[structopt(parse(from_os_str))]
+    input: PathBuf,
+    /// The stylesheet to write. Defaults to the input file with a `.css` extension.
+    #[structopt(short, long, parse(from_os_str))]
+    output: Option<PathBuf>,
+}
+
+fn main() {
+    pretty_env_logger::init();
+
+    let options = Options::from_args();
+    let output = options
+        .output
+        .clone()
+        .unwrap_or_else(|| options.input.with_extension("css"));
+
+    let json = std::fs::read_to_string(&options.input).unwrap_or_else(|err| {
+        eprintln!("Cannot read {:?}: {}", options.input, err);
+        std::process::exit(1);
+    });
+    let import = import_mapbox_style(&json).unwrap_or_else(|err| {
+        eprintln!("{:?} is not a valid style: {}", options.input, err);
+        std::process::exit(1);
+    });
+
+    if let Err(err) = std::fs::write(&output, serialize_styles(&import.rules)) {
+        eprintln!("Cannot write {:?}: {}", output, err);
+        std::process::exit(1);
+    }
+
+    if !import.unsupported.is_empty() {
+        println!(
+            "{} parts of the style are not supported:",
+            import.unsupported.len()
+        );
+        for unsupported in &import.unsupported {
+            println!("    {}", unsupported);
+        }
+    }
+    println!("Wrote {} rules to {:?}.", import.rules.len(), output);
+}
@@ -0,0 +1,405 @@
+use osm::*;
+use serde_derive::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use structopt::StructOpt;
+
+/// The average size of a tile used to estimate the download size.
+const ESTIMATED_TILE_SIZE: usize = 32 * 1024;
+
+/// Downloads all tiles of a region or along a route for offline use.
+#[derive(Debug, StructOpt)]
+#[structopt(name = "sailor-prefetch")]
+struct Options {
+    /// The region to download as `min_lat min_lon max_lat max_lon` in degrees.
+    #[structopt(
+        long,
+        number_of_values = 4,
+        allow_hyphen_values = true,
+        required_unless = "gpx"
+    )]
+    bbox: Vec<f32>,
+    /// A GPX file whose track, route and waypoints are downloaded. Points are connected in file order.
+    #[structopt(long, parse(from_os_str))]
+    gpx: Option<PathBuf>,
+    /// The distance around the GPX points which is downloaded as well, in nautical miles.
+    #[structopt(long, default_value = "2")]
+    buffer: f32,
+    /// The smallest zoom level to download.
+    #[structopt(long, default_value = "0")]
+    min_zoom: u32,
+    /// The biggest zoom level to download.
+    #[structopt(long, default_value = "14")]
+    max_zoom: u32,
+    /// Writes the tiles into this MBTiles archive instead of the disk cache.
+    #[structopt(long, parse(from_os_str))]
+    mbtiles: Option<PathBuf>,
+    /// The maximum number of requests per second sent to the tile server.
+    #[structopt(long, default_value = "10")]
+    rate: f32,
+    /// Only prints the number of tiles and the estimated download size.
+    #[structopt(long)]
+    dry_run: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct General {
+    data_root: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Source {
+    url: String,
+    key: String,
+    attribution: String,
+}
+
+/// The part of the sailor configuration the prefetcher needs.
+#[derive(Debug, Deserialize)]
+struct Config {
+    general: General,
+    source: Source,
+    cache: CacheConfig,
+}
+
+impl Config {
+    fn new() -> Result<Self, config::ConfigError> {
+        let mut s = config::Config::new();
+        s.merge(config::File::with_name("config/default"))?;
+        s.merge(config::File::with_name("config/local").required(false))?;
+        s.try_into()
+    }
+}
+
+/// Where the downloaded tiles are stored.
+enum Target {
+    DiskCache(PathBuf),
+    MbTiles(MbTilesWriter),
+    /// An archive which is only read during a dry run, `None` if it does not exist yet.
+    DryRunMbTiles(Option<MbTilesSource>),
+}
+
+impl Target {
+    fn contains(&self, tile_id: &TileId) -> bool {
+        match self {
+            Target::DiskCache(root) => tile_cache_path(root, tile_id).exists(),
+            Target::MbTiles(writer) => writer.contains(tile_id),
+            Target::DryRunMbTiles(source) => source
+                .as_ref()
+                .is_some_and(|source| source.contains(tile_id)),
+        }
+    }
+
+    fn download(&self, source: &HttpTileSource, tile_id: &TileId) -> bool {
+        match self {
+            // The HTTP source keeps a copy of every tile in the disk cache.
+            Target::DiskCache(_) => {
+                let fetched = source.fetch(tile_id).is_some();
+                if fetched {
+                    source.disk_cache().mark_prefetched(tile_id);
+                }
+                fetched
+            }
+            Target::MbTiles(writer) => fetch_tile_from_server(&source.url(tile_id), tile_id)
+                .is_some_and(|data| writer.insert(tile_id, &data)),
+            Target::DryRunMbTiles(_) => unreachable!("Dry runs do not download tiles."),
+        }
+    }
+}
+
+fn main() {
+    pretty_env_logger::init();
+
+    let options = Options::from_args();
+    let config = Config::new().expect("Config could not be loaded.");
+    prefetch(&options, &config);
+}
+
+/// Downloads the tiles selected by `options`, or only reports how many are missing in a dry run.
+fn prefetch(options: &Options, config: &Config) {
+    let (tiles, bounds) = match &options.gpx {
+        Some(gpx) => {
+            let points = read_gpx_points(gpx);
+            if points.is_empty() {
+                eprintln!("{:?} does not contain any points.", gpx);
+                std::process::exit(1);
+            }
+            (
+                tiles_along_route(&points, options.buffer, options.min_zoom..=options.max_zoom),
+                route_bounds(&points),
+            )
+        }
+        None => {
+            let b = [
+                options.bbox[0],
+                options.bbox[1],
+                options.bbox[2],
+                options.bbox[3],
+            ];
+            let mut tiles = BTreeSet::new();
+            for z in options.min_zoom..=options.max_zoom {
+                tiles.extend(TileField::from_bounds(b[0], b[1], b[2], b[3], z).iter());
+            }
+            (tiles, b)
+        }
+    };
+
+    // The archive is read before it is written, so a resumed download keeps what it covers already.
+    let existing = options
+        .mbtiles
+        .as_ref()
+        .filter(|path| path.exists())
+        .and_then(MbTilesSource::try_open);
+
+    let target = match &options.mbtiles {
+        // A dry run must not write to the archive or leave an empty one behind.
+        Some(_) if options.dry_run => Target::DryRunMbTiles(existing),
+        Some(path) => {
+            // MBTiles stores the bounds as `west,south,east,north`.
+            let mut bounds = [bounds[1], bounds[0], bounds[3], bounds[2]];
+            let mut zoom_range = (options.min_zoom, options.max_zoom);
+            if let Some(metadata) = existing.as_ref().map(MbTilesSource::metadata) {
+                if let Some(old) = metadata.bounds {
+                    bounds = union_bounds(bounds, old);
+                }
+                zoom_range.0 = zoom_range.0.min(metadata.min_zoom.unwrap_or(zoom_range.0));
+                zoom_range.1 = zoom_range.1.max(metadata.max_zoom.unwrap_or(zoom_range.1));
+            }
+            drop(existing);
+
+            let metadata = [
+                ("name", path.to_string_lossy().to_string()),
+                ("format", "pbf".to_string()),
+                (
+                    "bounds",
+                    format!("{},{},{},{}", bounds[0], bounds[1], bounds[2], bounds[3]),
+                ),
+                ("minzoom", zoom_range.0.to_string()),
+                ("maxzoom", zoom_range.1.to_string()),
+                ("attribution", config.source.attribution.clone()),
+            ];
+            match MbTilesWriter::try_create(path, &metadata) {
+                Some(writer) => Target::MbTiles(writer),
+                None => {
+                    eprintln!("Could not open {:?} for writing.", path);
+                    std::process::exit(1);
+                }
+            }
+        }
+        None => Target::DiskCache(config.general.data_root.clone().into()),
+    };
+
+    let missing = tiles
+        .iter()
+        .filter(|tile_id| !target.contains(tile_id))
+        .copied()
+        .collect::<Vec<_>>();
+
+    if options.dry_run {
+        let mut per_zoom = BTreeMap::new();
+        for tile_id in &tiles {
+            *per_zoom.entry(tile_id.z).or_insert(0) += 1;
+        }
+        for (z, count) in per_zoom {
+            println!("Zoom {:>2}: {:>8} tiles", z, count);
+        }
+        println!(
+            "{} tiles in total, {} already available offline.",
+            tiles.len(),
+            tiles.len() - missing.len()
+        );
+        println!(
+            "Estimated download size: {:.1} MiB",
+            (missing.len() * ESTIMATED_TILE_SIZE) as f64 / 1024.0 / 1024.0
+        );
+        return;
+    }
+
+    // Opening the disk cache creates its index, so a dry run must not do it.
+    // Tiles prefetched into the disk cache are stored like the tiles sailor downloads itself.
+    let source = HttpTileSource::new(
+        config.source.url.clone(),
+        config.source.key.clone(),
+        config.general.data_root.clone(),
+    )
+    .with_cache_policy(config.cache.policy());
+
+    // Tiles which were cached already are kept for offline use as well.
+    if let Target::DiskCache(_) = target {
+        let missing = missing.iter().collect::<BTreeSet<_>>();
+        for tile_id in tiles.iter().filter(|tile_id| !missing.contains(tile_id)) {
+            source.disk_cache().mark_prefetched(tile_id);
+        }
+    }
+
+    let interval = Duration::from_secs_f32(1.0 / options.rate.max(0.01));
+    let mut last_request = Instant::now() - interval;
+    let mut failed = vec![];
+    for (i, tile_id) in missing.iter().enumerate() {
+        let elapsed = last_request.elapsed();
+        if elapsed < interval {
+            std::thread::sleep(interval - elapsed);
+        }
+        last_request = Instant::now();
+
+        if !target.download(&source, tile_id) {
+            failed.push(*tile_id);
+        }
+
+        println!("[{}/{}] {}", i + 1, missing.len(), tile_id);
+    }
+
+    println!(
+        "Downloaded {} tiles, {} were already available offline.",
+        missing.len() - failed.len(),
+        tiles.len() - missing.len()
+    );
+
+    if !failed.is_empty() {
+        eprintln!(
+            "{} tiles could not be downloaded. Run the command again to retry them:",
+            failed.len()
+        );
+        for tile_id in failed {
+            eprintln!("    {}", tile_id);
+        }
+        std::process::exit(1);
+    }
+}
+
+/// Reads the lat/lon of all track points, route points and waypoints of a GPX file in file order.
+fn read_gpx_points(path: &Path) -> Vec<(f32, f32)> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            eprintln!("Unable to read {:?}. Reason:\r\n{}", path, e);
+            std::process::exit(1);
+        }
+    };
+
+    contents
+        .split('<')
+        .filter(|element| {
+            ["trkpt", "rtept", "wpt"].iter().any(|name| {
+                element.starts_with(name)
+                    && element[name.len()..].starts_with(|c: char| c.is_whitespace())
+            })
+        })
+        .filter_map(|element| Some((attribute(element, "lat")?, attribute(element, "lon")?)))
+        .collect()
+}
+
+/// Parses the numeric attribute `name` of an XML element.
+fn attribute(element: &str, name: &str) -> Option<f32> {
+    let element = &element[..element.find('>').unwrap_or(element.len())];
+    let start = element
+        .match_indices(name)
+        .map(|(i, _)| i)
+        .find(|i| element[..*i].ends_with(char::is_whitespace))?;
+    let value = element[start + name.len()..]
+        .trim_start()
+        .strip_prefix('=')?;
+    let value = value.trim_start();
+    let quote = value.chars().next()?;
+    let value = &value[1..];
+    value[..value.find(quote)?].trim().parse().ok()
+}
+
+/// Collects all tiles within `buffer` nautical miles of the route through `points`.
+fn tiles_along_route(
+    points: &[(f32, f32)],
+    buffer: f32,
+    zoom_levels: std::ops::RangeInclusive<u32>,
+) -> BTreeSet<TileId> {
+    // One nautical mile is one minute of latitude.
+    let buffer_lat = (buffer / 60.0).max(0.0001);
+
+    // Sample the legs densely enough for the buffers around the samples to overlap.
+    let mut samples = vec![points[0]];
+    for leg in points.windows(2) {
+        let (from, to) = (leg[0], leg[1]);
+        let distance = ((to.0 - from.0).powi(2) + (to.1 - from.1).powi(2)).sqrt();
+        let steps = (distance / buffer_lat).ceil().max(1.0) as usize;
+        for step in 1..=steps {
+            let t = step as f32 / steps as f32;
+            samples.push((from.0 + (to.0 - from.0) * t, from.1 + (to.1 - from.1) * t));
+        }
+    }
+
+    let mut tiles = BTreeSet::new();
+    for z in zoom_levels {
+        for (lat, lon) in &samples {
+            let buffer_lon = buffer_lat / lat.to_radians().cos().max(0.01);
+            tiles.extend(
+                TileField::from_bounds(
+                    lat - buffer_lat,
+                    lon - buffer_lon,
+                    lat + buffer_lat,
+                    lon + buffer_lon,
+                    z,
+                )
+                .iter(),
+            );
+        }
+    }
+    tiles
+}
+
+/// Returns the bounds covering both `a` and `b`, which are `[west, south, east, north]`.
+fn union_bounds(a: [f32; 4], b: [f32; 4]) -> [f32; 4] {
+    [
+        a[0].min(b[0]),
+        a[1].min(b[1]),
+        a[2].max(b[2]),
+        a[3].max(b[3]),
+    ]
+}
+
+/// Returns the bounds of a route as `[min_lat, min_lon, max_lat, max_lon]`.
+fn route_bounds(points: &[(f32, f32)]) -> [f32; 4] {
+    points
+        .iter()
+        .fold([f32::MAX, f32::MAX, f32::MIN, f32::MIN], |b, (lat, lon)| {
+            [
+                b[0].min(*lat),
+                b[1].min(*lon),
+                b[2].max(*lat),
+                b[3].max(*lon),
+            ]
+        })
+}
+
+#[test]
+fn dry_run_leaves_the_cache_untouched() {
+    let data_root = std::env::temp_dir().join(format!("sailor-prefetch-{}", std::process::id()));
+    let config = Config {
+        general: General {
+            data_root: data_root.to_string_lossy().to_string(),
+        },
+        source: Source {
+            url: "https://tiles.example.com/{z}/{x}/{y}.pbf".into(),
+            key: String::new(),
+            attribution: String::new(),
+        },
+        cache: CacheConfig {
+            max_age_days: 0,
+            max_size: 0,
+            compress: false,
+        },
+    };
+    let options = Options::from_iter(&[
+        "sailor-prefetch",
+        "--bbox",
+        "47.2",
+        "8.5",
+        "47.3",
+        "8.6",
+        "--max-zoom",
+        "4",
+        "--dry-run",
+    ]);
+
+    prefetch(&options, &config);
+    assert!(!data_root.exists());
+}